@@ -1,66 +1,55 @@
 use std::{collections::HashMap, path::Path};
 
 use crate::types::{Account, TestUnit};
+use ethereum_rust_blockchain::{add_block, notifier::ChainNotifier};
 use ethereum_rust_core::{
     rlp::decode::RLPDecode,
     rlp::encode::RLPEncode,
     types::{Account as CoreAccount, Block as CoreBlock, Transaction as CoreTransaction},
     Address,
 };
-use ethereum_rust_evm::{
-    apply_state_transitions, evm_state, execute_tx, process_withdrawals, EvmState, SpecId,
-};
 use ethereum_rust_storage::{EngineType, Store};
 
 pub fn execute_test(test_key: &str, test: &TestUnit, check_post_state: bool) {
     // Build pre state
-    let mut evm_state = build_evm_state_from_prestate(&test.pre);
-    let blocks = test.blocks.clone();
-    // Execute all txs in the test unit
-    for block in blocks.iter() {
-        let block_header = block.header().clone();
-        for (tx_index, transaction) in block.transactions().iter().enumerate() {
+    let store = build_store_from_prestate(test);
+    let notifier = ChainNotifier::default();
+    // Import all blocks in the test unit
+    for block_fixture in test.blocks.iter() {
+        let expects_exception = block_fixture.expect_exception.is_some();
+        // Blocks which can't be decoded are already checked by `validate_test`
+        if CoreBlock::decode(block_fixture.rlp.as_ref()).is_err() {
+            continue;
+        }
+        for transaction in block_fixture.transactions() {
             assert_eq!(
-                transaction.clone().sender,
-                CoreTransaction::from(transaction.clone()).sender(),
+                Some(transaction.clone().sender),
+                CoreTransaction::from(transaction.clone()).sender().ok(),
                 "Expected sender address differs from derived sender address on test: {}",
                 test_key
             );
-            let execution_result = execute_tx(
-                &transaction.clone().into(),
-                &block_header.clone().into(),
-                &mut evm_state,
-                SpecId::CANCUN,
-            );
-            // If this is the last tx in a block that is expecting an exception then we must make sure it fails
-            // TODO: Check that the exception is the one in the test unit
-            let is_last_tx = block.transactions().len() == tx_index + 1;
-            if block.expect_exception.is_some() && is_last_tx {
-                assert!(
-                    execution_result.is_err(),
-                    "Expected transaction execution to fail on test: {}",
-                    test_key
-                )
-            } else {
-                assert!(
-                    execution_result.is_ok(),
-                    "Transaction execution failed on test: {} with error: {}",
-                    test_key,
-                    execution_result.unwrap_err()
-                )
-            }
         }
-        // Apply state transitions
-        apply_state_transitions(&mut evm_state).expect("Failed to update DB state");
-        // Process withdrawals (if present)
-        if let Some(withdrawals) = block.withdrawals() {
-            process_withdrawals(evm_state.database(), withdrawals)
-                .expect("DB error when processing withdrawals")
+        let block: CoreBlock = block_fixture.block().clone().into();
+        let result = add_block(&block, &store, &notifier);
+        // TODO: Check that the exception is the one in the test unit
+        if expects_exception {
+            assert!(
+                result.is_err(),
+                "Expected block import to fail on test: {}",
+                test_key
+            )
+        } else {
+            assert!(
+                result.is_ok(),
+                "Block import failed on test: {} with error: {}",
+                test_key,
+                result.unwrap_err()
+            )
         }
     }
     // Check post state
     if check_post_state {
-        check_poststate_against_db(&test.post_state, evm_state.database())
+        check_poststate_against_db(&test.post_state, &store)
     }
 }
 
@@ -95,17 +84,32 @@ pub fn validate_test(test: &TestUnit) {
     }
 }
 
-/// Creates an in-memory DB for evm execution and loads the prestate accounts
-pub fn build_evm_state_from_prestate(pre: &HashMap<Address, Account>) -> EvmState {
+/// Creates an in-memory DB with the test's genesis block as the latest block and loads the prestate accounts
+pub fn build_store_from_prestate(test: &TestUnit) -> Store {
     let mut store =
         Store::new("store.db", EngineType::InMemory).expect("Failed to build DB for testing");
-    for (address, account) in pre {
+    let genesis_block = CoreBlock::decode(&test.genesis_rlp).expect("Failed to decode genesis");
+    let genesis_number = genesis_block.header.number;
+    let genesis_hash = genesis_block.header.compute_block_hash();
+    store
+        .add_block(genesis_block)
+        .expect("Failed to write to test DB");
+    store
+        .set_canonical_block(genesis_number, genesis_hash)
+        .expect("Failed to write to test DB");
+    store
+        .update_latest_block_number(genesis_number)
+        .expect("Failed to write to test DB");
+    store
+        .set_chain_config(&test.network.chain_config())
+        .expect("Failed to write to test DB");
+    for (address, account) in &test.pre {
         let account: CoreAccount = account.clone().into();
         store
             .add_account(*address, account)
             .expect("Failed to write to test DB")
     }
-    evm_state(store)
+    store
 }
 
 /// Checks that all accounts in the post-state are present and have the correct values in the DB
@@ -137,7 +141,8 @@ fn check_poststate_against_db(post: &HashMap<Address, Account>, db: &Store) {
             let db_storage_value = db
                 .get_storage_at(*addr, key)
                 .expect("Failed to read from DB")
-                .unwrap_or_else(|| panic!("Storage missing for address {addr} key {key} in DB"));
+                // Zero-valued slots are not stored
+                .unwrap_or_default();
             assert_eq!(
                 db_storage_value, value,
                 "Mismatched storage value for address {addr}, key {key}"
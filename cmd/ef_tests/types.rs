@@ -2,7 +2,7 @@ use bytes::Bytes;
 use ethereum_rust_core::types::Withdrawal;
 use ethereum_rust_core::types::{
     code_hash, Account as ethereum_rustAccount, AccountInfo, Block as CoreBlock, BlockBody,
-    EIP1559Transaction, EIP2930Transaction, EIP4844Transaction, LegacyTransaction,
+    ChainConfig, EIP1559Transaction, EIP2930Transaction, EIP4844Transaction, LegacyTransaction,
     Transaction as ethereum_rustTransaction, TxKind,
 };
 use ethereum_rust_core::{types::BlockHeader, Address, Bloom, H160, H256, H64, U256};
@@ -19,12 +19,55 @@ pub struct TestUnit {
     #[serde(rename = "genesisRLP", with = "ethereum_rust_core::serde_utils::bytes")]
     pub genesis_rlp: Bytes,
     pub lastblockhash: serde_json::Value,
-    pub network: serde_json::Value,
+    pub network: Network,
     pub post_state: HashMap<Address, Account>,
     pub pre: HashMap<Address, Account>,
     pub seal_engine: serde_json::Value,
 }
 
+/// Fork configuration used by a test
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Network {
+    #[serde(alias = "Merge")]
+    Paris,
+    ParisToShanghaiAtTime15k,
+    Shanghai,
+    ShanghaiToCancunAtTime15k,
+    Cancun,
+}
+
+impl Network {
+    /// Returns a chain config with all pre-merge forks active from genesis and the post-merge
+    /// forks activated as described by the network
+    pub fn chain_config(&self) -> ChainConfig {
+        let (shanghai_time, cancun_time) = match self {
+            Network::Paris => (None, None),
+            Network::ParisToShanghaiAtTime15k => (Some(15_000), None),
+            Network::Shanghai => (Some(0), None),
+            Network::ShanghaiToCancunAtTime15k => (Some(0), Some(15_000)),
+            Network::Cancun => (Some(0), Some(0)),
+        };
+        ChainConfig {
+            chain_id: U256::one(),
+            homestead_block: Some(0),
+            eip150_block: Some(0),
+            eip155_block: Some(0),
+            eip158_block: Some(0),
+            byzantium_block: Some(0),
+            constantinople_block: Some(0),
+            petersburg_block: Some(0),
+            istanbul_block: Some(0),
+            berlin_block: Some(0),
+            london_block: Some(0),
+            merge_netsplit_block: Some(0),
+            shanghai_time,
+            cancun_time,
+            terminal_total_difficulty: Some(U256::zero()),
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
 pub struct Account {
     pub balance: U256,
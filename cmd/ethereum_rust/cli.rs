@@ -1,5 +1,6 @@
 use clap::{Arg, ArgAction, Command};
 use ethereum_rust_net::bootnode::BootNode;
+use ethereum_rust_rpc::RpcNamespace;
 
 pub fn cli() -> Command {
     Command::new("ethereum_rust")
@@ -19,6 +20,16 @@ pub fn cli() -> Command {
                 .value_name("PORT")
                 .action(ArgAction::Set),
         )
+        .arg(
+            Arg::new("http.api")
+                .long("http.api")
+                .default_value("eth,net,web3")
+                .value_name("NAMESPACE_LIST")
+                .value_parser(clap::value_parser!(RpcNamespace))
+                .value_delimiter(',')
+                .num_args(1..)
+                .action(ArgAction::Set),
+        )
         .arg(
             Arg::new("authrpc.addr")
                 .long("authrpc.addr")
@@ -33,6 +44,47 @@ pub fn cli() -> Command {
                 .value_name("PORT")
                 .action(ArgAction::Set),
         )
+        .arg(
+            Arg::new("authrpc.api")
+                .long("authrpc.api")
+                .default_value("eth,engine")
+                .value_name("NAMESPACE_LIST")
+                .value_parser(clap::value_parser!(RpcNamespace))
+                .value_delimiter(',')
+                .num_args(1..)
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("authrpc.jwtsecret")
+                .long("authrpc.jwtsecret")
+                .default_value("jwt.hex")
+                .value_name("JWTSECRET_PATH")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("ws.addr")
+                .long("ws.addr")
+                .default_value("localhost")
+                .value_name("ADDRESS")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("ws.port")
+                .long("ws.port")
+                .default_value("8546")
+                .value_name("PORT")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("ws.api")
+                .long("ws.api")
+                .default_value("eth,net,web3")
+                .value_name("NAMESPACE_LIST")
+                .value_parser(clap::value_parser!(RpcNamespace))
+                .value_delimiter(',')
+                .num_args(1..)
+                .action(ArgAction::Set),
+        )
         .arg(
             Arg::new("p2p.addr")
                 .long("p2p.addr")
@@ -68,6 +120,101 @@ pub fn cli() -> Command {
                 .value_name("GENESIS_FILE_PATH")
                 .action(ArgAction::Set),
         )
+        .arg(
+            Arg::new("datadir")
+                .long("datadir")
+                .default_value("ethereum_rust_data")
+                .value_name("DATABASE_DIRECTORY")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("db.engine")
+                .long("db.engine")
+                .default_value("libmdbx")
+                .value_name("ENGINE")
+                .value_parser(["libmdbx", "in_memory"])
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("mempool.max_size")
+                .long("mempool.max_size")
+                .default_value("4096")
+                .value_name("MAX_TRANSACTIONS")
+                .value_parser(clap::value_parser!(usize))
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("state.history_window")
+                .long("state.history_window")
+                .default_value("128")
+                .value_name("BLOCKS")
+                .value_parser(clap::value_parser!(u64))
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("prune.mode")
+                .long("prune.mode")
+                .default_value("archive")
+                .value_name("MODE")
+                .value_parser(["archive", "full"])
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("prune.distance")
+                .long("prune.distance")
+                .default_value("90000")
+                .value_name("BLOCKS")
+                .value_parser(clap::value_parser!(u64))
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("logs.max_block_range")
+                .long("logs.max_block_range")
+                .default_value("10000")
+                .value_name("BLOCKS")
+                .value_parser(clap::value_parser!(u64))
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("logs.max_results")
+                .long("logs.max_results")
+                .default_value("10000")
+                .value_name("LOGS")
+                .value_parser(clap::value_parser!(usize))
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("filters.timeout")
+                .long("filters.timeout")
+                .default_value("300")
+                .value_name("SECONDS")
+                .value_parser(clap::value_parser!(u64))
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("filters.max")
+                .long("filters.max")
+                .default_value("1000")
+                .value_name("FILTERS")
+                .value_parser(clap::value_parser!(usize))
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("rpc.max_batch_size")
+                .long("rpc.max_batch_size")
+                .default_value("1000")
+                .value_name("REQUESTS")
+                .value_parser(clap::value_parser!(usize))
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("rpc.max_body_size")
+                .long("rpc.max_body_size")
+                .default_value("5242880")
+                .value_name("BYTES")
+                .value_parser(clap::value_parser!(usize))
+                .action(ArgAction::Set),
+        )
         .arg(
             Arg::new("bootnodes")
                 .long("bootnodes")
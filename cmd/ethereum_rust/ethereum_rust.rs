@@ -1,12 +1,21 @@
-use ethereum_rust_core::types::Genesis;
+use clap::ArgMatches;
+use ethereum_rust_blockchain::{mempool::Mempool, notifier::ChainNotifier};
+use ethereum_rust_core::{types::Genesis, Bytes};
 use ethereum_rust_net::bootnode::BootNode;
-use ethereum_rust_storage::{EngineType, Store};
+use ethereum_rust_rpc::{
+    ApiNamespaces, FilterManager, LogsLimits, RequestLimits, RpcApiContext, RpcNamespace,
+};
+use ethereum_rust_storage::{EngineType, PruningMode, Store};
 use std::{
-    io::{self, BufReader},
+    fs::{self, OpenOptions},
+    io::{self, BufReader, Write},
     net::{SocketAddr, ToSocketAddrs},
+    os::unix::fs::OpenOptionsExt,
+    path::Path,
+    time::Duration,
 };
 use tokio::try_join;
-use tracing::{warn, Level};
+use tracing::{info, warn, Level};
 use tracing_subscriber::FmtSubscriber;
 mod cli;
 
@@ -32,6 +41,15 @@ async fn main() {
     let authrpc_port = matches
         .get_one::<String>("authrpc.port")
         .expect("authrpc.port is required");
+    let authrpc_jwtsecret = matches
+        .get_one::<String>("authrpc.jwtsecret")
+        .expect("authrpc.jwtsecret is required");
+    let ws_addr = matches
+        .get_one::<String>("ws.addr")
+        .expect("ws.addr is required");
+    let ws_port = matches
+        .get_one::<String>("ws.port")
+        .expect("ws.port is required");
 
     let tcp_addr = matches
         .get_one::<String>("p2p.addr")
@@ -50,6 +68,73 @@ async fn main() {
         .get_one::<String>("network")
         .expect("network is required");
 
+    let data_dir = matches
+        .get_one::<String>("datadir")
+        .expect("datadir is required");
+    let engine_type = match matches
+        .get_one::<String>("db.engine")
+        .expect("db.engine is required")
+        .as_str()
+    {
+        "in_memory" => EngineType::InMemory,
+        _ => EngineType::Libmdbx,
+    };
+
+    let mempool_max_size = matches
+        .get_one::<usize>("mempool.max_size")
+        .expect("mempool.max_size is required");
+
+    let state_history_window = matches
+        .get_one::<u64>("state.history_window")
+        .expect("state.history_window is required");
+
+    // In full mode only the bodies, receipts and transaction index entries of the most recent
+    // blocks are kept
+    let prune_distance = matches
+        .get_one::<u64>("prune.distance")
+        .expect("prune.distance is required");
+    let pruning_mode = match matches
+        .get_one::<String>("prune.mode")
+        .expect("prune.mode is required")
+        .as_str()
+    {
+        "full" => PruningMode::Distance(*prune_distance),
+        _ => PruningMode::Archive,
+    };
+    // Blocks that may still be reorganized onto must be kept
+    assert!(
+        pruning_mode == PruningMode::Archive || prune_distance >= state_history_window,
+        "prune.distance must not be lower than state.history_window"
+    );
+
+    let logs_limits = LogsLimits {
+        max_block_range: *matches
+            .get_one::<u64>("logs.max_block_range")
+            .expect("logs.max_block_range is required"),
+        max_results: *matches
+            .get_one::<usize>("logs.max_results")
+            .expect("logs.max_results is required"),
+    };
+
+    // Filters that are not polled within this time are removed
+    let filter_timeout = Duration::from_secs(
+        *matches
+            .get_one::<u64>("filters.timeout")
+            .expect("filters.timeout is required"),
+    );
+    let max_filters = *matches
+        .get_one::<usize>("filters.max")
+        .expect("filters.max is required");
+
+    let request_limits = RequestLimits {
+        max_batch_size: *matches
+            .get_one::<usize>("rpc.max_batch_size")
+            .expect("rpc.max_batch_size is required"),
+        max_body_size: *matches
+            .get_one::<usize>("rpc.max_body_size")
+            .expect("rpc.max_body_size is required"),
+    };
+
     let bootnodes: Vec<BootNode> = matches
         .get_many("bootnodes")
         .map(Iterator::copied)
@@ -64,19 +149,59 @@ async fn main() {
         parse_socket_addr(http_addr, http_port).expect("Failed to parse http address and port");
     let authrpc_socket_addr = parse_socket_addr(authrpc_addr, authrpc_port)
         .expect("Failed to parse authrpc address and port");
+    let ws_socket_addr =
+        parse_socket_addr(ws_addr, ws_port).expect("Failed to parse ws address and port");
 
     let udp_socket_addr =
         parse_socket_addr(udp_addr, udp_port).expect("Failed to parse discovery address and port");
     let tcp_socket_addr =
         parse_socket_addr(tcp_addr, tcp_port).expect("Failed to parse addr and port");
 
-    let mut store = Store::new("storage.db", EngineType::InMemory).expect("Failed to create Store");
-    let genesis = read_genesis_file(genesis_file_path);
-    store
-        .add_initial_state(genesis)
-        .expect("Failed to create genesis block");
+    let mut store = Store::new(data_dir, engine_type).expect("Failed to create Store");
+    store.set_state_history_window(*state_history_window);
+    store.set_pruning_mode(pruning_mode);
+    // The genesis is checked against the one of a persisted store, which can also be resumed
+    // without a genesis file
+    if !genesis_file_path.is_empty() {
+        let genesis = read_genesis_file(genesis_file_path);
+        store
+            .add_initial_state(genesis)
+            .expect("Failed to create genesis block");
+    }
+    match store
+        .get_latest_block_number()
+        .expect("Failed to read the stored head")
+    {
+        Some(head_number) => info!("Starting from head block {head_number}"),
+        None => panic!("A genesis file must be given to initialize the store"),
+    }
+
+    let api_namespaces = ApiNamespaces {
+        http: read_public_namespaces(&matches, "http.api"),
+        ws: read_public_namespaces(&matches, "ws.api"),
+        authrpc: read_namespaces(&matches, "authrpc.api"),
+    };
 
-    let rpc_api = ethereum_rust_rpc::start_api(http_socket_addr, authrpc_socket_addr, store);
+    // Imported blocks and pooled transactions are pushed to the websocket subscriptions
+    let notifier = ChainNotifier::new();
+    let mempool = Mempool::new(*mempool_max_size, notifier.clone());
+    let jwt_secret = read_jwtsecret_file(authrpc_jwtsecret);
+
+    let rpc_api = ethereum_rust_rpc::start_api(
+        http_socket_addr,
+        authrpc_socket_addr,
+        ws_socket_addr,
+        RpcApiContext {
+            storage: store,
+            mempool,
+            logs_limits,
+            filters: FilterManager::new(filter_timeout, max_filters),
+            notifier,
+            request_limits,
+        },
+        jwt_secret,
+        api_namespaces,
+    );
     let networking = ethereum_rust_net::start_network(udp_socket_addr, tcp_socket_addr, bootnodes);
 
     try_join!(tokio::spawn(rpc_api), tokio::spawn(networking)).unwrap();
@@ -88,6 +213,50 @@ fn read_genesis_file(genesis_file_path: &str) -> Genesis {
     serde_json::from_reader(genesis_reader).expect("Failed to read genesis file")
 }
 
+/// Reads the namespaces enabled for a listener
+fn read_namespaces(matches: &ArgMatches, id: &str) -> Vec<RpcNamespace> {
+    matches
+        .get_many::<RpcNamespace>(id)
+        .map(Iterator::copied)
+        .map(Iterator::collect)
+        .unwrap_or_default()
+}
+
+/// Reads the namespaces enabled for a listener that doesn't require authentication, which can't
+/// serve the engine api
+fn read_public_namespaces(matches: &ArgMatches, id: &str) -> Vec<RpcNamespace> {
+    let namespaces = read_namespaces(matches, id);
+    assert!(
+        !namespaces.contains(&RpcNamespace::Engine),
+        "The engine namespace can only be enabled for the authrpc listener, remove it from {id}"
+    );
+    namespaces
+}
+
+/// Reads the secret shared with the consensus client, generating and writing a new one if the
+/// file doesn't exist
+fn read_jwtsecret_file(jwtsecret_path: &str) -> Bytes {
+    if !Path::new(jwtsecret_path).exists() {
+        let secret: [u8; 32] = rand::random();
+        // Only the owner can read the secret
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(jwtsecret_path)
+            .and_then(|mut file| file.write_all(hex::encode(secret).as_bytes()))
+            .expect("Failed to write jwt secret file");
+        info!("Generated jwt secret at {jwtsecret_path}");
+        return Bytes::copy_from_slice(&secret);
+    }
+    let contents = fs::read_to_string(jwtsecret_path).expect("Failed to read jwt secret file");
+    let contents = contents.trim();
+    let secret = hex::decode(contents.strip_prefix("0x").unwrap_or(contents))
+        .expect("Failed to decode jwt secret, it must be hex encoded");
+    assert_eq!(secret.len(), 32, "The jwt secret must be 32 bytes long");
+    Bytes::from(secret)
+}
+
 fn parse_socket_addr(addr: &str, port: &str) -> io::Result<SocketAddr> {
     // NOTE: this blocks until hostname can be resolved
     format!("{addr}:{port}")
@@ -0,0 +1,165 @@
+pub mod error;
+pub mod fork_choice;
+pub mod mempool;
+pub mod notifier;
+pub mod payload;
+
+use error::{ChainError, InvalidBlockError};
+use ethereum_rust_core::types::{
+    compute_receipts_root, compute_withdrawals_root, validate_block_header,
+    validate_cancun_header_fields, validate_no_cancun_header_fields, Block, BlockHeader,
+    ChainConfig, Receipt, GAS_PER_BLOB,
+};
+use ethereum_rust_core::Bloom;
+use ethereum_rust_evm::{evm_state, execute_block, get_state_transitions, spec_id};
+use ethereum_rust_storage::Store;
+use notifier::{ChainEvent, ChainNotifier};
+use tracing::info;
+
+/// Validates, executes and stores a block on top of the current chain state
+///
+/// The block is only written to the store if it is valid, that is, if its header is valid in
+/// relation to its parent and its gas used, receipts root, logs bloom and state root match the
+/// results of executing it
+/// The block, its receipts and its resulting state are written atomically
+/// Blocks can only be executed on top of the latest block, as its state is the one kept. Blocks
+/// built on top of other blocks are stored as side chain blocks without being executed, so that
+/// they can be executed if the canonical chain is reorganized onto them
+/// Executed blocks are published as new heads through the notifier
+/// The chain is locked during the whole import, so that concurrent imports and fork choice
+/// updates can't change the latest block between checking the parent and committing the block
+pub fn add_block(
+    block: &Block,
+    storage: &Store,
+    notifier: &ChainNotifier,
+) -> Result<(), ChainError> {
+    let _chain_lock = storage.lock_chain();
+    import_block(block, storage, notifier)
+}
+
+/// Imports a block as `add_block` does, the caller must hold the chain lock
+pub(crate) fn import_block(
+    block: &Block,
+    storage: &Store,
+    notifier: &ChainNotifier,
+) -> Result<(), ChainError> {
+    let parent_header = find_parent_header(&block.header, storage)?;
+
+    // Validate the block pre-execution
+    let chain_config = storage.get_chain_config()?;
+    validate_block(block, &parent_header, &chain_config)?;
+
+    // Only the state of the latest block is available
+    if storage.get_latest_block_number()? != Some(parent_header.number)
+        || storage.get_canonical_block_hash(parent_header.number)? != Some(block.header.parent_hash)
+    {
+        storage.add_block(block.clone())?;
+        return Err(ChainError::ParentStateNotFound);
+    }
+
+    // Execute the block
+    let spec_id = spec_id(&chain_config, block.header.number, block.header.timestamp);
+    let mut state = evm_state(storage.clone(), parent_header.number);
+    let receipts = execute_block(block, &mut state, spec_id)?;
+
+    // Validate the execution results
+    validate_receipts(&block.header, &receipts)?;
+    let account_updates = get_state_transitions(&mut state);
+    let new_state_root = storage.compute_state_root(&account_updates)?;
+    if new_state_root != block.header.state_root {
+        return Err(InvalidBlockError::StateRootMismatch.into());
+    }
+
+    // Commit the block and its resulting state
+    storage.add_executed_block(block.clone(), receipts, &account_updates)?;
+    let block_hash = block.header.compute_block_hash();
+    info!(
+        "Added block {} with hash {:#x}",
+        block.header.number, block_hash
+    );
+    notifier.publish(ChainEvent::NewHead(block_hash, block.header.clone()));
+    Ok(())
+}
+
+/// Looks up the header of the block's parent in the store
+fn find_parent_header(header: &BlockHeader, storage: &Store) -> Result<BlockHeader, ChainError> {
+    storage
+        .get_block_header_by_hash(header.parent_hash)?
+        .ok_or(ChainError::ParentNotFound)
+}
+
+/// Performs pre-execution validations on the block's header and body according to the active fork
+fn validate_block(
+    block: &Block,
+    parent_header: &BlockHeader,
+    chain_config: &ChainConfig,
+) -> Result<(), ChainError> {
+    let header = &block.header;
+    if !validate_block_header(header, parent_header) {
+        return Err(InvalidBlockError::InvalidHeader.into());
+    }
+    if chain_config.is_shanghai_activated(header.timestamp) != header.withdrawals_root.is_some() {
+        return Err(InvalidBlockError::InvalidHeader.into());
+    }
+    if chain_config.is_cancun_activated(header.timestamp) {
+        if !validate_cancun_header_fields(header, parent_header) {
+            return Err(InvalidBlockError::InvalidHeader.into());
+        }
+        let blob_count: u64 = block
+            .body
+            .transactions
+            .iter()
+            .map(|tx| tx.blob_versioned_hashes().len() as u64)
+            .sum();
+        if header.blob_gas_used != Some(blob_count * GAS_PER_BLOB) {
+            return Err(InvalidBlockError::BlobGasUsedMismatch.into());
+        }
+    } else if !validate_no_cancun_header_fields(header) {
+        return Err(InvalidBlockError::InvalidHeader.into());
+    }
+    if !block.body.ommers.is_empty() {
+        return Err(InvalidBlockError::OmmersNotEmpty.into());
+    }
+    if block.body.compute_transactions_root() != block.header.transactions_root {
+        return Err(InvalidBlockError::TransactionsRootMismatch.into());
+    }
+    if block
+        .body
+        .transactions
+        .iter()
+        .any(|tx| tx.sender().is_err())
+    {
+        return Err(InvalidBlockError::InvalidTransactionSignature.into());
+    }
+    let withdrawals_root = block
+        .body
+        .withdrawals
+        .as_ref()
+        .map(|withdrawals| compute_withdrawals_root(withdrawals));
+    if withdrawals_root != block.header.withdrawals_root {
+        return Err(InvalidBlockError::WithdrawalsRootMismatch.into());
+    }
+    Ok(())
+}
+
+/// Checks the receipts obtained by executing the block against the block's header
+fn validate_receipts(header: &BlockHeader, receipts: &[Receipt]) -> Result<(), ChainError> {
+    let gas_used = receipts
+        .last()
+        .map(|receipt| receipt.cumulative_gas_used)
+        .unwrap_or_default();
+    if gas_used != header.gas_used {
+        return Err(InvalidBlockError::GasUsedMismatch.into());
+    }
+    if compute_receipts_root(receipts) != header.receipt_root {
+        return Err(InvalidBlockError::ReceiptsRootMismatch.into());
+    }
+    let mut logs_bloom = Bloom::zero();
+    for receipt in receipts {
+        logs_bloom.accrue_bloom(&receipt.bloom);
+    }
+    if logs_bloom != header.logs_bloom {
+        return Err(InvalidBlockError::LogsBloomMismatch.into());
+    }
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use ethereum_rust_evm::EvmError;
+use ethereum_rust_storage::error::StoreError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ChainError {
+    #[error("Invalid Block: {0}")]
+    InvalidBlock(#[from] InvalidBlockError),
+    #[error("Parent block not found (block is orphan)")]
+    ParentNotFound,
+    #[error("Parent block state not available (parent is not the latest block)")]
+    ParentStateNotFound,
+    #[error("DB error: {0}")]
+    StoreError(#[from] StoreError),
+    #[error("EVM error: {0}")]
+    EvmError(#[from] EvmError),
+}
+
+#[derive(Debug, Error)]
+pub enum InvalidBlockError {
+    #[error("Invalid header, validation against the parent header failed")]
+    InvalidHeader,
+    #[error("Transactions root doesn't match the one in the header")]
+    TransactionsRootMismatch,
+    #[error("Withdrawals root doesn't match the one in the header")]
+    WithdrawalsRootMismatch,
+    #[error("Transaction signature is invalid, its sender can't be recovered")]
+    InvalidTransactionSignature,
+    #[error("Block has ommers")]
+    OmmersNotEmpty,
+    #[error("Blob gas used doesn't match the blobs in the block's transactions")]
+    BlobGasUsedMismatch,
+    #[error("Gas used doesn't match the one in the header")]
+    GasUsedMismatch,
+    #[error("Receipts root doesn't match the one in the header")]
+    ReceiptsRootMismatch,
+    #[error("Logs bloom doesn't match the one in the header")]
+    LogsBloomMismatch,
+    #[error("World state root doesn't match the one in the header after executing")]
+    StateRootMismatch,
+}
+
+#[derive(Debug, Error)]
+pub enum InvalidForkChoice {
+    #[error("DB error: {0}")]
+    StoreError(#[from] StoreError),
+    #[error("Head block is unknown, the node is still syncing")]
+    Syncing,
+    #[error("Head hash is zero")]
+    InvalidHeadHash,
+    #[error("New head block is an ancestor of the current head, skipping update")]
+    NewHeadAlreadyCanonical,
+    #[error("Safe or finalized block is not part of the canonical chain")]
+    Disconnected,
+    #[error(
+        "Finalized block is newer than the safe block, or the safe block is newer than the head"
+    )]
+    Unordered,
+    #[error("Failed to execute the new canonical chain: {0}")]
+    InvalidChain(#[from] ChainError),
+}
+
+#[derive(Debug, Error)]
+pub enum MempoolError {
+    #[error("DB error: {0}")]
+    StoreError(#[from] StoreError),
+    #[error("Transaction already known")]
+    AlreadyKnown,
+    #[error("Invalid transaction signature")]
+    InvalidSignature,
+    #[error("Invalid chain id {0}")]
+    InvalidChainId(u64),
+    #[error("Nonce too low")]
+    NonceTooLow,
+    #[error("Insufficient funds for gas * price + value")]
+    InsufficientFunds,
+    #[error("Intrinsic gas too low")]
+    IntrinsicGasTooLow,
+    #[error("Transaction gas limit exceeds the block gas limit")]
+    GasLimitExceeded,
+    #[error("Max initcode size exceeded")]
+    InitCodeSizeExceeded,
+    #[error("Max priority fee per gas higher than max fee per gas")]
+    TipAboveFeeCap,
+    #[error("Max fee per gas lower than the current base fee")]
+    FeeCapBelowBaseFee,
+    #[error("Replacement transaction underpriced")]
+    ReplacementUnderpriced,
+    #[error("Transaction pool is full and the transaction's tip is too low")]
+    PoolFull,
+    #[error("Blob transaction received without its blobs bundle")]
+    BlobsBundleMissing,
+    #[error("Invalid amount of blobs: {0}")]
+    InvalidBlobCount(usize),
+    #[error("Blobs bundle doesn't match the transaction's blob versioned hashes")]
+    BlobsBundleMismatch,
+    #[error("Invalid blob KZG proof")]
+    InvalidKzgProof,
+}
@@ -0,0 +1,373 @@
+use ethereum_rust_core::{
+    types::{Block, BlockHeader, BlockNumber},
+    H256,
+};
+use ethereum_rust_storage::Store;
+use tracing::{info, warn};
+
+use crate::{
+    error::InvalidForkChoice,
+    import_block,
+    mempool::Mempool,
+    notifier::{ChainEvent, ChainNotifier},
+};
+
+/// Updates the canonical head, safe and finalized block pointers according to the fork choice
+/// state received from the consensus layer and returns the header of the new head
+///
+/// The head must be a known block, while the safe and finalized blocks, if set (non-zero), must
+/// be part of the head's chain and satisfy `finalized <= safe <= head`
+/// If the head is on a side chain, the canonical chain is reorganized onto it and the blocks of
+/// the side chain are executed on top of the common ancestor of both chains. If any of them is
+/// invalid the previous canonical chain is restored
+/// If the new head is an ancestor of the current one the update is skipped
+/// The transactions of the blocks that become canonical are removed from the mempool, while the
+/// ones of the blocks that leave the canonical chain are added back to it
+/// The blocks that leave the canonical chain are published as removed through the notifier,
+/// before the executed ones are published as new heads
+/// The chain is locked during the whole update, including the restoration of the previous chain,
+/// so that no block import or other update can interleave with it
+pub fn apply_fork_choice(
+    store: &Store,
+    mempool: &Mempool,
+    notifier: &ChainNotifier,
+    head_hash: H256,
+    safe_hash: H256,
+    finalized_hash: H256,
+) -> Result<BlockHeader, InvalidForkChoice> {
+    if head_hash.is_zero() {
+        return Err(InvalidForkChoice::InvalidHeadHash);
+    }
+    let _chain_lock = store.lock_chain();
+    let Some(head) = store.get_block_header_by_hash(head_hash)? else {
+        return Err(InvalidForkChoice::Syncing);
+    };
+
+    // The pointers are validated before touching the chain, so that an invalid update leaves it
+    // untouched
+    let safe = find_chain_header(store, &head, head_hash, safe_hash)?;
+    let finalized = find_chain_header(store, &head, head_hash, finalized_hash)?;
+    let safe_number = safe.as_ref().map(|header| header.number);
+    let finalized_number = finalized.as_ref().map(|header| header.number);
+    if finalized_number.is_some_and(|number| number > safe_number.unwrap_or(head.number)) {
+        return Err(InvalidForkChoice::Unordered);
+    }
+
+    let latest_block_number = store.get_latest_block_number()?.unwrap_or_default();
+    let previous_head_hash = store.get_canonical_block_hash(latest_block_number)?;
+    let mut removed_blocks = Vec::new();
+    if store.get_canonical_block_hash(head.number)? != Some(head_hash) {
+        let ancestor_number = store.reorg_to(head_hash)?;
+        info!(
+            "Reorganized the canonical chain onto block {:#x}, common ancestor is block {}",
+            head_hash, ancestor_number
+        );
+        if let Some(previous_head_hash) = previous_head_hash {
+            removed_blocks = removed_chain(store, previous_head_hash, ancestor_number)?;
+            publish_removed_blocks(notifier, &removed_blocks);
+        }
+    } else if head.number < latest_block_number {
+        return Err(InvalidForkChoice::NewHeadAlreadyCanonical);
+    }
+    let first_executed_number = store.get_latest_block_number()?.unwrap_or_default() + 1;
+    if let Err(error) = execute_canonical_chain(store, notifier, head.number) {
+        // The rejected blocks must not stay canonical
+        if let Some(previous_head_hash) = previous_head_hash {
+            warn!(
+                "Failed to execute the chain of block {:#x}, restoring the previous head {:#x}",
+                head_hash, previous_head_hash
+            );
+            let executed_number = store.get_latest_block_number()?.unwrap_or_default();
+            let executed_hash = store.get_canonical_block_hash(executed_number)?;
+            let ancestor_number = store.reorg_to(previous_head_hash)?;
+            // The blocks executed before the failure were already published as new heads
+            if let Some(executed_hash) = executed_hash {
+                publish_removed_blocks(
+                    notifier,
+                    &removed_chain(store, executed_hash, ancestor_number)?,
+                );
+            }
+            execute_canonical_chain(store, notifier, latest_block_number)?;
+        }
+        return Err(error);
+    }
+    for block_number in first_executed_number..=head.number {
+        if let Some(body) = store.get_block_body(block_number)? {
+            mempool.remove_included_transactions(&body.transactions);
+        }
+    }
+    readd_removed_transactions(store, mempool, &removed_blocks)?;
+
+    if let Some(number) = safe_number {
+        store.update_safe_block_number(number)?;
+    }
+    if let Some(number) = finalized_number {
+        store.update_finalized_block_number(number)?;
+    }
+    info!(
+        "Updated fork choice: head {}, safe {:?}, finalized {:?}",
+        head.number, safe_number, finalized_number
+    );
+    Ok(head)
+}
+
+/// Executes the canonical blocks up to the given one whose state has not been applied yet, such
+/// as the blocks of a side chain that just became canonical
+/// The caller must hold the chain lock
+fn execute_canonical_chain(
+    store: &Store,
+    notifier: &ChainNotifier,
+    head_number: BlockNumber,
+) -> Result<(), InvalidForkChoice> {
+    let latest_block_number = store.get_latest_block_number()?.unwrap_or_default();
+    for block_number in latest_block_number + 1..=head_number {
+        let (Some(header), Some(body)) = (
+            store.get_block_header(block_number)?,
+            store.get_block_body(block_number)?,
+        ) else {
+            return Err(InvalidForkChoice::Syncing);
+        };
+        import_block(&Block { header, body }, store, notifier)?;
+    }
+    Ok(())
+}
+
+/// Returns the hashes and headers of the blocks that left the canonical chain, walking back from
+/// its previous head to the common ancestor of both chains
+fn removed_chain(
+    store: &Store,
+    previous_head_hash: H256,
+    ancestor_number: BlockNumber,
+) -> Result<Vec<(H256, BlockHeader)>, InvalidForkChoice> {
+    let mut removed_blocks = Vec::new();
+    let mut block_hash = previous_head_hash;
+    while let Some(header) = store.get_block_header_by_hash(block_hash)? {
+        if header.number <= ancestor_number {
+            break;
+        }
+        let parent_hash = header.parent_hash;
+        removed_blocks.push((block_hash, header));
+        block_hash = parent_hash;
+    }
+    Ok(removed_blocks)
+}
+
+fn publish_removed_blocks(notifier: &ChainNotifier, removed_blocks: &[(H256, BlockHeader)]) {
+    for (block_hash, header) in removed_blocks {
+        notifier.publish(ChainEvent::RemovedBlock(*block_hash, header.clone()));
+    }
+}
+
+/// Adds the transactions of the blocks that left the canonical chain back to the mempool
+/// The transactions that are not valid on top of the new chain, such as the ones it also
+/// includes, are discarded, as well as blob transactions, whose blobs are no longer available
+fn readd_removed_transactions(
+    store: &Store,
+    mempool: &Mempool,
+    removed_blocks: &[(H256, BlockHeader)],
+) -> Result<(), InvalidForkChoice> {
+    for (block_hash, _) in removed_blocks {
+        if let Some(body) = store.get_block_body_by_hash(*block_hash)? {
+            for tx in body.transactions {
+                let _ = mempool.add_transaction(tx, store);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Looks up the header of a safe or finalized block, which must be the head or one of its
+/// ancestors, a zero hash means the block is not set yet
+/// The head's chain is walked back until reaching the block's height or the canonical chain, as
+/// the head may not be canonical yet
+fn find_chain_header(
+    store: &Store,
+    head: &BlockHeader,
+    head_hash: H256,
+    block_hash: H256,
+) -> Result<Option<BlockHeader>, InvalidForkChoice> {
+    if block_hash.is_zero() {
+        return Ok(None);
+    }
+    let Some(header) = store.get_block_header_by_hash(block_hash)? else {
+        return Err(InvalidForkChoice::Disconnected);
+    };
+    if header.number > head.number {
+        return Err(InvalidForkChoice::Unordered);
+    }
+    let mut chain_hash = head_hash;
+    let mut chain_header = head.clone();
+    while chain_header.number > header.number
+        && store.get_canonical_block_hash(chain_header.number)? != Some(chain_hash)
+    {
+        chain_hash = chain_header.parent_hash;
+        chain_header = store
+            .get_block_header_by_hash(chain_hash)?
+            .ok_or(InvalidForkChoice::Disconnected)?;
+    }
+    let on_chain = if chain_header.number == header.number {
+        chain_hash == block_hash
+    } else {
+        // The rest of the head's chain is canonical
+        store.get_canonical_block_hash(header.number)? == Some(block_hash)
+    };
+    if !on_chain {
+        return Err(InvalidForkChoice::Disconnected);
+    }
+    Ok(Some(header))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mempool::DEFAULT_MEMPOOL_MAX_SIZE;
+    use ethereum_rust_core::types::BlockBody;
+    use ethereum_rust_storage::EngineType;
+
+    /// Stores a chain of `length` blocks on top of a default genesis and returns their hashes
+    fn store_chain(store: &Store, length: u64) -> Vec<H256> {
+        let mut hashes = vec![];
+        let mut parent_hash = H256::zero();
+        for number in 0..=length {
+            let header = BlockHeader {
+                number,
+                parent_hash,
+                ..Default::default()
+            };
+            parent_hash = header.compute_block_hash();
+            hashes.push(parent_hash);
+            store
+                .add_block(Block {
+                    header,
+                    body: BlockBody::empty(),
+                })
+                .unwrap();
+            store.set_canonical_block(number, parent_hash).unwrap();
+        }
+        store.update_latest_block_number(length).unwrap();
+        hashes
+    }
+
+    #[test]
+    fn test_apply_fork_choice_updates_pointers() {
+        let store = Store::new("test", EngineType::InMemory).unwrap();
+        let notifier = ChainNotifier::default();
+        let mempool = Mempool::new(DEFAULT_MEMPOOL_MAX_SIZE, notifier.clone());
+        let hashes = store_chain(&store, 3);
+        let head = apply_fork_choice(&store, &mempool, &notifier, hashes[3], hashes[2], hashes[1])
+            .unwrap();
+        assert_eq!(head.number, 3);
+        assert_eq!(store.get_latest_block_number().unwrap(), Some(3));
+        assert_eq!(store.get_safe_block_number().unwrap(), Some(2));
+        assert_eq!(store.get_finalized_block_number().unwrap(), Some(1));
+    }
+
+    #[test]
+    fn test_apply_fork_choice_unknown_head() {
+        let store = Store::new("test", EngineType::InMemory).unwrap();
+        let notifier = ChainNotifier::default();
+        let mempool = Mempool::new(DEFAULT_MEMPOOL_MAX_SIZE, notifier.clone());
+        store_chain(&store, 3);
+        let result = apply_fork_choice(
+            &store,
+            &mempool,
+            &notifier,
+            H256::from_low_u64_be(0xdead),
+            H256::zero(),
+            H256::zero(),
+        );
+        assert!(matches!(result, Err(InvalidForkChoice::Syncing)));
+    }
+
+    #[test]
+    fn test_apply_fork_choice_ancestor_head() {
+        let store = Store::new("test", EngineType::InMemory).unwrap();
+        let notifier = ChainNotifier::default();
+        let mempool = Mempool::new(DEFAULT_MEMPOOL_MAX_SIZE, notifier.clone());
+        let hashes = store_chain(&store, 3);
+        let result = apply_fork_choice(
+            &store,
+            &mempool,
+            &notifier,
+            hashes[2],
+            H256::zero(),
+            H256::zero(),
+        );
+        assert!(matches!(
+            result,
+            Err(InvalidForkChoice::NewHeadAlreadyCanonical)
+        ));
+        assert_eq!(store.get_latest_block_number().unwrap(), Some(3));
+    }
+
+    #[test]
+    fn test_apply_fork_choice_invalid_safe_and_finalized() {
+        let store = Store::new("test", EngineType::InMemory).unwrap();
+        let notifier = ChainNotifier::default();
+        let mempool = Mempool::new(DEFAULT_MEMPOOL_MAX_SIZE, notifier.clone());
+        let hashes = store_chain(&store, 3);
+        let result = apply_fork_choice(
+            &store,
+            &mempool,
+            &notifier,
+            hashes[3],
+            H256::from_low_u64_be(0xdead),
+            H256::zero(),
+        );
+        assert!(matches!(result, Err(InvalidForkChoice::Disconnected)));
+        let result =
+            apply_fork_choice(&store, &mempool, &notifier, hashes[3], hashes[1], hashes[2]);
+        assert!(matches!(result, Err(InvalidForkChoice::Unordered)));
+        assert_eq!(store.get_safe_block_number().unwrap(), None);
+        assert_eq!(store.get_finalized_block_number().unwrap(), None);
+    }
+
+    #[test]
+    fn test_apply_fork_choice_rejected_chain_is_not_canonical() {
+        let store = Store::new("test", EngineType::InMemory).unwrap();
+        let notifier = ChainNotifier::default();
+        let mempool = Mempool::new(DEFAULT_MEMPOOL_MAX_SIZE, notifier.clone());
+        let hashes = store_chain(&store, 1);
+        // Blocks stored without being executed, whose headers fail validation once executed
+        let mut head_hash = hashes[1];
+        for number in 2..=3 {
+            let header = BlockHeader {
+                number,
+                parent_hash: head_hash,
+                ..Default::default()
+            };
+            head_hash = header.compute_block_hash();
+            store
+                .add_block(Block {
+                    header,
+                    body: BlockBody::empty(),
+                })
+                .unwrap();
+        }
+
+        let result = apply_fork_choice(
+            &store,
+            &mempool,
+            &notifier,
+            head_hash,
+            H256::from_low_u64_be(0xdead),
+            H256::zero(),
+        );
+        assert!(matches!(result, Err(InvalidForkChoice::Disconnected)));
+        assert_eq!(store.get_canonical_block_hash(2).unwrap(), None);
+
+        let result = apply_fork_choice(
+            &store,
+            &mempool,
+            &notifier,
+            head_hash,
+            H256::zero(),
+            H256::zero(),
+        );
+        assert!(matches!(result, Err(InvalidForkChoice::InvalidChain(_))));
+        assert_eq!(store.get_latest_block_number().unwrap(), Some(1));
+        assert_eq!(store.get_canonical_block_hash(1).unwrap(), Some(hashes[1]));
+        assert_eq!(store.get_canonical_block_hash(2).unwrap(), None);
+        assert_eq!(store.get_canonical_block_hash(3).unwrap(), None);
+    }
+}
@@ -0,0 +1,840 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BinaryHeap, HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use ethereum_rust_core::{
+    types::{
+        BlobsBundle, BlockHeader, ChainConfig, Transaction, TxKind, WrappedEIP4844Transaction,
+        GAS_PER_BLOB, MAX_BLOB_GAS_PER_BLOCK,
+    },
+    Address, Bytes, H256, U256,
+};
+use ethereum_rust_evm::{kzg_commitment_to_versioned_hash, verify_blob_kzg_proof_batch};
+use ethereum_rust_storage::{error::StoreError, Store};
+
+use crate::{
+    error::MempoolError,
+    notifier::{ChainEvent, ChainNotifier},
+};
+
+/// Default maximum amount of transactions kept in the mempool
+pub const DEFAULT_MEMPOOL_MAX_SIZE: usize = 4096;
+
+// Intrinsic gas costs, as defined in the yellow paper (section 6.2), EIP-2930 and EIP-3860
+const TX_GAS_COST: u64 = 21000;
+const TX_CREATE_GAS_COST: u64 = 53000;
+const TX_DATA_ZERO_GAS_COST: u64 = 4;
+const TX_DATA_NON_ZERO_GAS_COST: u64 = 16;
+const TX_ACCESS_LIST_ADDRESS_GAS: u64 = 2400;
+const TX_ACCESS_LIST_STORAGE_KEY_GAS: u64 = 1900;
+const TX_INIT_CODE_WORD_GAS_COST: u64 = 2;
+/// Maximum size of the initcode of a contract creation transaction, as defined by EIP-3860
+const MAX_INITCODE_SIZE: usize = 49152;
+/// Minimum fee increase (as a percentage) needed to replace a transaction with the same nonce
+const REPLACEMENT_FEE_BUMP: u64 = 10;
+
+/// Pool of validated transactions waiting to be included in a block
+/// Clones share the same underlying pool, so it can be handed to both the rpc and the payload builder
+#[derive(Clone)]
+pub struct Mempool {
+    inner: Arc<Mutex<MempoolInner>>,
+    max_size: usize,
+    notifier: ChainNotifier,
+}
+
+#[derive(Default)]
+struct MempoolInner {
+    /// Transactions indexed by hash
+    transactions: HashMap<H256, MempoolTransaction>,
+    /// Hashes of each sender's transactions indexed by nonce
+    senders: HashMap<Address, BTreeMap<u64, H256>>,
+    /// Blobs bundles of the pooled blob transactions, indexed by transaction hash
+    blobs_bundles: HashMap<H256, BlobsBundle>,
+    /// Arrival counter, used to favour older transactions when tips are equal
+    next_sequence: u64,
+}
+
+struct MempoolTransaction {
+    tx: Transaction,
+    sender: Address,
+    sequence: u64,
+}
+
+impl Mempool {
+    /// Creates an empty mempool that holds at most `max_size` transactions and publishes the
+    /// ones added to it through the notifier
+    pub fn new(max_size: usize, notifier: ChainNotifier) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(MempoolInner::default())),
+            max_size,
+            notifier,
+        }
+    }
+
+    /// Validates the transaction against the latest state and adds it to the pool
+    /// A pooled transaction with the same sender and nonce is replaced if the new one pays
+    /// sufficiently higher fees
+    /// If the pool is full, the transaction with the lowest effective tip is evicted
+    /// Returns the hash of the added transaction
+    /// Blob transactions must be added along with their blobs via `add_blob_transaction`
+    pub fn add_transaction(&self, tx: Transaction, storage: &Store) -> Result<H256, MempoolError> {
+        if matches!(tx, Transaction::EIP4844Transaction(_)) {
+            return Err(MempoolError::BlobsBundleMissing);
+        }
+        self.add(tx, None, storage)
+    }
+
+    /// Validates a blob transaction and its blobs bundle and adds both to the pool
+    /// The bundle's commitments must match the transaction's versioned hashes and its proofs
+    /// must be valid for its blobs
+    /// Returns the hash of the added transaction
+    pub fn add_blob_transaction(
+        &self,
+        wrapped_tx: WrappedEIP4844Transaction,
+        storage: &Store,
+    ) -> Result<H256, MempoolError> {
+        let WrappedEIP4844Transaction { tx, blobs_bundle } = wrapped_tx;
+        validate_blobs_bundle(&tx.blob_versioned_hashes, &blobs_bundle)?;
+        self.add(
+            Transaction::EIP4844Transaction(tx),
+            Some(blobs_bundle),
+            storage,
+        )
+    }
+
+    fn add(
+        &self,
+        tx: Transaction,
+        blobs_bundle: Option<BlobsBundle>,
+        storage: &Store,
+    ) -> Result<H256, MempoolError> {
+        let hash = tx.compute_hash();
+        if self.inner.lock().unwrap().transactions.contains_key(&hash) {
+            return Err(MempoolError::AlreadyKnown);
+        }
+        // The transaction is validated without holding the pool, so that submissions and payload
+        // building are not blocked by it
+        let latest_header = latest_block_header(storage)?;
+        let chain_config = storage.get_chain_config()?;
+        let sender = validate_transaction(&tx, &latest_header, &chain_config, storage)?;
+
+        // The pool may have changed in the meantime
+        let mut inner = self.inner.lock().unwrap();
+        if inner.transactions.contains_key(&hash) {
+            return Err(MempoolError::AlreadyKnown);
+        }
+        if let Some(pooled_hash) = inner.find(sender, tx.nonce()) {
+            if !is_replacement(&inner.transactions[&pooled_hash].tx, &tx) {
+                return Err(MempoolError::ReplacementUnderpriced);
+            }
+            inner.remove(pooled_hash);
+        }
+        inner.insert(hash, sender, tx);
+        if let Some(blobs_bundle) = blobs_bundle {
+            inner.blobs_bundles.insert(hash, blobs_bundle);
+        }
+
+        if inner.transactions.len() > self.max_size {
+            let evicted = inner.evict(latest_header.base_fee_per_gas);
+            if evicted == Some(hash) {
+                return Err(MempoolError::PoolFull);
+            }
+        }
+        drop(inner);
+        self.notifier
+            .publish(ChainEvent::NewPendingTransaction(hash));
+        Ok(hash)
+    }
+
+    /// Returns the pooled transaction with the given hash
+    pub fn get_transaction(&self, hash: H256) -> Option<Transaction> {
+        let inner = self.inner.lock().unwrap();
+        inner.transactions.get(&hash).map(|entry| entry.tx.clone())
+    }
+
+    /// Returns the blobs bundle of the pooled blob transaction with the given hash
+    pub fn get_blobs_bundle(&self, tx_hash: H256) -> Option<BlobsBundle> {
+        let inner = self.inner.lock().unwrap();
+        inner.blobs_bundles.get(&tx_hash).cloned()
+    }
+
+    /// Looks up a blob and its proof among the pooled blob transactions by its versioned hash
+    pub fn get_blob_and_proof(&self, versioned_hash: H256) -> Option<(Bytes, Bytes)> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .blobs_bundles
+            .iter()
+            .find_map(|(tx_hash, blobs_bundle)| {
+                let index = inner.transactions[tx_hash]
+                    .tx
+                    .blob_versioned_hashes()
+                    .iter()
+                    .position(|hash| *hash == versioned_hash)?;
+                Some((
+                    blobs_bundle.blobs[index].clone(),
+                    blobs_bundle.proofs[index].clone(),
+                ))
+            })
+    }
+
+    /// Returns the hashes of the pooled transactions that arrived from the given arrival sequence
+    /// number onwards, in arrival order, along with the sequence number of the next arrival
+    pub fn transaction_hashes_since(&self, sequence: u64) -> (Vec<H256>, u64) {
+        let inner = self.inner.lock().unwrap();
+        if sequence >= inner.next_sequence {
+            return (vec![], inner.next_sequence);
+        }
+        let mut transactions: Vec<_> = inner
+            .transactions
+            .iter()
+            .filter(|(_, entry)| entry.sequence >= sequence)
+            .map(|(hash, entry)| (entry.sequence, *hash))
+            .collect();
+        transactions.sort_unstable();
+        let hashes = transactions.into_iter().map(|(_, hash)| hash).collect();
+        (hashes, inner.next_sequence)
+    }
+
+    /// Returns the amount of transactions in the pool
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes the transactions that were included in a block, along with any other pooled
+    /// transaction from the same senders that became invalid due to their nonces being used
+    pub fn remove_included_transactions(&self, transactions: &[Transaction]) {
+        let mut inner = self.inner.lock().unwrap();
+        for tx in transactions {
+            let Ok(sender) = tx.sender() else {
+                continue;
+            };
+            let stale: Vec<H256> = inner
+                .senders
+                .get(&sender)
+                .map(|txs| txs.range(..=tx.nonce()).map(|(_, hash)| *hash).collect())
+                .unwrap_or_default();
+            for hash in stale {
+                inner.remove(hash);
+            }
+        }
+    }
+
+    /// Returns the pooled transactions that can be executed on top of the latest state in a
+    /// block with the given base fee
+    /// Each sender's transactions must follow the sender's current nonce without gaps, and
+    /// must be able to pay the base fee
+    pub fn pending_transactions(
+        &self,
+        base_fee: u64,
+        storage: &Store,
+    ) -> Result<TransactionQueue, StoreError> {
+        let inner = self.inner.lock().unwrap();
+        let mut queue = TransactionQueue {
+            heads: BinaryHeap::new(),
+            transactions: HashMap::new(),
+            base_fee,
+        };
+        for (sender, txs) in inner.senders.iter() {
+            let mut next_nonce = storage
+                .get_account_info(*sender)?
+                .map(|info| info.nonce)
+                .unwrap_or_default();
+            let mut pending = VecDeque::new();
+            for (nonce, hash) in txs.range(next_nonce..) {
+                let entry = &inner.transactions[hash];
+                if *nonce != next_nonce || entry.tx.effective_gas_tip(base_fee).is_none() {
+                    break;
+                }
+                pending.push_back((entry.tx.clone(), entry.sequence));
+                next_nonce += 1;
+            }
+            if !pending.is_empty() {
+                queue.transactions.insert(*sender, pending);
+                queue.push_next(*sender);
+            }
+        }
+        Ok(queue)
+    }
+}
+
+impl MempoolInner {
+    fn find(&self, sender: Address, nonce: u64) -> Option<H256> {
+        self.senders.get(&sender)?.get(&nonce).copied()
+    }
+
+    fn insert(&mut self, hash: H256, sender: Address, tx: Transaction) {
+        self.senders
+            .entry(sender)
+            .or_default()
+            .insert(tx.nonce(), hash);
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.transactions.insert(
+            hash,
+            MempoolTransaction {
+                tx,
+                sender,
+                sequence,
+            },
+        );
+    }
+
+    fn remove(&mut self, hash: H256) {
+        let Some(entry) = self.transactions.remove(&hash) else {
+            return;
+        };
+        self.blobs_bundles.remove(&hash);
+        if let Some(txs) = self.senders.get_mut(&entry.sender) {
+            txs.remove(&entry.tx.nonce());
+            if txs.is_empty() {
+                self.senders.remove(&entry.sender);
+            }
+        }
+    }
+
+    /// Removes the transaction with the lowest effective tip given the base fee, the newest
+    /// one among those with the same tip, and returns its hash
+    fn evict(&mut self, base_fee: u64) -> Option<H256> {
+        let hash = self
+            .transactions
+            .iter()
+            .min_by_key(|(_, entry)| {
+                (
+                    entry.tx.effective_gas_tip(base_fee).unwrap_or_default(),
+                    u64::MAX - entry.sequence,
+                )
+            })
+            .map(|(hash, _)| *hash)?;
+        self.remove(hash);
+        Some(hash)
+    }
+}
+
+/// Transactions ready to be included in a block, ordered by nonce for each sender and by
+/// effective tip among senders
+/// Only the next transaction of each sender competes for inclusion, so the highest paying
+/// transaction is always executable on top of the previously included ones
+pub struct TransactionQueue {
+    heads: BinaryHeap<HeadTransaction>,
+    transactions: HashMap<Address, VecDeque<(Transaction, u64)>>,
+    base_fee: u64,
+}
+
+impl TransactionQueue {
+    /// Returns the transaction with the highest effective tip among the senders' next transactions
+    pub fn peek(&self) -> Option<&Transaction> {
+        self.heads.peek().map(|head| &head.tx)
+    }
+
+    /// Discards the current transaction and replaces it with the next one from the same sender
+    /// Used after the transaction has been included
+    pub fn shift(&mut self) {
+        if let Some(head) = self.heads.pop() {
+            self.push_next(head.sender);
+        }
+    }
+
+    /// Discards the current transaction along with the rest of the transactions from the same
+    /// sender, which can't be executed without it
+    /// Used when the transaction can't be included
+    pub fn pop(&mut self) {
+        if let Some(head) = self.heads.pop() {
+            self.transactions.remove(&head.sender);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heads.is_empty()
+    }
+
+    fn push_next(&mut self, sender: Address) {
+        let Some((tx, sequence)) = self
+            .transactions
+            .get_mut(&sender)
+            .and_then(|txs| txs.pop_front())
+        else {
+            self.transactions.remove(&sender);
+            return;
+        };
+        self.heads.push(HeadTransaction {
+            tip: tx.effective_gas_tip(self.base_fee).unwrap_or_default(),
+            sequence,
+            sender,
+            tx,
+        });
+    }
+}
+
+struct HeadTransaction {
+    tip: u64,
+    sequence: u64,
+    sender: Address,
+    tx: Transaction,
+}
+
+impl Ord for HeadTransaction {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Higher tips first, older transactions first when tied
+        self.tip
+            .cmp(&other.tip)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for HeadTransaction {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for HeadTransaction {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HeadTransaction {}
+
+fn latest_block_header(storage: &Store) -> Result<BlockHeader, StoreError> {
+    let latest_block_number = storage
+        .get_latest_block_number()?
+        .ok_or(StoreError::Custom(
+            "Latest block number not found".to_string(),
+        ))?;
+    storage
+        .get_block_header(latest_block_number)?
+        .ok_or(StoreError::Custom(
+            "Latest block header not found".to_string(),
+        ))
+}
+
+/// Validates the transaction against the latest block and state and returns its sender
+fn validate_transaction(
+    tx: &Transaction,
+    latest_header: &BlockHeader,
+    chain_config: &ChainConfig,
+    storage: &Store,
+) -> Result<Address, MempoolError> {
+    // Legacy transactions may not be replay protected
+    if let Some(chain_id) = tx.chain_id() {
+        if U256::from(chain_id) != chain_config.chain_id {
+            return Err(MempoolError::InvalidChainId(chain_id));
+        }
+    }
+    if tx
+        .max_priority_fee()
+        .is_some_and(|max_priority_fee| max_priority_fee > tx.gas_price())
+    {
+        return Err(MempoolError::TipAboveFeeCap);
+    }
+    if tx.gas_price() < latest_header.base_fee_per_gas {
+        return Err(MempoolError::FeeCapBelowBaseFee);
+    }
+    if tx.gas_limit() > latest_header.gas_limit {
+        return Err(MempoolError::GasLimitExceeded);
+    }
+    let is_shanghai = chain_config.is_shanghai_activated(latest_header.timestamp);
+    if is_shanghai && tx.to() == TxKind::Create && tx.data().len() > MAX_INITCODE_SIZE {
+        return Err(MempoolError::InitCodeSizeExceeded);
+    }
+    if tx.gas_limit() < intrinsic_gas(tx, is_shanghai) {
+        return Err(MempoolError::IntrinsicGasTooLow);
+    }
+
+    let sender = tx.sender().map_err(|_| MempoolError::InvalidSignature)?;
+    let (nonce, balance) = match storage.get_account_info(sender)? {
+        Some(info) => (info.nonce, info.balance),
+        None => (0, U256::zero()),
+    };
+    if tx.nonce() < nonce {
+        return Err(MempoolError::NonceTooLow);
+    }
+    if balance < transaction_cost(tx) {
+        return Err(MempoolError::InsufficientFunds);
+    }
+    Ok(sender)
+}
+
+/// Checks that the blobs bundle holds one blob, commitment and proof for each of the
+/// transaction's versioned hashes, that the commitments match the hashes and that the proofs are
+/// valid
+fn validate_blobs_bundle(
+    versioned_hashes: &[H256],
+    blobs_bundle: &BlobsBundle,
+) -> Result<(), MempoolError> {
+    let blob_count = versioned_hashes.len();
+    if blob_count == 0 || blob_count as u64 * GAS_PER_BLOB > MAX_BLOB_GAS_PER_BLOCK {
+        return Err(MempoolError::InvalidBlobCount(blob_count));
+    }
+    if blobs_bundle.blobs.len() != blob_count
+        || blobs_bundle.commitments.len() != blob_count
+        || blobs_bundle.proofs.len() != blob_count
+    {
+        return Err(MempoolError::BlobsBundleMismatch);
+    }
+    let commitments_match = blobs_bundle
+        .commitments
+        .iter()
+        .zip(versioned_hashes)
+        .all(|(commitment, hash)| kzg_commitment_to_versioned_hash(commitment) == *hash);
+    if !commitments_match {
+        return Err(MempoolError::BlobsBundleMismatch);
+    }
+    if !verify_blob_kzg_proof_batch(
+        &blobs_bundle.blobs,
+        &blobs_bundle.commitments,
+        &blobs_bundle.proofs,
+    ) {
+        return Err(MempoolError::InvalidKzgProof);
+    }
+    Ok(())
+}
+
+/// Computes the gas charged before executing the transaction
+fn intrinsic_gas(tx: &Transaction, is_shanghai: bool) -> u64 {
+    let is_create = tx.to() == TxKind::Create;
+    let mut gas = if is_create {
+        TX_CREATE_GAS_COST
+    } else {
+        TX_GAS_COST
+    };
+    let data = tx.data();
+    let zero_bytes = data.iter().filter(|byte| **byte == 0).count() as u64;
+    gas += zero_bytes * TX_DATA_ZERO_GAS_COST
+        + (data.len() as u64 - zero_bytes) * TX_DATA_NON_ZERO_GAS_COST;
+    // EIP-3860: initcode is charged per 32-byte word
+    if is_create && is_shanghai {
+        gas += data.len().div_ceil(32) as u64 * TX_INIT_CODE_WORD_GAS_COST;
+    }
+    for (_, storage_keys) in tx.access_list() {
+        gas +=
+            TX_ACCESS_LIST_ADDRESS_GAS + storage_keys.len() as u64 * TX_ACCESS_LIST_STORAGE_KEY_GAS;
+    }
+    gas
+}
+
+/// Computes the maximum amount of wei the sender may pay for the transaction
+fn transaction_cost(tx: &Transaction) -> U256 {
+    let gas_cost = U256::from(tx.gas_limit()) * U256::from(tx.gas_price());
+    let blob_gas = U256::from(tx.blob_versioned_hashes().len() as u64 * GAS_PER_BLOB);
+    let blob_gas_cost = tx
+        .max_fee_per_blob_gas()
+        .unwrap_or_default()
+        .saturating_mul(blob_gas);
+    gas_cost
+        .saturating_add(blob_gas_cost)
+        .saturating_add(tx.value())
+}
+
+/// Checks whether the new transaction raises both the fee cap and tip of the pooled one by at
+/// least `REPLACEMENT_FEE_BUMP` percent
+fn is_replacement(pooled: &Transaction, new: &Transaction) -> bool {
+    let bumped = |fee: u64| fee as u128 * (100 + REPLACEMENT_FEE_BUMP) as u128 / 100;
+    let tip = |tx: &Transaction| tx.max_priority_fee().unwrap_or(tx.gas_price());
+    new.gas_price() as u128 >= bumped(pooled.gas_price()) && tip(new) as u128 >= bumped(tip(pooled))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_rust_core::{
+        rlp::decode::RLPDecode,
+        types::{
+            AccountInfo, Block, BlockBody, EIP1559Transaction, EIP4844Transaction,
+            LegacyTransaction,
+        },
+    };
+    use ethereum_rust_storage::EngineType;
+
+    const CHAIN_ID: u64 = 3151908;
+
+    /// Signed legacy transaction with chain id 3151908, nonce 0 and gas price 1001000000
+    fn signed_legacy_tx() -> Transaction {
+        let encoded_tx = "f86d80843baa0c4082f618946177843db3138ae69679a54b95cf345ed759450d870aa87bee538000808360306ba0151ccc02146b9b11adf516e6787b59acae3e76544fdcd75e77e67c6b598ce65da064c5dd5aae2fbb535830ebbdad0234975cd7ece3562013b63ea18cc0df6c97d4";
+        let tx = LegacyTransaction::decode(&hex::decode(encoded_tx).unwrap()).unwrap();
+        Transaction::LegacyTransaction(tx)
+    }
+
+    fn eip1559_tx(nonce: u64, max_fee_per_gas: u64, max_priority_fee_per_gas: u64) -> Transaction {
+        Transaction::EIP1559Transaction(EIP1559Transaction {
+            chain_id: CHAIN_ID,
+            nonce,
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+            gas_limit: TX_GAS_COST,
+            to: TxKind::Call(Address::from_low_u64_be(1)),
+            ..Default::default()
+        })
+    }
+
+    fn setup_store(chain_id: u64) -> Store {
+        let store = Store::new("test", EngineType::InMemory).unwrap();
+        let header = BlockHeader {
+            gas_limit: 30_000_000,
+            base_fee_per_gas: 1_000_000_000,
+            ..Default::default()
+        };
+        let block_hash = header.compute_block_hash();
+        store
+            .add_block(Block {
+                header,
+                body: BlockBody::empty(),
+            })
+            .unwrap();
+        store.set_canonical_block(0, block_hash).unwrap();
+        store.update_latest_block_number(0).unwrap();
+        store
+            .set_chain_config(&ChainConfig {
+                chain_id: U256::from(chain_id),
+                shanghai_time: Some(0),
+                ..Default::default()
+            })
+            .unwrap();
+        store
+    }
+
+    fn fund_account(store: &Store, address: Address, balance: U256, nonce: u64) {
+        let info = AccountInfo {
+            balance,
+            nonce,
+            ..Default::default()
+        };
+        store.add_account_info(address, info).unwrap();
+    }
+
+    #[test]
+    fn test_add_transaction() {
+        let store = setup_store(CHAIN_ID);
+        let tx = signed_legacy_tx();
+        fund_account(
+            &store,
+            tx.sender().unwrap(),
+            U256::from(10).pow(18.into()),
+            0,
+        );
+        let notifier = ChainNotifier::default();
+        let mut events = notifier.subscribe();
+        let mempool = Mempool::new(DEFAULT_MEMPOOL_MAX_SIZE, notifier);
+        let hash = mempool.add_transaction(tx.clone(), &store).unwrap();
+        assert_eq!(hash, tx.compute_hash());
+        assert_eq!(mempool.get_transaction(hash), Some(tx.clone()));
+        assert!(matches!(
+            events.try_recv(),
+            Ok(ChainEvent::NewPendingTransaction(event_hash)) if event_hash == hash
+        ));
+        assert!(matches!(
+            mempool.add_transaction(tx.clone(), &store),
+            Err(MempoolError::AlreadyKnown)
+        ));
+        assert!(events.try_recv().is_err());
+        mempool.remove_included_transactions(&[tx]);
+        assert!(mempool.is_empty());
+    }
+
+    #[test]
+    fn test_reject_invalid_transactions() {
+        let mempool = Mempool::new(DEFAULT_MEMPOOL_MAX_SIZE, ChainNotifier::default());
+        let tx = signed_legacy_tx();
+
+        let store = setup_store(CHAIN_ID);
+        assert!(matches!(
+            mempool.add_transaction(tx.clone(), &store),
+            Err(MempoolError::InsufficientFunds)
+        ));
+        fund_account(
+            &store,
+            tx.sender().unwrap(),
+            U256::from(10).pow(18.into()),
+            1,
+        );
+        assert!(matches!(
+            mempool.add_transaction(tx.clone(), &store),
+            Err(MempoolError::NonceTooLow)
+        ));
+
+        let store = setup_store(1);
+        assert!(matches!(
+            mempool.add_transaction(tx, &store),
+            Err(MempoolError::InvalidChainId(CHAIN_ID))
+        ));
+        assert!(matches!(
+            mempool.add_transaction(eip1559_tx(0, 1_000_000_000, 2_000_000_000), &store),
+            Err(MempoolError::InvalidChainId(CHAIN_ID))
+        ));
+
+        let store = setup_store(CHAIN_ID);
+        assert!(matches!(
+            mempool.add_transaction(eip1559_tx(0, 1_000_000_000, 2_000_000_000), &store),
+            Err(MempoolError::TipAboveFeeCap)
+        ));
+        assert!(matches!(
+            mempool.add_transaction(eip1559_tx(0, 1, 1), &store),
+            Err(MempoolError::FeeCapBelowBaseFee)
+        ));
+        assert!(mempool.is_empty());
+    }
+
+    #[test]
+    fn test_intrinsic_gas() {
+        assert_eq!(intrinsic_gas(&eip1559_tx(0, 0, 0), true), TX_GAS_COST);
+        let tx = Transaction::EIP1559Transaction(EIP1559Transaction {
+            to: TxKind::Create,
+            data: vec![0, 1, 2].into(),
+            access_list: vec![(Address::zero(), vec![H256::zero(), H256::zero()])],
+            ..Default::default()
+        });
+        let expected = TX_CREATE_GAS_COST
+            + TX_DATA_ZERO_GAS_COST
+            + 2 * TX_DATA_NON_ZERO_GAS_COST
+            + TX_ACCESS_LIST_ADDRESS_GAS
+            + 2 * TX_ACCESS_LIST_STORAGE_KEY_GAS;
+        assert_eq!(intrinsic_gas(&tx, false), expected);
+        assert_eq!(
+            intrinsic_gas(&tx, true),
+            expected + TX_INIT_CODE_WORD_GAS_COST
+        );
+    }
+
+    #[test]
+    fn test_pending_transactions_order() {
+        let store = setup_store(CHAIN_ID);
+        let mempool = Mempool::new(DEFAULT_MEMPOOL_MAX_SIZE, ChainNotifier::default());
+        let (sender_a, sender_b) = (Address::from_low_u64_be(1), Address::from_low_u64_be(2));
+        fund_account(&store, sender_b, U256::zero(), 1);
+        {
+            let mut inner = mempool.inner.lock().unwrap();
+            // Sender A's second transaction pays the most but can only follow the first one
+            inner.insert(H256::from_low_u64_be(1), sender_a, eip1559_tx(0, 10, 1));
+            inner.insert(H256::from_low_u64_be(2), sender_a, eip1559_tx(1, 10, 5));
+            // Sender B's stale and gapped transactions are not pending
+            inner.insert(H256::from_low_u64_be(3), sender_b, eip1559_tx(0, 10, 9));
+            inner.insert(H256::from_low_u64_be(4), sender_b, eip1559_tx(1, 10, 3));
+            inner.insert(H256::from_low_u64_be(5), sender_b, eip1559_tx(3, 10, 9));
+        }
+        let mut queue = mempool.pending_transactions(0, &store).unwrap();
+        let mut order = vec![];
+        while let Some(tx) = queue.peek() {
+            order.push(tx.max_priority_fee().unwrap());
+            queue.shift();
+        }
+        assert_eq!(order, vec![3, 1, 5]);
+
+        // Transactions that can't pay the base fee end their sender's queue
+        let queue = mempool.pending_transactions(11, &store).unwrap();
+        assert!(queue.is_empty());
+
+        // Popping a transaction discards the rest of its sender's
+        let mut queue = mempool.pending_transactions(0, &store).unwrap();
+        queue.pop();
+        assert_eq!(queue.peek().unwrap().max_priority_fee(), Some(1));
+        queue.pop();
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_evict_lowest_tip() {
+        let mempool = Mempool::new(DEFAULT_MEMPOOL_MAX_SIZE, ChainNotifier::default());
+        let mut inner = mempool.inner.lock().unwrap();
+        let sender = Address::from_low_u64_be(1);
+        inner.insert(H256::from_low_u64_be(1), sender, eip1559_tx(0, 10, 3));
+        inner.insert(H256::from_low_u64_be(2), sender, eip1559_tx(1, 10, 1));
+        inner.insert(H256::from_low_u64_be(3), sender, eip1559_tx(2, 10, 1));
+        // Among the lowest tips the newest transaction is evicted
+        assert_eq!(inner.evict(0), Some(H256::from_low_u64_be(3)));
+        assert_eq!(inner.evict(0), Some(H256::from_low_u64_be(2)));
+        assert_eq!(inner.find(sender, 0), Some(H256::from_low_u64_be(1)));
+        assert_eq!(inner.find(sender, 1), None);
+    }
+
+    #[test]
+    fn test_replacement_fee_bump() {
+        let pooled = eip1559_tx(0, 100, 10);
+        assert!(is_replacement(&pooled, &eip1559_tx(0, 110, 11)));
+        assert!(!is_replacement(&pooled, &eip1559_tx(0, 109, 11)));
+        assert!(!is_replacement(&pooled, &eip1559_tx(0, 200, 10)));
+    }
+
+    /// Blobs bundle holding a single zero blob, whose commitment and proof are the point at infinity
+    fn zero_blobs_bundle() -> BlobsBundle {
+        let mut point_at_infinity = vec![0; 48];
+        point_at_infinity[0] = 0xc0;
+        BlobsBundle {
+            commitments: vec![Bytes::from(point_at_infinity.clone())],
+            proofs: vec![Bytes::from(point_at_infinity)],
+            blobs: vec![Bytes::from(vec![0; 131072])],
+        }
+    }
+
+    #[test]
+    fn test_validate_blobs_bundle() {
+        let blobs_bundle = zero_blobs_bundle();
+        let versioned_hash = kzg_commitment_to_versioned_hash(&blobs_bundle.commitments[0]);
+        assert!(validate_blobs_bundle(&[versioned_hash], &blobs_bundle).is_ok());
+        assert!(matches!(
+            validate_blobs_bundle(&[], &BlobsBundle::default()),
+            Err(MempoolError::InvalidBlobCount(0))
+        ));
+        assert!(matches!(
+            validate_blobs_bundle(&[H256::zero()], &blobs_bundle),
+            Err(MempoolError::BlobsBundleMismatch)
+        ));
+        assert!(matches!(
+            validate_blobs_bundle(&[versioned_hash, versioned_hash], &blobs_bundle),
+            Err(MempoolError::BlobsBundleMismatch)
+        ));
+        let invalid_proof = BlobsBundle {
+            proofs: vec![Bytes::from(vec![0; 48])],
+            ..zero_blobs_bundle()
+        };
+        assert!(matches!(
+            validate_blobs_bundle(&[versioned_hash], &invalid_proof),
+            Err(MempoolError::InvalidKzgProof)
+        ));
+    }
+
+    #[test]
+    fn test_blob_transactions_require_blobs_bundle() {
+        let store = setup_store(CHAIN_ID);
+        let mempool = Mempool::new(DEFAULT_MEMPOOL_MAX_SIZE, ChainNotifier::default());
+        let tx = Transaction::EIP4844Transaction(EIP4844Transaction::default());
+        assert!(matches!(
+            mempool.add_transaction(tx, &store),
+            Err(MempoolError::BlobsBundleMissing)
+        ));
+    }
+
+    #[test]
+    fn test_blobs_bundle_lookup() {
+        let mempool = Mempool::new(DEFAULT_MEMPOOL_MAX_SIZE, ChainNotifier::default());
+        let blobs_bundle = zero_blobs_bundle();
+        let versioned_hash = kzg_commitment_to_versioned_hash(&blobs_bundle.commitments[0]);
+        let tx = Transaction::EIP4844Transaction(EIP4844Transaction {
+            blob_versioned_hashes: vec![versioned_hash],
+            ..Default::default()
+        });
+        let hash = H256::from_low_u64_be(1);
+        {
+            let mut inner = mempool.inner.lock().unwrap();
+            inner.insert(hash, Address::zero(), tx);
+            inner.blobs_bundles.insert(hash, blobs_bundle.clone());
+        }
+        assert_eq!(mempool.get_blobs_bundle(hash), Some(blobs_bundle.clone()));
+        assert_eq!(
+            mempool.get_blob_and_proof(versioned_hash),
+            Some((
+                blobs_bundle.blobs[0].clone(),
+                blobs_bundle.proofs[0].clone()
+            ))
+        );
+        assert_eq!(mempool.get_blob_and_proof(H256::zero()), None);
+        // Removing the transaction removes its blobs bundle
+        mempool.inner.lock().unwrap().remove(hash);
+        assert_eq!(mempool.get_blobs_bundle(hash), None);
+    }
+}
@@ -0,0 +1,51 @@
+use ethereum_rust_core::{
+    types::{BlockHash, BlockHeader},
+    H256,
+};
+use tokio::sync::broadcast;
+
+/// Number of events kept for subscribers that fall behind, older ones are dropped
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Publishes the changes made to the canonical chain and the mempool as they happen, so that
+/// they can be pushed to the rpc subscriptions
+/// Clones publish to the same subscribers
+#[derive(Clone, Debug)]
+pub struct ChainNotifier {
+    sender: broadcast::Sender<ChainEvent>,
+}
+
+#[derive(Clone, Debug)]
+pub enum ChainEvent {
+    /// A block was executed on top of the canonical chain
+    NewHead(BlockHash, BlockHeader),
+    /// A block left the canonical chain due to a reorg
+    /// The blocks of a reorg are removed from the newest to the oldest one, before the blocks of
+    /// the new chain are published
+    RemovedBlock(BlockHash, BlockHeader),
+    /// A transaction was added to the mempool
+    NewPendingTransaction(H256),
+}
+
+impl Default for ChainNotifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChainNotifier {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self { sender }
+    }
+
+    /// Returns a receiver for the events published from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<ChainEvent> {
+        self.sender.subscribe()
+    }
+
+    pub(crate) fn publish(&self, event: ChainEvent) {
+        // Sending only fails if nobody is subscribed
+        let _ = self.sender.send(event);
+    }
+}
@@ -0,0 +1,274 @@
+use ethereum_rust_core::{
+    rlp::encode::RLPEncode,
+    types::{
+        bloom_from_logs, calc_excess_blob_gas, calculate_base_fee_per_gas, compute_receipts_root,
+        compute_withdrawals_root, BlobsBundle, Block, BlockBody, BlockHash, BlockHeader, Receipt,
+        Withdrawal, DEFAULT_OMMERS_HASH, GAS_PER_BLOB, MAX_BLOB_GAS_PER_BLOCK,
+    },
+    Address, Bloom, Bytes, H256, U256,
+};
+use ethereum_rust_evm::{
+    beacon_root_contract_call, evm_state, execute_tx, get_state_transitions, process_withdrawals,
+    spec_id, EvmError, EvmState, SpecId,
+};
+use ethereum_rust_storage::Store;
+use sha3::{Digest, Keccak256};
+use tracing::debug;
+
+use crate::{
+    error::{ChainError, InvalidBlockError},
+    mempool::Mempool,
+};
+
+/// Arguments received from the consensus layer in order to build a payload
+pub struct BuildPayloadArgs {
+    pub parent: BlockHash,
+    pub timestamp: u64,
+    pub fee_recipient: Address,
+    pub random: H256,
+    pub withdrawals: Vec<Withdrawal>,
+    pub beacon_root: Option<H256>,
+    pub version: u8,
+}
+
+impl BuildPayloadArgs {
+    /// Computes an 8-byte identifier by hashing the components of the payload arguments
+    /// The first byte of the identifier is the version of the engine api call that requested it
+    pub fn id(&self) -> u64 {
+        let mut hasher = Keccak256::new();
+        hasher.update(self.parent);
+        hasher.update(self.timestamp.to_be_bytes());
+        hasher.update(self.random);
+        hasher.update(self.fee_recipient);
+        hasher.update(self.withdrawals.encode_to_vec());
+        if let Some(beacon_root) = self.beacon_root {
+            hasher.update(beacon_root);
+        }
+        let hash = hasher.finalize();
+        let mut id = [0; 8];
+        id.copy_from_slice(&hash[..8]);
+        id[0] = self.version;
+        u64::from_be_bytes(id)
+    }
+}
+
+/// Creates a new payload (in the form of a block without transactions) on top of the parent
+/// block given by the payload arguments
+/// The payload still needs to be filled with transactions and executed via `build_payload`
+pub fn create_payload(args: &BuildPayloadArgs, storage: &Store) -> Result<Block, ChainError> {
+    let parent_header = storage
+        .get_block_header_by_hash(args.parent)?
+        .ok_or(ChainError::ParentNotFound)?;
+    // TODO: Allow the gas limit target to be configured
+    let gas_limit = parent_header.gas_limit;
+    let base_fee_per_gas = calculate_base_fee_per_gas(
+        gas_limit,
+        parent_header.gas_limit,
+        parent_header.gas_used,
+        parent_header.base_fee_per_gas,
+    )
+    .ok_or(InvalidBlockError::InvalidHeader)?;
+    let body = BlockBody {
+        transactions: Vec::new(),
+        ommers: Vec::new(),
+        withdrawals: Some(args.withdrawals.clone()),
+    };
+    let header = BlockHeader {
+        parent_hash: args.parent,
+        ommers_hash: *DEFAULT_OMMERS_HASH,
+        coinbase: args.fee_recipient,
+        state_root: parent_header.state_root,
+        transactions_root: body.compute_transactions_root(),
+        receipt_root: compute_receipts_root(&[]),
+        logs_bloom: Bloom::zero(),
+        difficulty: U256::zero(),
+        number: parent_header.number + 1,
+        gas_limit,
+        gas_used: 0,
+        timestamp: args.timestamp,
+        extra_data: Bytes::new(),
+        prev_randao: args.random,
+        nonce: 0,
+        base_fee_per_gas,
+        withdrawals_root: Some(compute_withdrawals_root(&args.withdrawals)),
+        blob_gas_used: Some(0),
+        excess_blob_gas: Some(calc_excess_blob_gas(
+            parent_header.excess_blob_gas.unwrap_or_default(),
+            parent_header.blob_gas_used.unwrap_or_default(),
+        )),
+        parent_beacon_block_root: args.beacon_root,
+    };
+    Ok(Block { header, body })
+}
+
+/// Completes a payload created by `create_payload` by filling it with transactions from the
+/// mempool, executing it on top of the current state and filling in the header fields that
+/// depend on the execution results
+/// Returns the value of the block, that is, the fees paid to the fee recipient, along with the
+/// blobs bundle of the included blob transactions
+pub fn build_payload(
+    payload: &mut Block,
+    storage: &Store,
+    mempool: &Mempool,
+) -> Result<(U256, BlobsBundle), ChainError> {
+    // Only the state of the latest block is available
+    let parent_number = payload.header.number.saturating_sub(1);
+    if storage.get_latest_block_number()? != Some(parent_number)
+        || storage.get_canonical_block_hash(parent_number)? != Some(payload.header.parent_hash)
+    {
+        return Err(ChainError::ParentStateNotFound);
+    }
+    let chain_config = storage.get_chain_config()?;
+    let spec_id = spec_id(
+        &chain_config,
+        payload.header.number,
+        payload.header.timestamp,
+    );
+    let mut state = evm_state(storage.clone(), parent_number);
+    // EIP-4788: The beacon roots contract is called before executing the block's transactions
+    if payload.header.parent_beacon_block_root.is_some() && spec_id >= SpecId::CANCUN {
+        beacon_root_contract_call(&mut state, &payload.header, spec_id)?;
+    }
+    let (receipts, blobs_bundle) =
+        fill_transactions(payload, &mut state, spec_id, storage, mempool)?;
+    if let Some(withdrawals) = &payload.body.withdrawals {
+        process_withdrawals(&mut state, withdrawals)?;
+    }
+    let account_updates = get_state_transitions(&mut state);
+
+    let header = &mut payload.header;
+    header.state_root = storage.compute_state_root(&account_updates)?;
+    header.transactions_root = payload.body.compute_transactions_root();
+    header.receipt_root = compute_receipts_root(&receipts);
+    header.gas_used = receipts
+        .last()
+        .map(|receipt| receipt.cumulative_gas_used)
+        .unwrap_or_default();
+    let mut logs_bloom = Bloom::zero();
+    for receipt in receipts.iter() {
+        logs_bloom.accrue_bloom(&receipt.bloom);
+    }
+    header.logs_bloom = logs_bloom;
+    let blob_count: u64 = payload
+        .body
+        .transactions
+        .iter()
+        .map(|tx| tx.blob_versioned_hashes().len() as u64)
+        .sum();
+    header.blob_gas_used = Some(blob_count * GAS_PER_BLOB);
+
+    Ok((compute_block_value(payload, &receipts), blobs_bundle))
+}
+
+/// Executes the mempool's pending transactions, highest effective tip first, and adds them to
+/// the payload until its gas or blob gas runs out
+/// A transaction that doesn't fit or fails to execute is skipped along with the rest of the
+/// transactions from the same sender
+/// Returns the receipts of the included transactions and the blobs of the included blob
+/// transactions
+fn fill_transactions(
+    payload: &mut Block,
+    state: &mut EvmState,
+    spec_id: SpecId,
+    storage: &Store,
+    mempool: &Mempool,
+) -> Result<(Vec<Receipt>, BlobsBundle), ChainError> {
+    let mut queue = mempool.pending_transactions(payload.header.base_fee_per_gas, storage)?;
+    let mut receipts = Vec::new();
+    let mut blobs_bundle = BlobsBundle::default();
+    let mut cumulative_gas_used = 0;
+    let mut blob_gas_used = 0;
+    while let Some(tx) = queue.peek() {
+        let tx = tx.clone();
+        let tx_blob_gas = tx.blob_versioned_hashes().len() as u64 * GAS_PER_BLOB;
+        if cumulative_gas_used + tx.gas_limit() > payload.header.gas_limit
+            || blob_gas_used + tx_blob_gas > MAX_BLOB_GAS_PER_BLOCK
+        {
+            queue.pop();
+            continue;
+        }
+        // The blobs must be available in order to publish the payload
+        let tx_blobs_bundle = if tx_blob_gas > 0 {
+            let Some(tx_blobs_bundle) = mempool.get_blobs_bundle(tx.compute_hash()) else {
+                queue.pop();
+                continue;
+            };
+            Some(tx_blobs_bundle)
+        } else {
+            None
+        };
+        let result = match execute_tx(&tx, &payload.header, state, spec_id) {
+            Ok(result) => result,
+            Err(EvmError::DB(error)) => return Err(error.into()),
+            Err(error) => {
+                debug!(
+                    "Skipping transaction {:#x} while building payload: {error}",
+                    tx.compute_hash()
+                );
+                queue.pop();
+                continue;
+            }
+        };
+        queue.shift();
+        cumulative_gas_used += result.gas_used();
+        blob_gas_used += tx_blob_gas;
+        let logs = result.logs();
+        receipts.push(Receipt::new(
+            tx.tx_type(),
+            result.is_success(),
+            cumulative_gas_used,
+            bloom_from_logs(&logs),
+            logs,
+        ));
+        payload.body.transactions.push(tx);
+        if let Some(tx_blobs_bundle) = tx_blobs_bundle {
+            blobs_bundle.extend(tx_blobs_bundle);
+        }
+    }
+    Ok((receipts, blobs_bundle))
+}
+
+/// Computes the fees paid to the fee recipient by the transactions in the block
+fn compute_block_value(block: &Block, receipts: &[Receipt]) -> U256 {
+    let mut block_value = U256::zero();
+    let mut previous_cumulative_gas_used = 0;
+    for (tx, receipt) in block.body.transactions.iter().zip(receipts) {
+        let gas_used = receipt.cumulative_gas_used - previous_cumulative_gas_used;
+        previous_cumulative_gas_used = receipt.cumulative_gas_used;
+        let gas_tip = tx
+            .effective_gas_tip(block.header.base_fee_per_gas)
+            .unwrap_or_default();
+        block_value += U256::from(gas_used) * U256::from(gas_tip);
+    }
+    block_value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_args() -> BuildPayloadArgs {
+        BuildPayloadArgs {
+            parent: H256::from_low_u64_be(1),
+            timestamp: 12,
+            fee_recipient: Address::from_low_u64_be(2),
+            random: H256::from_low_u64_be(3),
+            withdrawals: Vec::new(),
+            beacon_root: Some(H256::zero()),
+            version: 3,
+        }
+    }
+
+    #[test]
+    fn payload_id_depends_on_args() {
+        let args = test_args();
+        let other_args = BuildPayloadArgs {
+            timestamp: 13,
+            ..test_args()
+        };
+        assert_eq!(args.id(), test_args().id());
+        assert_ne!(args.id(), other_args.id());
+        // The version is encoded in the first byte
+        assert_eq!(args.id() >> 56, 3);
+    }
+}
@@ -183,6 +183,12 @@ impl<'a> Encoder<'a> {
         self
     }
 
+    /// Stores a field that is already rlp encoded
+    pub fn encode_raw(mut self, encoded_value: &[u8]) -> Self {
+        self.temp_buf.put_slice(encoded_value);
+        self
+    }
+
     /// If `Some`, stores a field to be encoded, else does nothing.
     pub fn encode_optional_field<T: RLPEncode>(mut self, opt_value: &Option<T>) -> Self {
         if let Some(value) = opt_value {
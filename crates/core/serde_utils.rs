@@ -30,6 +30,27 @@ pub mod u256 {
         let value = String::deserialize(d)?;
         U256::from_dec_str(&value).map_err(|e| D::Error::custom(e.to_string()))
     }
+
+    /// Serializes into a json number, the value must fit into a u128
+    pub fn serialize_number<S>(value: &U256, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let value = u128::try_from(*value).map_err(|_| {
+            <S::Error as serde::ser::Error>::custom("Failed to serialize U256 value as number")
+        })?;
+        serializer.serialize_u128(value)
+    }
+
+    pub fn serialize_number_opt<S>(value: &Option<U256>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match value {
+            Some(value) => serialize_number(value, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
 }
 
 pub mod u64 {
@@ -78,6 +99,33 @@ pub mod u64 {
         }
     }
 
+    /// Serializes to and deserializes from a 0x prefixed hex string padded to 8 bytes
+    pub mod hex_str_padding_opt {
+        use serde::Serialize;
+
+        use super::*;
+
+        pub fn serialize<S>(value: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            Option::<String>::serialize(&value.map(|v| format!("{:#018x}", v)), serializer)
+        }
+
+        pub fn deserialize<'de, D>(d: D) -> Result<Option<u64>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let value = Option::<String>::deserialize(d)?;
+            value
+                .map(|value| {
+                    u64::from_str_radix(value.trim_start_matches("0x"), 16)
+                        .map_err(|_| D::Error::custom("Failed to deserialize u64 value"))
+                })
+                .transpose()
+        }
+    }
+
     pub fn deser_dec_str<'de, D>(d: D) -> Result<u64, D::Error>
     where
         D: Deserializer<'de>,
@@ -129,6 +177,36 @@ pub mod bytes {
             }
             Ok(output)
         }
+
+        pub fn serialize<S>(value: &[Bytes], serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serializer.collect_seq(value.iter().map(|bytes| format!("0x{:x}", bytes)))
+        }
+    }
+
+    pub mod opt {
+        use serde::Serialize;
+
+        use super::*;
+
+        pub fn deserialize<'de, D>(d: D) -> Result<Option<Bytes>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            super::deserialize(d).map(Some)
+        }
+
+        pub fn serialize<S>(value: &Option<Bytes>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            Option::<String>::serialize(
+                &value.as_ref().map(|bytes| format!("0x{:x}", bytes)),
+                serializer,
+            )
+        }
     }
 }
 
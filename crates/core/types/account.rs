@@ -1,8 +1,9 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 
 use bytes::Bytes;
-use ethereum_types::{H256, U256};
+use ethereum_types::{Address, H256, U256};
 use patricia_merkle_tree::PatriciaMerkleTree;
+use serde::Deserialize;
 use sha3::{Digest as _, Keccak256};
 
 use crate::rlp::{
@@ -18,6 +19,8 @@ use lazy_static::lazy_static;
 lazy_static! {
     // Keccak256(""), represents the code hash for an account without code
     pub static ref EMPTY_KECCACK_HASH: H256 = H256::from_slice(&hex::decode("c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470").unwrap());
+    // Keccak256(rlp("")), represents the root of an empty trie, such as the storage trie of an account without storage
+    pub static ref EMPTY_TRIE_HASH: H256 = H256::from_slice(&hex::decode("56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421").unwrap());
 }
 
 #[allow(unused)]
@@ -43,6 +46,26 @@ pub struct AccountState {
     pub code_hash: H256,
 }
 
+/// Temporary changes to an account's state used when simulating calls
+/// `state` replaces the whole account storage, while `state_diff` only overrides the given slots
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountStateOverride {
+    #[serde(default)]
+    pub balance: Option<U256>,
+    #[serde(default, with = "crate::serde_utils::u64::hex_str_opt")]
+    pub nonce: Option<u64>,
+    #[serde(default, with = "crate::serde_utils::bytes::opt")]
+    pub code: Option<Bytes>,
+    #[serde(default)]
+    pub state: Option<BTreeMap<H256, H256>>,
+    #[serde(default)]
+    pub state_diff: Option<BTreeMap<H256, H256>>,
+}
+
+/// State overrides for a simulated call, indexed by the address of the account they apply to
+pub type StateOverrides = HashMap<Address, AccountStateOverride>;
+
 impl Default for AccountInfo {
     fn default() -> Self {
         Self {
@@ -128,9 +151,14 @@ pub fn compute_storage_root(storage: &BTreeMap<H256, H256>) -> H256 {
     let mut storage_trie = PatriciaMerkleTree::<Vec<u8>, Vec<u8>, Keccak256>::new();
 
     for (k, v) in storage.iter() {
+        // Zero-valued slots are not part of the storage trie
+        if v.is_zero() {
+            continue;
+        }
         let mut v_buf = vec![];
         let k_buf = Keccak256::new_with_prefix(k).finalize().to_vec();
-        v.encode(&mut v_buf);
+        // Values are encoded as integers, without leading zeros
+        U256::from_big_endian(v.as_bytes()).encode(&mut v_buf);
         storage_trie.insert(k_buf, v_buf);
     }
 
@@ -172,4 +200,36 @@ mod test {
                 .unwrap()
         )
     }
+
+    #[test]
+    fn storage_root_ignores_zero_values() {
+        let empty_trie_hash =
+            H256::from_str("56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421")
+                .unwrap();
+        let storage = BTreeMap::from([(H256::from_low_u64_be(1), H256::zero())]);
+        assert_eq!(compute_storage_root(&storage), empty_trie_hash);
+        assert_eq!(compute_storage_root(&BTreeMap::new()), empty_trie_hash);
+    }
+
+    #[test]
+    fn deserialize_state_overrides() {
+        let json = r#"{"0x0100000000000000000000000000000000000000":{"balance":"0x10","nonce":"0x2","code":"0x6000","stateDiff":{"0x0000000000000000000000000000000000000000000000000000000000000001":"0x0000000000000000000000000000000000000000000000000000000000000002"}}}"#;
+        let overrides: StateOverrides = serde_json::from_str(json).unwrap();
+        let account_override =
+            &overrides[&Address::from_str("0100000000000000000000000000000000000000").unwrap()];
+        assert_eq!(account_override.balance, Some(U256::from(0x10)));
+        assert_eq!(account_override.nonce, Some(2));
+        assert_eq!(
+            account_override.code,
+            Some(Bytes::from_static(&[0x60, 0x00]))
+        );
+        assert_eq!(account_override.state, None);
+        assert_eq!(
+            account_override.state_diff,
+            Some(BTreeMap::from([(
+                H256::from_low_u64_be(1),
+                H256::from_low_u64_be(2)
+            )]))
+        );
+    }
 }
@@ -0,0 +1,24 @@
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+/// Blobs, KZG commitments and KZG proofs accompanying blob transactions
+/// Used both as the sidecar of a single blob transaction and as the bundle of all the blobs
+/// included in a payload
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BlobsBundle {
+    #[serde(with = "crate::serde_utils::bytes::vec")]
+    pub commitments: Vec<Bytes>,
+    #[serde(with = "crate::serde_utils::bytes::vec")]
+    pub proofs: Vec<Bytes>,
+    #[serde(with = "crate::serde_utils::bytes::vec")]
+    pub blobs: Vec<Bytes>,
+}
+
+impl BlobsBundle {
+    /// Appends the blobs, commitments and proofs of another bundle to this one
+    pub fn extend(&mut self, other: BlobsBundle) {
+        self.commitments.extend(other.commitments);
+        self.proofs.extend(other.proofs);
+        self.blobs.extend(other.blobs);
+    }
+}
@@ -1,6 +1,7 @@
 use super::{
     ReceiptBlockInfo, BASE_FEE_MAX_CHANGE_DENOMINATOR, ELASTICITY_MULTIPLIER,
-    GAS_LIMIT_ADJUSTMENT_FACTOR, GAS_LIMIT_MINIMUM,
+    GAS_LIMIT_ADJUSTMENT_FACTOR, GAS_LIMIT_MINIMUM, MAX_BLOB_GAS_PER_BLOCK,
+    TARGET_BLOB_GAS_PER_BLOCK,
 };
 use crate::{
     rlp::{
@@ -32,7 +33,7 @@ use lazy_static::lazy_static;
 lazy_static! {
     pub static ref DEFAULT_OMMERS_HASH: H256 = H256::from_slice(&hex::decode("1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347").unwrap()); // = Keccak256(RLP([])) as of EIP-3675
 }
-#[derive(PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Block {
     pub header: BlockHeader,
     pub body: BlockBody,
@@ -366,7 +367,7 @@ fn check_gas_limit(gas_limit: u64, parent_gas_limit: u64) -> bool {
 
 // Calculates the base fee for the current block based on its gas_limit and parent's gas and fee
 // Returns None if the block gas limit is not valid in relation to its parent's gas limit
-fn calculate_base_fee_per_gas(
+pub fn calculate_base_fee_per_gas(
     block_gas_limit: u64,
     parent_gas_limit: u64,
     parent_gas_used: u64,
@@ -406,6 +407,12 @@ fn calculate_base_fee_per_gas(
     })
 }
 
+/// Calculates the excess blob gas of a block based on its parent's excess blob gas and blob gas used
+/// See [EIP-4844](https://eips.ethereum.org/EIPS/eip-4844)
+pub fn calc_excess_blob_gas(parent_excess_blob_gas: u64, parent_blob_gas_used: u64) -> u64 {
+    (parent_excess_blob_gas + parent_blob_gas_used).saturating_sub(TARGET_BLOB_GAS_PER_BLOCK)
+}
+
 pub fn validate_block_header(header: &BlockHeader, parent_header: &BlockHeader) -> bool {
     if header.gas_used > header.gas_limit {
         return false;
@@ -431,6 +438,27 @@ pub fn validate_block_header(header: &BlockHeader, parent_header: &BlockHeader)
         && header.parent_hash == parent_header.compute_block_hash()
 }
 
+/// Validates the header fields introduced by the Cancun fork
+/// See [EIP-4844](https://eips.ethereum.org/EIPS/eip-4844) and [EIP-4788](https://eips.ethereum.org/EIPS/eip-4788)
+pub fn validate_cancun_header_fields(header: &BlockHeader, parent_header: &BlockHeader) -> bool {
+    let expected_excess_blob_gas = calc_excess_blob_gas(
+        parent_header.excess_blob_gas.unwrap_or_default(),
+        parent_header.blob_gas_used.unwrap_or_default(),
+    );
+    header.excess_blob_gas == Some(expected_excess_blob_gas)
+        && header
+            .blob_gas_used
+            .is_some_and(|blob_gas_used| blob_gas_used <= MAX_BLOB_GAS_PER_BLOCK)
+        && header.parent_beacon_block_root.is_some()
+}
+
+/// Validates that the header doesn't contain the fields introduced by the Cancun fork
+pub fn validate_no_cancun_header_fields(header: &BlockHeader) -> bool {
+    header.excess_blob_gas.is_none()
+        && header.blob_gas_used.is_none()
+        && header.parent_beacon_block_root.is_none()
+}
+
 #[allow(unused)]
 mod serializable {
     use super::*;
@@ -4,3 +4,8 @@ pub const BASE_FEE_MAX_CHANGE_DENOMINATOR: u64 = 8;
 pub const GAS_LIMIT_ADJUSTMENT_FACTOR: u64 = 1024;
 pub const GAS_LIMIT_MINIMUM: u64 = 5000;
 pub const GWEI_TO_WEI: u64 = 1_000_000_000;
+
+// Blob related
+pub const GAS_PER_BLOB: u64 = 1 << 17;
+pub const TARGET_BLOB_GAS_PER_BLOCK: u64 = 393216; // TARGET_BLOBS_PER_BLOCK * GAS_PER_BLOB
+pub const MAX_BLOB_GAS_PER_BLOCK: u64 = 786432; // MAX_BLOBS_PER_BLOCK * GAS_PER_BLOB
@@ -0,0 +1,69 @@
+use ethereum_types::{Address, H256};
+use serde::{Deserialize, Serialize};
+
+use super::PayloadStatus;
+use crate::types::Withdrawal;
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForkChoiceState {
+    pub head_block_hash: H256,
+    pub safe_block_hash: H256,
+    pub finalized_block_hash: H256,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PayloadAttributesV3 {
+    #[serde(with = "crate::serde_utils::u64::hex_str")]
+    pub timestamp: u64,
+    pub prev_randao: H256,
+    pub suggested_fee_recipient: Address,
+    pub withdrawals: Vec<Withdrawal>,
+    pub parent_beacon_block_root: H256,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForkChoiceResponse {
+    pub payload_status: PayloadStatus,
+    #[serde(with = "crate::serde_utils::u64::hex_str_padding_opt")]
+    pub payload_id: Option<u64>,
+}
+
+impl ForkChoiceResponse {
+    pub fn new(payload_status: PayloadStatus, payload_id: Option<u64>) -> Self {
+        Self {
+            payload_status,
+            payload_id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::PayloadValidationStatus;
+
+    #[test]
+    fn deserialize_payload_attributes() {
+        let json = r#"{"timestamp":"0x66846fb2","prevRandao":"0x2971eefd1f71f3548728cad87c16cc91b979ef035054828c59a02e49ae300a84","suggestedFeeRecipient":"0x8943545177806ed17b9f23f0a21ee5948ecaa776","withdrawals":[],"parentBeaconBlockRoot":"0x0000000000000000000000000000000000000000000000000000000000000000"}"#;
+        let attributes: PayloadAttributesV3 = serde_json::from_str(json).unwrap();
+        assert_eq!(attributes.timestamp, 0x66846fb2);
+        assert!(attributes.withdrawals.is_empty());
+    }
+
+    #[test]
+    fn serialize_fork_choice_response() {
+        let response = ForkChoiceResponse::new(
+            PayloadStatus {
+                status: PayloadValidationStatus::Valid,
+                latest_valid_hash: Some(H256::zero()),
+                validation_error: None,
+            },
+            Some(1),
+        );
+        let expected = r#"{"payloadStatus":{"status":"VALID","latestValidHash":"0x0000000000000000000000000000000000000000000000000000000000000000","validationError":null},"payloadId":"0x0000000000000001"}"#;
+        assert_eq!(serde_json::to_string(&response).unwrap(), expected);
+    }
+}
@@ -1,17 +1,22 @@
 use bytes::Bytes;
-use ethereum_types::{Address, Bloom};
+use ethereum_types::{Address, Bloom, U256};
 use keccak_hash::H256;
 use serde::{Deserialize, Serialize};
 
-use crate::rlp::decode::RLPDecode;
+use crate::rlp::{
+    decode::RLPDecode,
+    encode::RLPEncode,
+    structs::{Decoder, Encoder},
+};
 use crate::{rlp::error::RLPDecodeError, serde_utils};
 
 use crate::types::{
-    compute_withdrawals_root, BlockBody, BlockHeader, Transaction, Withdrawal, DEFAULT_OMMERS_HASH,
+    compute_withdrawals_root, BlobsBundle, Block, BlockBody, BlockHeader, Transaction, Withdrawal,
+    DEFAULT_OMMERS_HASH,
 };
 
 #[allow(unused)]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ExecutionPayloadV3 {
     parent_hash: H256,
@@ -56,6 +61,15 @@ impl<'de> Deserialize<'de> for EncodedTransaction {
     }
 }
 
+impl Serialize for EncodedTransaction {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serde_utils::bytes::serialize(&self.0, serializer)
+    }
+}
+
 #[allow(unused)]
 impl EncodedTransaction {
     /// Based on [EIP-2718]
@@ -63,7 +77,12 @@ impl EncodedTransaction {
     /// A) `TransactionType || Transaction` (Where Transaction type is an 8-bit number between 0 and 0x7f, and Transaction is an rlp encoded transaction of type TransactionType)
     /// B) `LegacyTransaction` (An rlp encoded LegacyTransaction)
     fn decode(&self) -> Result<Transaction, RLPDecodeError> {
-        Transaction::decode(self.0.as_ref())
+        Transaction::decode_canonical(self.0.as_ref())
+    }
+
+    /// Encodes a transaction following the formats described in `decode`
+    pub fn encode(tx: &Transaction) -> Self {
+        Self(Bytes::from(tx.encode_canonical_to_vec()))
     }
 }
 
@@ -112,6 +131,95 @@ impl ExecutionPayloadV3 {
             block_body,
         ))
     }
+
+    /// Converts a block into an `ExecutionPayloadV3`, the block's parent beacon block root is not part of the payload
+    pub fn from_block(block: Block) -> Self {
+        ExecutionPayloadV3 {
+            parent_hash: block.header.parent_hash,
+            fee_recipient: block.header.coinbase,
+            state_root: block.header.state_root,
+            receipts_root: block.header.receipt_root,
+            logs_bloom: block.header.logs_bloom,
+            prev_randao: block.header.prev_randao,
+            block_number: block.header.number,
+            gas_limit: block.header.gas_limit,
+            gas_used: block.header.gas_used,
+            timestamp: block.header.timestamp,
+            extra_data: block.header.extra_data.clone(),
+            base_fee_per_gas: block.header.base_fee_per_gas,
+            block_hash: block.header.compute_block_hash(),
+            transactions: block
+                .body
+                .transactions
+                .iter()
+                .map(EncodedTransaction::encode)
+                .collect(),
+            withdrawals: block.body.withdrawals.unwrap_or_default(),
+            blob_gas_used: block.header.blob_gas_used.unwrap_or_default(),
+            excess_blob_gas: block.header.excess_blob_gas.unwrap_or_default(),
+        }
+    }
+}
+
+/// Response to `engine_getPayloadV3`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionPayloadResponseV3 {
+    pub execution_payload: ExecutionPayloadV3,
+    pub block_value: U256,
+    pub blobs_bundle: BlobsBundle,
+    pub should_override_builder: bool,
+}
+
+/// A payload built for a payload id, kept along with its value and the blobs of its blob
+/// transactions until it is requested via `engine_getPayloadV3`
+#[derive(Debug, Clone)]
+pub struct BuiltPayload {
+    pub block: Block,
+    pub block_value: U256,
+    pub blobs_bundle: BlobsBundle,
+}
+
+impl RLPEncode for BuiltPayload {
+    fn encode(&self, buf: &mut dyn bytes::BufMut) {
+        Encoder::new(buf)
+            .encode_field(&self.block)
+            .encode_field(&self.block_value)
+            .encode_field(&self.blobs_bundle.commitments)
+            .encode_field(&self.blobs_bundle.proofs)
+            .encode_field(&self.blobs_bundle.blobs)
+            .finish();
+    }
+}
+
+impl RLPDecode for BuiltPayload {
+    fn decode_unfinished(rlp: &[u8]) -> Result<(BuiltPayload, &[u8]), RLPDecodeError> {
+        let decoder = Decoder::new(rlp)?;
+        let (block, decoder) = decoder.decode_field("block")?;
+        let (block_value, decoder) = decoder.decode_field("block_value")?;
+        let (commitments, decoder) = decoder.decode_field("commitments")?;
+        let (proofs, decoder) = decoder.decode_field("proofs")?;
+        let (blobs, decoder) = decoder.decode_field("blobs")?;
+        let payload = BuiltPayload {
+            block,
+            block_value,
+            blobs_bundle: BlobsBundle {
+                commitments,
+                proofs,
+                blobs,
+            },
+        };
+        Ok((payload, decoder.finish()?))
+    }
+}
+
+/// A blob along with its KZG proof, as returned by `engine_getBlobsV1`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BlobAndProofV1 {
+    #[serde(with = "crate::serde_utils::bytes")]
+    pub blob: Bytes,
+    #[serde(with = "crate::serde_utils::bytes")]
+    pub proof: Bytes,
 }
 
 #[allow(unused)]
@@ -123,6 +231,44 @@ pub struct PayloadStatus {
     pub validation_error: Option<String>,
 }
 
+impl PayloadStatus {
+    /// Creates a PayloadStatus with SYNCING status and no latest valid hash
+    pub fn syncing() -> Self {
+        PayloadStatus {
+            status: PayloadValidationStatus::Syncing,
+            latest_valid_hash: None,
+            validation_error: None,
+        }
+    }
+
+    /// Creates a PayloadStatus with VALID status and the given latest valid hash
+    pub fn valid_with_hash(hash: H256) -> Self {
+        PayloadStatus {
+            status: PayloadValidationStatus::Valid,
+            latest_valid_hash: Some(hash),
+            validation_error: None,
+        }
+    }
+
+    /// Creates a PayloadStatus with ACCEPTED status and no latest valid hash
+    pub fn accepted() -> Self {
+        PayloadStatus {
+            status: PayloadValidationStatus::Accepted,
+            latest_valid_hash: None,
+            validation_error: None,
+        }
+    }
+
+    /// Creates a PayloadStatus with INVALID status, the given latest valid hash and validation error
+    pub fn invalid_with(latest_valid_hash: Option<H256>, error: String) -> Self {
+        PayloadStatus {
+            status: PayloadValidationStatus::Invalid,
+            latest_valid_hash,
+            validation_error: Some(error),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum PayloadValidationStatus {
@@ -143,4 +289,17 @@ mod test {
         let payload: ExecutionPayloadV3 = serde_json::from_str(json).unwrap();
         assert!(payload.into_block(H256::zero()).is_ok());
     }
+
+    #[test]
+    fn payload_from_block_roundtrip() {
+        let json = r#"{"baseFeePerGas":"0x342770c0","blobGasUsed":"0x0","blockHash":"0x4029a2342bb6d54db91457bc8e442be22b3481df8edea24cc721f9d0649f65be","blockNumber":"0x1","excessBlobGas":"0x0","extraData":"0xd883010e06846765746888676f312e32322e34856c696e7578","feeRecipient":"0x8943545177806ed17b9f23f0a21ee5948ecaa776","gasLimit":"0x17dd79d","gasUsed":"0x401640","logsBloom":"0x00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000","parentHash":"0x2971eefd1f71f3548728cad87c16cc91b979ef035054828c59a02e49ae300a84","prevRandao":"0x2971eefd1f71f3548728cad87c16cc91b979ef035054828c59a02e49ae300a84","receiptsRoot":"0x0185e8473b81c3a504c4919249a94a94965a2f61c06367ee6ffb88cb7a3ef02b","stateRoot":"0x0eb8fd0af53174e65bb660d0904e5016425a713d8f11c767c26148b526fc05f3","timestamp":"0x66846fb2","transactions":["0xf86d80843baa0c4082f618946177843db3138ae69679a54b95cf345ed759450d870aa87bee538000808360306ba0151ccc02146b9b11adf516e6787b59acae3e76544fdcd75e77e67c6b598ce65da064c5dd5aae2fbb535830ebbdad0234975cd7ece3562013b63ea18cc0df6c97d4"],"withdrawals":[]}"#;
+        let payload: ExecutionPayloadV3 = serde_json::from_str(json).unwrap();
+        let (header, body) = payload.into_block(H256::zero()).unwrap();
+        let block = Block { header, body };
+        let payload = ExecutionPayloadV3::from_block(block.clone());
+        let encoded = serde_json::to_string(&payload).unwrap();
+        let decoded: ExecutionPayloadV3 = serde_json::from_str(&encoded).unwrap();
+        let (header, body) = decoded.into_block(H256::zero()).unwrap();
+        assert_eq!(Block { header, body }, block);
+    }
 }
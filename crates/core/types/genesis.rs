@@ -1,14 +1,15 @@
 use bytes::Bytes;
 use ethereum_types::{Address, Bloom, H256, U256};
 use patricia_merkle_tree::PatriciaMerkleTree;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use sha3::{Digest, Keccak256};
 use std::collections::{BTreeMap, HashMap};
 
 use crate::rlp::encode::RLPEncode as _;
 
 use super::{
-    code_hash, AccountInfo, AccountState, Block, BlockBody, BlockHeader, DEFAULT_OMMERS_HASH,
+    code_hash, AccountInfo, AccountState, Block, BlockBody, BlockHeader, BlockNumber,
+    DEFAULT_OMMERS_HASH,
 };
 
 #[allow(unused)]
@@ -34,11 +35,14 @@ pub struct Genesis {
 
 /// Blockchain settings defined per block
 #[allow(unused)]
-#[derive(Debug, Deserialize, Default, PartialEq)]
+#[derive(Debug, Clone, Deserialize, Serialize, Default, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct ChainConfig {
     /// Current chain identifier
-    #[serde(deserialize_with = "crate::serde_utils::u256::deser_number")]
+    #[serde(
+        deserialize_with = "crate::serde_utils::u256::deser_number",
+        serialize_with = "crate::serde_utils::u256::serialize_number"
+    )]
     pub chain_id: U256,
 
     /// Block numbers for the block where each fork was activated
@@ -75,7 +79,9 @@ pub struct ChainConfig {
     /// Amount of total difficulty reached by the network that triggers the consensus upgrade.
     #[serde(
         default,
-        deserialize_with = "crate::serde_utils::u256::deser_number_opt"
+        deserialize_with = "crate::serde_utils::u256::deser_number_opt",
+        serialize_with = "crate::serde_utils::u256::serialize_number_opt",
+        skip_serializing_if = "Option::is_none"
     )]
     pub terminal_total_difficulty: Option<U256>,
     /// Network has already passed the terminal total difficult
@@ -83,6 +89,108 @@ pub struct ChainConfig {
     pub terminal_total_difficulty_passed: bool,
 }
 
+/// Ethereum hard forks, in activation order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Fork {
+    Frontier,
+    Homestead,
+    DaoFork,
+    Tangerine,
+    SpuriousDragon,
+    Byzantium,
+    Constantinople,
+    Petersburg,
+    Istanbul,
+    MuirGlacier,
+    Berlin,
+    London,
+    ArrowGlacier,
+    GrayGlacier,
+    Paris,
+    Shanghai,
+    Cancun,
+    Prague,
+}
+
+impl ChainConfig {
+    /// Returns the fork active for a block with the given number and timestamp
+    pub fn get_fork(&self, block_number: BlockNumber, timestamp: u64) -> Fork {
+        // Forks after the merge are activated by timestamp
+        let time_forks = [
+            (self.prague_time, Fork::Prague),
+            (self.cancun_time, Fork::Cancun),
+            (self.shanghai_time, Fork::Shanghai),
+        ];
+        if let Some((_, fork)) = time_forks
+            .into_iter()
+            .find(|(activation, _)| is_active(*activation, timestamp))
+        {
+            return fork;
+        }
+        if self.is_paris_activated(block_number) {
+            return Fork::Paris;
+        }
+        self.block_forks()
+            .into_iter()
+            .find(|(activation, _)| is_active(*activation, block_number))
+            .map(|(_, fork)| fork)
+            .unwrap_or(Fork::Frontier)
+    }
+
+    /// Returns the forks activated by block number along with their activation blocks, from the
+    /// latest to the earliest one
+    fn block_forks(&self) -> [(Option<BlockNumber>, Fork); 13] {
+        [
+            (self.gray_glacier_block, Fork::GrayGlacier),
+            (self.arrow_glacier_block, Fork::ArrowGlacier),
+            (self.london_block, Fork::London),
+            (self.berlin_block, Fork::Berlin),
+            (self.muir_glacier_block, Fork::MuirGlacier),
+            (self.istanbul_block, Fork::Istanbul),
+            (self.petersburg_block, Fork::Petersburg),
+            (self.constantinople_block, Fork::Constantinople),
+            (self.byzantium_block, Fork::Byzantium),
+            (self.eip158_block, Fork::SpuriousDragon),
+            (self.eip150_block, Fork::Tangerine),
+            (self.dao_fork_block, Fork::DaoFork),
+            (self.homestead_block, Fork::Homestead),
+        ]
+    }
+
+    /// Returns true if the merge took place at or before the given block number
+    /// The merge is triggered by total difficulty, which is not tracked, so its block is only
+    /// known from a zero terminal total difficulty or the merge netsplit block. Otherwise, a chain
+    /// that already passed the terminal total difficulty is only considered merged after its
+    /// last fork activated by block number, as the blocks before it can't be post-merge
+    pub fn is_paris_activated(&self, block_number: BlockNumber) -> bool {
+        if self.terminal_total_difficulty == Some(U256::zero()) {
+            return true;
+        }
+        if let Some(merge_netsplit_block) = self.merge_netsplit_block {
+            return merge_netsplit_block <= block_number;
+        }
+        self.terminal_total_difficulty_passed
+            && self
+                .block_forks()
+                .into_iter()
+                .filter_map(|(activation, _)| activation)
+                .all(|activation| activation < block_number)
+    }
+
+    pub fn is_shanghai_activated(&self, timestamp: u64) -> bool {
+        is_active(self.shanghai_time, timestamp)
+    }
+
+    pub fn is_cancun_activated(&self, timestamp: u64) -> bool {
+        is_active(self.cancun_time, timestamp)
+    }
+}
+
+/// Checks if a fork with the given activation block number or timestamp is active at `value`
+fn is_active(activation: Option<u64>, value: u64) -> bool {
+    activation.is_some_and(|activation| activation <= value)
+}
+
 #[allow(unused)]
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct GenesisAccount {
@@ -254,6 +362,69 @@ mod tests {
         );
     }
 
+    #[test]
+    fn chain_config_get_fork() {
+        let config = ChainConfig {
+            london_block: Some(0),
+            merge_netsplit_block: Some(10),
+            shanghai_time: Some(100),
+            cancun_time: Some(200),
+            ..Default::default()
+        };
+        assert_eq!(config.get_fork(0, 0), Fork::London);
+        assert_eq!(config.get_fork(10, 50), Fork::Paris);
+        assert_eq!(config.get_fork(11, 100), Fork::Shanghai);
+        assert_eq!(config.get_fork(12, 199), Fork::Shanghai);
+        assert_eq!(config.get_fork(13, 200), Fork::Cancun);
+        assert_eq!(ChainConfig::default().get_fork(0, 0), Fork::Frontier);
+    }
+
+    #[test]
+    fn chain_config_paris_activation() {
+        // The netsplit block decides even if the terminal total difficulty was passed
+        let config = ChainConfig {
+            london_block: Some(0),
+            merge_netsplit_block: Some(10),
+            terminal_total_difficulty: Some(U256::from(100)),
+            terminal_total_difficulty_passed: true,
+            ..Default::default()
+        };
+        assert!(!config.is_paris_activated(9));
+        assert!(config.is_paris_activated(10));
+        // Without it, the flag doesn't apply to the blocks of the earlier forks
+        let config = ChainConfig {
+            london_block: Some(5),
+            gray_glacier_block: Some(8),
+            terminal_total_difficulty: Some(U256::from(100)),
+            terminal_total_difficulty_passed: true,
+            ..Default::default()
+        };
+        assert_eq!(config.get_fork(5, 0), Fork::London);
+        assert_eq!(config.get_fork(8, 0), Fork::GrayGlacier);
+        assert_eq!(config.get_fork(9, 0), Fork::Paris);
+        let config = ChainConfig {
+            terminal_total_difficulty_passed: false,
+            ..config
+        };
+        assert_eq!(config.get_fork(9, 0), Fork::GrayGlacier);
+        let config = ChainConfig {
+            terminal_total_difficulty: Some(U256::zero()),
+            ..config
+        };
+        assert_eq!(config.get_fork(0, 0), Fork::Paris);
+    }
+
+    #[test]
+    fn chain_config_serde_roundtrip() {
+        let file = File::open("../../test_data/genesis.json").expect("Failed to open genesis file");
+        let reader = BufReader::new(file);
+        let genesis: Genesis =
+            serde_json::from_reader(reader).expect("Failed to deserialize genesis file");
+        let encoded = serde_json::to_string(&genesis.config).unwrap();
+        let decoded: ChainConfig = serde_json::from_str(&encoded).unwrap();
+        assert_eq!(decoded, genesis.config);
+    }
+
     #[test]
     fn genesis_block() {
         // Deserialize genesis file
@@ -1,15 +1,19 @@
 mod account;
+mod blobs_bundle;
 mod block;
 mod constants;
 mod engine;
 mod genesis;
+mod proof;
 mod receipt;
 mod transaction;
 
 pub use account::*;
+pub use blobs_bundle::*;
 pub use block::*;
 pub use constants::*;
 pub use engine::*;
 pub use genesis::*;
+pub use proof::*;
 pub use receipt::*;
 pub use transaction::*;
@@ -0,0 +1,256 @@
+use bytes::Bytes;
+use ethereum_types::{Address, H256, U256};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest as _, Keccak256};
+use thiserror::Error;
+
+use crate::rlp::{
+    decode::{decode_rlp_item, get_item_with_prefix, RLPDecode},
+    error::RLPDecodeError,
+};
+
+use super::{AccountState, EMPTY_KECCACK_HASH, EMPTY_TRIE_HASH};
+
+/// Merkle proof of an account and some of its storage slots, as returned by `eth_getProof`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountProof {
+    pub address: Address,
+    /// Encoded state trie nodes along the path from the state root to the account
+    #[serde(with = "crate::serde_utils::bytes::vec")]
+    pub account_proof: Vec<Bytes>,
+    pub balance: U256,
+    pub code_hash: H256,
+    #[serde(with = "crate::serde_utils::u64::hex_str")]
+    pub nonce: u64,
+    pub storage_hash: H256,
+    pub storage_proof: Vec<StorageProof>,
+}
+
+/// Merkle proof of a storage slot against the storage root of its account
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StorageProof {
+    pub key: H256,
+    pub value: U256,
+    /// Encoded storage trie nodes along the path from the storage root to the slot
+    #[serde(with = "crate::serde_utils::bytes::vec")]
+    pub proof: Vec<Bytes>,
+}
+
+#[derive(Debug, Error)]
+pub enum ProofError {
+    #[error("Proof is missing the node with hash {0:#x}")]
+    MissingNode(H256),
+    #[error("Proof node doesn't match its expected hash {0:#x}")]
+    HashMismatch(H256),
+    #[error("Invalid proof node: {0}")]
+    InvalidNode(#[from] RLPDecodeError),
+    #[error("Account state doesn't match the proof")]
+    AccountMismatch,
+    #[error("Storage value of slot {0:#x} doesn't match the proof")]
+    StorageMismatch(H256),
+}
+
+impl AccountProof {
+    /// Checks that the account state and storage values claimed by the proof are the ones in the
+    /// state with the given root, such as the `state_root` of a block header
+    pub fn verify(&self, state_root: H256) -> Result<(), ProofError> {
+        let claimed_state = AccountState {
+            nonce: self.nonce,
+            balance: self.balance,
+            storage_root: self.storage_hash,
+            code_hash: self.code_hash,
+        };
+        let account_path = Keccak256::digest(self.address);
+        let account_state = match verify_proof(state_root, &account_path, &self.account_proof)? {
+            Some(encoded) => AccountState::decode(&encoded)?,
+            // Accounts that don't exist are reported as empty accounts
+            None => AccountState {
+                nonce: 0,
+                balance: U256::zero(),
+                storage_root: *EMPTY_TRIE_HASH,
+                code_hash: *EMPTY_KECCACK_HASH,
+            },
+        };
+        if account_state != claimed_state {
+            return Err(ProofError::AccountMismatch);
+        }
+        for storage_proof in &self.storage_proof {
+            let slot_path = Keccak256::digest(storage_proof.key);
+            let value = match verify_proof(self.storage_hash, &slot_path, &storage_proof.proof)? {
+                Some(encoded) => U256::decode(&encoded)?,
+                // Slots that are not in the trie hold a zero value
+                None => U256::zero(),
+            };
+            if value != storage_proof.value {
+                return Err(ProofError::StorageMismatch(storage_proof.key));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Verifies a Merkle proof, that is, the encoded trie nodes along the path to a key, against the
+/// root of the trie
+/// Returns the value stored under the key, or `None` if the proof shows that the key is not in the trie
+pub fn verify_proof(
+    root: H256,
+    key: &[u8],
+    proof: &[Bytes],
+) -> Result<Option<Vec<u8>>, ProofError> {
+    if root == *EMPTY_TRIE_HASH {
+        return Ok(None);
+    }
+    let path: Vec<u8> = key.iter().flat_map(|b| [b >> 4, b & 0x0f]).collect();
+    let mut path = path.as_slice();
+    let mut proof = proof.iter();
+    let mut node = next_proof_node(&mut proof, root)?;
+    loop {
+        let (true, mut payload, _) = decode_rlp_item(&node)? else {
+            return Err(RLPDecodeError::UnexpectedString.into());
+        };
+        let mut items = Vec::new();
+        while !payload.is_empty() {
+            let (item, rest) = get_item_with_prefix(payload)?;
+            items.push(item);
+            payload = rest;
+        }
+        let child = match items.as_slice() {
+            // Leaf or extension node
+            [encoded_path, second] => {
+                let (node_path, is_leaf) = decode_path(decode_bytes(encoded_path)?)?;
+                if is_leaf {
+                    if node_path != path {
+                        return Ok(None);
+                    }
+                    return Ok(Some(decode_bytes(second)?.to_vec()));
+                }
+                let Some(rest) = path.strip_prefix(node_path.as_slice()) else {
+                    return Ok(None);
+                };
+                path = rest;
+                *second
+            }
+            // Branch node
+            [children @ .., value] if children.len() == 16 => match path.split_first() {
+                Some((nibble, rest)) => {
+                    path = rest;
+                    children[*nibble as usize]
+                }
+                None => {
+                    let value = decode_bytes(value)?;
+                    return Ok((!value.is_empty()).then(|| value.to_vec()));
+                }
+            },
+            _ => return Err(RLPDecodeError::MalformedData.into()),
+        };
+        node = match decode_rlp_item(child)? {
+            // Nodes shorter than a hash are embedded in their parent
+            (true, _, _) => child.to_vec(),
+            (false, [], _) => return Ok(None),
+            (false, hash, _) if hash.len() == 32 => {
+                next_proof_node(&mut proof, H256::from_slice(hash))?
+            }
+            _ => return Err(RLPDecodeError::MalformedData.into()),
+        };
+    }
+}
+
+/// Takes the next node of the proof, which must have the given hash
+fn next_proof_node<'a>(
+    proof: &mut impl Iterator<Item = &'a Bytes>,
+    hash: H256,
+) -> Result<Vec<u8>, ProofError> {
+    let node = proof.next().ok_or(ProofError::MissingNode(hash))?;
+    if Keccak256::digest(node).as_slice() != hash.as_bytes() {
+        return Err(ProofError::HashMismatch(hash));
+    }
+    Ok(node.to_vec())
+}
+
+fn decode_bytes(encoded: &[u8]) -> Result<&[u8], RLPDecodeError> {
+    match decode_rlp_item(encoded)? {
+        (false, payload, _) => Ok(payload),
+        (true, _, _) => Err(RLPDecodeError::UnexpectedList),
+    }
+}
+
+/// Decodes a hex-prefix encoded path into nibbles, along with whether it belongs to a leaf
+fn decode_path(encoded: &[u8]) -> Result<(Vec<u8>, bool), RLPDecodeError> {
+    let (first, rest) = encoded.split_first().ok_or(RLPDecodeError::InvalidLength)?;
+    let flag = first >> 4;
+    if flag > 3 {
+        return Err(RLPDecodeError::MalformedData);
+    }
+    let mut path = Vec::with_capacity(rest.len() * 2 + 1);
+    if flag & 1 == 1 {
+        path.push(first & 0x0f);
+    }
+    path.extend(rest.iter().flat_map(|b| [b >> 4, b & 0x0f]));
+    Ok((path, flag & 2 == 2))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rlp::{encode::RLPEncode, structs::Encoder};
+
+    use super::*;
+
+    /// Encodes a leaf node holding the value under the key, as the only node of a trie
+    fn single_leaf_trie(key: &[u8], value: &[u8]) -> (H256, Bytes) {
+        // Even-length leaf path, flagged by a 0x20 prefix
+        let path = [&[0x20], key].concat();
+        let mut leaf = Vec::new();
+        Encoder::new(&mut leaf)
+            .encode_raw(&path.as_slice().encode_to_vec())
+            .encode_raw(&value.encode_to_vec())
+            .finish();
+        (H256(Keccak256::digest(&leaf).into()), Bytes::from(leaf))
+    }
+
+    #[test]
+    fn verify_single_leaf_proof() {
+        let (root, leaf) = single_leaf_trie(&[0x12, 0x34], b"kiwi");
+        let proof = [leaf];
+        assert_eq!(
+            verify_proof(root, &[0x12, 0x34], &proof).unwrap(),
+            Some(b"kiwi".to_vec())
+        );
+        // The leaf proves that no other key is in the trie
+        assert_eq!(verify_proof(root, &[0x12, 0x35], &proof).unwrap(), None);
+        assert!(matches!(
+            verify_proof(H256::zero(), &[0x12, 0x34], &proof),
+            Err(ProofError::HashMismatch(_))
+        ));
+        assert!(matches!(
+            verify_proof(root, &[0x12, 0x34], &[]),
+            Err(ProofError::MissingNode(_))
+        ));
+    }
+
+    #[test]
+    fn verify_missing_account_proof() {
+        let proof = AccountProof {
+            address: Address::random(),
+            account_proof: vec![],
+            balance: U256::zero(),
+            code_hash: *EMPTY_KECCACK_HASH,
+            nonce: 0,
+            storage_hash: *EMPTY_TRIE_HASH,
+            storage_proof: vec![StorageProof {
+                key: H256::random(),
+                value: U256::zero(),
+                proof: vec![],
+            }],
+        };
+        proof.verify(*EMPTY_TRIE_HASH).unwrap();
+        let proof = AccountProof {
+            balance: U256::one(),
+            ..proof
+        };
+        assert!(matches!(
+            proof.verify(*EMPTY_TRIE_HASH),
+            Err(ProofError::AccountMismatch)
+        ));
+    }
+}
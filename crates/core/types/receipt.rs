@@ -5,7 +5,7 @@ use crate::rlp::{
     structs::{Decoder, Encoder},
 };
 use bytes::Bytes;
-use ethereum_types::{Address, Bloom, H256};
+use ethereum_types::{Address, Bloom, BloomInput, H256};
 use serde::Serialize;
 
 use super::{BlockHash, BlockNumber, TxKind, TxType};
@@ -96,10 +96,10 @@ impl RLPDecode for Receipt {
 /// Data record produced during the execution of a transaction.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub struct Log {
-    address: Address,
-    topics: Vec<H256>,
+    pub address: Address,
+    pub topics: Vec<H256>,
     #[serde(with = "crate::serde_utils::bytes")]
-    data: Bytes,
+    pub data: Bytes,
 }
 
 impl RLPEncode for Log {
@@ -127,6 +127,18 @@ impl RLPDecode for Log {
     }
 }
 
+/// Computes the bloom filter for the given logs, as described in the yellow paper (section 4.4.1)
+pub fn bloom_from_logs(logs: &[Log]) -> Bloom {
+    let mut bloom = Bloom::zero();
+    for log in logs {
+        bloom.accrue(BloomInput::Raw(log.address.as_ref()));
+        for topic in log.topics.iter() {
+            bloom.accrue(BloomInput::Raw(topic.as_ref()));
+        }
+    }
+    bloom
+}
+
 // Struct used by RPC
 #[derive(Debug, Serialize)]
 pub struct ReceiptWithTxAndBlockInfo {
@@ -4,6 +4,7 @@ use secp256k1::{ecdsa::RecoveryId, Message, SECP256K1};
 use serde::{ser::SerializeStruct, Serialize};
 pub use serde_impl::{AccessListEntry, GenericTransaction};
 use sha3::{Digest, Keccak256};
+use thiserror::Error;
 
 use crate::rlp::{
     constants::RLP_NULL,
@@ -13,7 +14,12 @@ use crate::rlp::{
     structs::{Decoder, Encoder},
 };
 
-use super::ReceiptTxInfo;
+use super::{BlobsBundle, ReceiptTxInfo};
+
+/// The signature of a transaction is not valid, so its sender can't be recovered
+#[derive(Debug, Error)]
+#[error("Invalid transaction signature, the sender can't be recovered")]
+pub struct InvalidSignatureError;
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 #[serde(untagged)]
@@ -24,7 +30,7 @@ pub enum Transaction {
     EIP4844Transaction(EIP4844Transaction),
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
 pub struct LegacyTransaction {
     pub nonce: u64,
     pub gas_price: u64,
@@ -39,7 +45,7 @@ pub struct LegacyTransaction {
     pub s: U256,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
 pub struct EIP2930Transaction {
     pub chain_id: u64,
     pub nonce: u64,
@@ -54,7 +60,7 @@ pub struct EIP2930Transaction {
     pub signature_s: U256,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
 pub struct EIP1559Transaction {
     pub chain_id: u64,
     pub nonce: u64,
@@ -70,7 +76,7 @@ pub struct EIP1559Transaction {
     pub signature_s: U256,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
 pub struct EIP4844Transaction {
     pub chain_id: u64,
     pub nonce: u64,
@@ -88,6 +94,15 @@ pub struct EIP4844Transaction {
     pub signature_s: U256,
 }
 
+/// Network representation of a blob transaction, which carries the blobs along with their
+/// KZG commitments and proofs
+/// Encoded as `0x03 || rlp([tx_payload_body, blobs, commitments, proofs])`, see [EIP-4844]
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct WrappedEIP4844Transaction {
+    pub tx: EIP4844Transaction,
+    pub blobs_bundle: BlobsBundle,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
 pub enum TxType {
     #[default]
@@ -161,9 +176,10 @@ impl RLPDecode for Transaction {
 }
 
 /// The transaction's kind: call or create.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
 pub enum TxKind {
     Call(Address),
+    #[default]
     Create,
 }
 
@@ -409,9 +425,62 @@ impl RLPDecode for EIP4844Transaction {
     }
 }
 
+impl RLPEncode for WrappedEIP4844Transaction {
+    fn encode(&self, buf: &mut dyn bytes::BufMut) {
+        // The inner transaction is included as a list of fields, without its type
+        let encoded_tx = self.tx.encode_to_vec();
+        let tx_fields = &get_rlp_bytes_item_payload(&encoded_tx)[1..];
+        Encoder::new(buf)
+            .encode_raw(tx_fields)
+            .encode_field(&self.blobs_bundle.blobs)
+            .encode_field(&self.blobs_bundle.commitments)
+            .encode_field(&self.blobs_bundle.proofs)
+            .finish();
+    }
+}
+
+impl RLPDecode for WrappedEIP4844Transaction {
+    fn decode_unfinished(rlp: &[u8]) -> Result<(WrappedEIP4844Transaction, &[u8]), RLPDecodeError> {
+        let decoder = Decoder::new(rlp)?;
+        let (tx, decoder) = decoder.decode_field("tx")?;
+        let (blobs, decoder) = decoder.decode_field("blobs")?;
+        let (commitments, decoder) = decoder.decode_field("commitments")?;
+        let (proofs, decoder) = decoder.decode_field("proofs")?;
+        let wrapped_tx = WrappedEIP4844Transaction {
+            tx,
+            blobs_bundle: BlobsBundle {
+                commitments,
+                proofs,
+                blobs,
+            },
+        };
+        Ok((wrapped_tx, decoder.finish()?))
+    }
+}
+
+impl WrappedEIP4844Transaction {
+    /// Encodes the transaction in its network form: `0x03 || rlp([tx_payload_body, blobs, commitments, proofs])`
+    pub fn encode_canonical_to_vec(&self) -> Vec<u8> {
+        let mut buf = vec![TxType::EIP4844 as u8];
+        self.encode(&mut buf);
+        buf
+    }
+
+    /// Decodes a transaction encoded in its network form (see `encode_canonical_to_vec`)
+    pub fn decode_canonical(bytes: &[u8]) -> Result<Self, RLPDecodeError> {
+        match bytes.split_first() {
+            Some((tx_type, rlp)) if *tx_type == TxType::EIP4844 as u8 => Self::decode(rlp),
+            _ => Err(RLPDecodeError::Custom(
+                "Expected a blob transaction with its blobs bundle".to_string(),
+            )),
+        }
+    }
+}
+
 impl Transaction {
-    pub fn sender(&self) -> Address {
-        match self {
+    /// Recovers the address of the transaction's signer, fails if the signature is invalid
+    pub fn sender(&self) -> Result<Address, InvalidSignatureError> {
+        let sender = match self {
             Transaction::LegacyTransaction(tx) => {
                 let signature_y_parity = match self.chain_id() {
                     Some(chain_id) => tx.v.as_u64().saturating_sub(35 + chain_id * 2) != 0,
@@ -502,7 +571,8 @@ impl Transaction {
                     &Bytes::from(buf),
                 )
             }
-        }
+        };
+        sender.ok_or(InvalidSignatureError)
     }
 
     pub fn gas_limit(&self) -> u64 {
@@ -550,6 +620,16 @@ impl Transaction {
         }
     }
 
+    /// Returns the amount per unit of gas paid to the block's coinbase given the block's base fee
+    /// Returns None if the transaction's max fee per gas can't cover the base fee
+    pub fn effective_gas_tip(&self, base_fee: u64) -> Option<u64> {
+        let tip = self.gas_price().checked_sub(base_fee)?;
+        Some(match self.max_priority_fee() {
+            Some(max_priority_fee) => tip.min(max_priority_fee),
+            None => tip,
+        })
+    }
+
     pub fn chain_id(&self) -> Option<u64> {
         match self {
             Transaction::LegacyTransaction(tx) => derive_legacy_chain_id(tx.v),
@@ -604,19 +684,44 @@ impl Transaction {
         }
     }
 
+    /// Encodes the transaction in the format used by the engine and eth apis:
+    /// `TransactionType || Transaction` for typed transactions and `LegacyTransaction` for legacy ones
+    pub fn encode_canonical_to_vec(&self) -> Vec<u8> {
+        let encoded = self.encode_to_vec();
+        match self {
+            Transaction::LegacyTransaction(_) => encoded,
+            // Typed transactions are encoded as an rlp bytes item, we only need its payload
+            _ => get_rlp_bytes_item_payload(&encoded).to_vec(),
+        }
+    }
+
+    /// Decodes a transaction encoded in the format used by the engine and eth apis
+    /// (see `encode_canonical_to_vec`)
+    pub fn decode_canonical(bytes: &[u8]) -> Result<Self, RLPDecodeError> {
+        match bytes.first() {
+            // Typed transaction, wrap it as an rlp bytes item in order to decode it
+            Some(tx_type) if *tx_type < 0x7f => {
+                Transaction::decode(&Bytes::copy_from_slice(bytes).encode_to_vec())
+            }
+            Some(_) => Transaction::decode(bytes),
+            None => Err(RLPDecodeError::InvalidLength),
+        }
+    }
+
+    /// Computes the transaction hash, the hash of its canonical encoding
     pub fn compute_hash(&self) -> H256 {
-        keccak_hash::keccak(self.encode_to_vec())
+        keccak_hash::keccak(self.encode_canonical_to_vec())
     }
 
-    pub fn receipt_info(&self, index: u64) -> ReceiptTxInfo {
-        ReceiptTxInfo {
+    pub fn receipt_info(&self, index: u64) -> Result<ReceiptTxInfo, InvalidSignatureError> {
+        Ok(ReceiptTxInfo {
             transaction_hash: self.compute_hash(),
             transaction_index: index,
-            from: self.sender(),
+            from: self.sender()?,
             to: self.to(),
             effective_gas_price: self.gas_price(),
             blob_gas_price: self.max_fee_per_blob_gas().map(|x| x.as_u64()),
-        }
+        })
     }
 }
 
@@ -625,7 +730,7 @@ fn recover_address(
     signature_s: &U256,
     signature_y_parity: bool,
     message: &Bytes,
-) -> Address {
+) -> Option<Address> {
     // Create signature
     let mut signature_bytes = [0; 64];
     signature_r.to_big_endian(&mut signature_bytes[0..32]);
@@ -634,7 +739,7 @@ fn recover_address(
         &signature_bytes,
         RecoveryId::from_i32(signature_y_parity as i32).unwrap(), // cannot fail
     )
-    .unwrap();
+    .ok()?;
     // Hash message
     let msg_digest: [u8; 32] = Keccak256::new_with_prefix(message.as_ref())
         .finalize()
@@ -642,10 +747,10 @@ fn recover_address(
     // Recover public key
     let public = SECP256K1
         .recover_ecdsa(&Message::from_digest(msg_digest), &signature)
-        .unwrap();
+        .ok()?;
     // Hash public key to obtain address
     let hash = Keccak256::new_with_prefix(&public.serialize_uncompressed()[1..]).finalize();
-    Address::from_slice(&hash[12..])
+    Some(Address::from_slice(&hash[12..]))
 }
 
 fn derive_legacy_chain_id(v: U256) -> Option<u64> {
@@ -877,21 +982,23 @@ mod serde_impl {
     }
 
     /// Unsigned Transaction struct generic to all types which may not contain all required transaction fields
-    /// Used to perform gas estimations and access list creation
-    #[derive(Deserialize, Debug, PartialEq)]
+    /// Used to perform calls, gas estimations and access list creation
+    #[derive(Deserialize, Debug, Clone, PartialEq)]
     #[serde(rename_all = "camelCase")]
     pub struct GenericTransaction {
         #[serde(default)]
         pub r#type: TxType,
-        #[serde(with = "crate::serde_utils::u64::hex_str")]
-        pub nonce: u64,
+        #[serde(default, with = "crate::serde_utils::u64::hex_str_opt")]
+        pub nonce: Option<u64>,
+        #[serde(default)]
         pub to: TxKind,
+        #[serde(default)]
         pub from: Address,
         #[serde(default, with = "crate::serde_utils::u64::hex_str_opt")]
         pub gas: Option<u64>,
         #[serde(default)]
         pub value: U256,
-        #[serde(default, with = "crate::serde_utils::bytes")]
+        #[serde(default, alias = "data", with = "crate::serde_utils::bytes")]
         pub input: Bytes,
         #[serde(default, with = "crate::serde_utils::u64::hex_str")]
         pub gas_price: u64,
@@ -1081,7 +1188,7 @@ mod tests {
         }"#;
         let deserialized_generic_transaction = GenericTransaction {
             r#type: TxType::EIP2930,
-            nonce: 2,
+            nonce: Some(2),
             to: TxKind::Create,
             from: Address::from_slice(
                 &hex::decode("6177843db3138ae69679A54b95cf345ED759450d").unwrap(),
@@ -1108,4 +1215,39 @@ mod tests {
             serde_json::from_str(generic_transaction).unwrap()
         )
     }
+
+    #[test]
+    fn wrapped_eip4844_tx_roundtrip() {
+        let wrapped_tx = WrappedEIP4844Transaction {
+            tx: EIP4844Transaction {
+                chain_id: 3151908,
+                nonce: 1,
+                gas: 21000,
+                blob_versioned_hashes: vec![H256::from_low_u64_be(1)],
+                ..Default::default()
+            },
+            blobs_bundle: BlobsBundle {
+                commitments: vec![Bytes::from(vec![1; 48])],
+                proofs: vec![Bytes::from(vec![2; 48])],
+                blobs: vec![Bytes::from(vec![3; 131072])],
+            },
+        };
+        let encoded = wrapped_tx.encode_canonical_to_vec();
+        assert_eq!(encoded[0], TxType::EIP4844 as u8);
+        let decoded = WrappedEIP4844Transaction::decode_canonical(&encoded).unwrap();
+        assert_eq!(decoded, wrapped_tx);
+        // The canonical form without the blobs bundle is not a valid network blob transaction
+        let tx = Transaction::EIP4844Transaction(wrapped_tx.tx);
+        assert!(
+            WrappedEIP4844Transaction::decode_canonical(&tx.encode_canonical_to_vec()).is_err()
+        );
+    }
+
+    #[test]
+    fn decode_canonical_rejects_empty_input() {
+        assert!(matches!(
+            Transaction::decode_canonical(&[]),
+            Err(RLPDecodeError::InvalidLength)
+        ));
+    }
 }
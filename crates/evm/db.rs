@@ -1,25 +1,29 @@
-use ethereum_rust_core::{Address as CoreAddress, H256 as CoreH256};
+use ethereum_rust_core::{types::BlockNumber, Address as CoreAddress, H256 as CoreH256};
 use ethereum_rust_storage::{error::StoreError, Store};
 use revm::primitives::{
     AccountInfo as RevmAccountInfo, Address as RevmAddress, Bytecode as RevmBytecode,
     Bytes as RevmBytes, B256 as RevmB256, U256 as RevmU256,
 };
 
-pub struct StoreWrapper(pub Store);
+/// Database used by the EVM, which reads the state at the end of the given block from the store
+pub struct StoreWrapper {
+    pub store: Store,
+    pub block_number: BlockNumber,
+}
 
 impl revm::Database for StoreWrapper {
     type Error = StoreError;
 
     fn basic(&mut self, address: RevmAddress) -> Result<Option<RevmAccountInfo>, Self::Error> {
         let acc_info = match self
-            .0
-            .get_account_info(CoreAddress::from(address.0.as_ref()))?
+            .store
+            .get_account_info_at_block(self.block_number, CoreAddress::from(address.0.as_ref()))?
         {
             None => return Ok(None),
             Some(acc_info) => acc_info,
         };
         let code = self
-            .0
+            .store
             .get_account_code(acc_info.code_hash)?
             .map(|b| RevmBytecode::new_raw(RevmBytes(b)));
 
@@ -32,7 +36,7 @@ impl revm::Database for StoreWrapper {
     }
 
     fn code_by_hash(&mut self, code_hash: RevmB256) -> Result<RevmBytecode, Self::Error> {
-        self.0
+        self.store
             .get_account_code(CoreH256::from(code_hash.as_ref()))?
             .map(|b| RevmBytecode::new_raw(RevmBytes(b)))
             .ok_or_else(|| StoreError::Custom(format!("No code for hash {code_hash}")))
@@ -40,8 +44,9 @@ impl revm::Database for StoreWrapper {
 
     fn storage(&mut self, address: RevmAddress, index: RevmU256) -> Result<RevmU256, Self::Error> {
         Ok(self
-            .0
-            .get_storage_at(
+            .store
+            .get_storage_at_block(
+                self.block_number,
                 CoreAddress::from(address.0.as_ref()),
                 CoreH256::from(index.to_be_bytes()),
             )?
@@ -50,7 +55,7 @@ impl revm::Database for StoreWrapper {
     }
 
     fn block_hash(&mut self, number: RevmU256) -> Result<RevmB256, Self::Error> {
-        self.0
+        self.store
             .get_block_header(number.to())?
             .map(|header| RevmB256::from_slice(&header.compute_block_hash().0))
             .ok_or_else(|| StoreError::Custom(format!("Block {number} not found")))
@@ -1,22 +1,25 @@
 mod db;
 mod errors;
 mod execution_result;
+mod kzg;
 
 use db::StoreWrapper;
 use ethereum_rust_core::{
     types::{
-        AccountInfo, BlockHeader, GenericTransaction, Transaction, TxKind, Withdrawal, GWEI_TO_WEI,
+        bloom_from_logs, AccountInfo, Block, BlockHeader, BlockNumber, ChainConfig, Fork,
+        GenericTransaction, Receipt, StateOverrides, Transaction, TxKind, Withdrawal, GWEI_TO_WEI,
     },
     Address, BigEndianHash, H256, U256,
 };
-use ethereum_rust_storage::{error::StoreError, Store};
+use ethereum_rust_storage::{error::StoreError, AccountUpdate, Store};
+use lazy_static::lazy_static;
 use revm::{
-    db::states::bundle_state::BundleRetention,
+    db::states::{bundle_state::BundleRetention, CacheAccount},
     inspector_handle_register,
     inspectors::TracerEip3155,
     precompile::{PrecompileSpecId, Precompiles},
-    primitives::{BlockEnv, TxEnv, B256, U256 as RevmU256},
-    Database, Evm,
+    primitives::{BlobExcessGasAndPrice, BlockEnv, Bytecode, TxEnv, B256, U256 as RevmU256},
+    Database, DatabaseCommit, Evm,
 };
 use revm_inspectors::access_list::AccessListInspector;
 // Rename imported types for clarity
@@ -25,10 +28,19 @@ use revm_primitives::{AccessList as RevmAccessList, AccessListItem as RevmAccess
 // Export needed types
 pub use errors::EvmError;
 pub use execution_result::*;
+pub use kzg::{kzg_commitment_to_versioned_hash, verify_blob_kzg_proof_batch};
 pub use revm::primitives::SpecId;
 
 type AccessList = Vec<(Address, Vec<H256>)>;
 
+lazy_static! {
+    // See [EIP-4788](https://eips.ethereum.org/EIPS/eip-4788)
+    static ref SYSTEM_ADDRESS: RevmAddress = RevmAddress::from_slice(&hex::decode("fffffffffffffffffffffffffffffffffffffffe").unwrap());
+    static ref BEACON_ROOTS_ADDRESS: RevmAddress = RevmAddress::from_slice(&hex::decode("000F3df6D732807Ef1319fB7B8bB8522d0Beac02").unwrap());
+}
+/// Gas limit used for system contract calls, as defined by EIP-4788
+const SYSTEM_CALL_GAS_LIMIT: u64 = 30_000_000;
+
 /// State used when running the EVM
 // Encapsulates state behaviour to be agnostic to the evm implementation for crate users
 pub struct EvmState(revm::db::State<StoreWrapper>);
@@ -36,10 +48,46 @@ pub struct EvmState(revm::db::State<StoreWrapper>);
 impl EvmState {
     /// Get a reference to inner `Store` database
     pub fn database(&self) -> &Store {
-        &self.0.database.0
+        &self.0.database.store
     }
 }
 
+/// Executes all transactions in a block, applies its withdrawals and returns the resulting receipts
+/// State transitions are stored in the EvmState and can be obtained via `get_state_transitions`
+pub fn execute_block(
+    block: &Block,
+    state: &mut EvmState,
+    spec_id: SpecId,
+) -> Result<Vec<Receipt>, EvmError> {
+    let block_header = &block.header;
+    // EIP-4788: The beacon roots contract is called before executing the block's transactions
+    if block_header.parent_beacon_block_root.is_some() && spec_id >= SpecId::CANCUN {
+        beacon_root_contract_call(state, block_header, spec_id)?;
+    }
+
+    let mut receipts = Vec::new();
+    let mut cumulative_gas_used = 0;
+    for transaction in block.body.transactions.iter() {
+        let result = execute_tx(transaction, block_header, state, spec_id)?;
+        cumulative_gas_used += result.gas_used();
+        let logs = result.logs();
+        let receipt = Receipt::new(
+            transaction.tx_type(),
+            result.is_success(),
+            cumulative_gas_used,
+            bloom_from_logs(&logs),
+            logs,
+        );
+        receipts.push(receipt);
+    }
+
+    if let Some(withdrawals) = &block.body.withdrawals {
+        process_withdrawals(state, withdrawals)?;
+    }
+
+    Ok(receipts)
+}
+
 // Executes a single tx, doesn't perform state transitions
 pub fn execute_tx(
     tx: &Transaction,
@@ -48,7 +96,7 @@ pub fn execute_tx(
     spec_id: SpecId,
 ) -> Result<ExecutionResult, EvmError> {
     let block_env = block_env(header);
-    let tx_env = tx_env(tx);
+    let tx_env = tx_env(tx)?;
     run_evm(tx_env, block_env, state, spec_id)
 }
 
@@ -75,6 +123,71 @@ fn run_evm(
     Ok(tx_result.into())
 }
 
+/// Runs a transaction that may not contain all required fields on top of the given state and
+/// returns its result without committing any state changes
+/// Used to perform calls and gas estimations
+pub fn simulate_tx_from_generic(
+    tx: &GenericTransaction,
+    header: &BlockHeader,
+    state: &mut EvmState,
+    spec_id: SpecId,
+) -> Result<ExecutionResult, EvmError> {
+    run_without_commit(tx_env_from_generic(tx), block_env(header), state, spec_id)
+}
+
+/// Applies the given overrides to the cached state, so that they are taken into account by
+/// the transactions that are later simulated on it
+/// The overrides are never persisted, as they are not part of the state transitions
+pub fn apply_state_overrides(
+    state: &mut EvmState,
+    overrides: &StateOverrides,
+) -> Result<(), EvmError> {
+    for (address, account_override) in overrides {
+        if account_override.state.is_some() && account_override.state_diff.is_some() {
+            return Err(EvmError::Custom(format!(
+                "Both state and stateDiff overrides provided for account {address:#x}"
+            )));
+        }
+        let address = RevmAddress(address.0.into());
+        let cache_account = state.0.load_cache_account(address)?;
+        let (mut info, mut storage) = cache_account
+            .account
+            .clone()
+            .map(|account| (account.info, account.storage))
+            .unwrap_or_default();
+        if let Some(balance) = account_override.balance {
+            info.balance = RevmU256::from_limbs(balance.0);
+        }
+        if let Some(nonce) = account_override.nonce {
+            info.nonce = nonce;
+        }
+        if let Some(code) = &account_override.code {
+            let bytecode = Bytecode::new_raw(code.clone().into());
+            info.code_hash = bytecode.hash_slow();
+            info.code = Some(bytecode.clone());
+            state.0.cache.contracts.insert(info.code_hash, bytecode);
+        }
+        let to_revm_slot = |(key, value): (&H256, &H256)| {
+            (
+                RevmU256::from_be_bytes(key.0),
+                RevmU256::from_be_bytes(value.0),
+            )
+        };
+        let cache_account = if let Some(full_storage) = &account_override.state {
+            // The storage is fully known, so slots not present in the override are read as empty
+            storage = full_storage.iter().map(to_revm_slot).collect();
+            CacheAccount::new_newly_created(info, storage)
+        } else {
+            if let Some(storage_diff) = &account_override.state_diff {
+                storage.extend(storage_diff.iter().map(to_revm_slot));
+            }
+            CacheAccount::new_loaded(info, storage)
+        };
+        state.0.cache.accounts.insert(address, cache_account);
+    }
+    Ok(())
+}
+
 /// Runs the transaction and returns the access list and estimated gas use (when running the tx with said access list)
 pub fn create_access_list(
     tx: &GenericTransaction,
@@ -98,7 +211,7 @@ pub fn create_access_list(
                     .collect(),
             )
         }));
-        estimate_gas(tx_env, block_env, state, spec_id)?
+        run_without_commit(tx_env, block_env, state, spec_id)?
     } else {
         execution_result
     };
@@ -145,8 +258,9 @@ fn create_access_list_inner(
     Ok((tx_result.result.into(), access_list))
 }
 
-/// Runs the transaction and returns the estimated gas
-fn estimate_gas(
+/// Runs the transaction without committing its changes to the state and returns its result
+/// The base fee and block gas limit checks are disabled
+fn run_without_commit(
     tx_env: TxEnv,
     block_env: BlockEnv,
     state: &mut EvmState,
@@ -168,76 +282,172 @@ fn estimate_gas(
     Ok(tx_result.result.into())
 }
 
-// Merges transitions stored when executing transactions and applies the resulting changes to the DB
-pub fn apply_state_transitions(state: &mut EvmState) -> Result<(), StoreError> {
+/// Merges transitions stored when executing transactions and returns the resulting account updates
+/// Doesn't update the DB
+pub fn get_state_transitions(state: &mut EvmState) -> Vec<AccountUpdate> {
     state.0.merge_transitions(BundleRetention::PlainState);
     let bundle = state.0.take_bundle();
-    // Update accounts
+    let mut account_updates: Vec<AccountUpdate> = vec![];
     for (address, account) in bundle.state() {
         if account.status.is_not_modified() {
             continue;
         }
         let address = Address::from_slice(address.0.as_slice());
-        // Remove account from DB if destroyed
-        if account.status.was_destroyed() {
-            state.database().remove_account(address)?;
-        }
-        // Apply account changes to DB
+        // Remove account from DB if destroyed (the account may be recreated later on by the same update)
+        let was_destroyed = account.status.was_destroyed();
+        let mut account_update = AccountUpdate {
+            address,
+            removed: was_destroyed,
+            ..Default::default()
+        };
         // If the account was changed then both original and current info will be present in the bundle account
-        if account.is_info_changed() {
-            // Update account info in DB
+        if account.is_info_changed() || was_destroyed {
             if let Some(new_acc_info) = account.account_info() {
                 let code_hash = H256::from_slice(new_acc_info.code_hash.as_slice());
-                let account_info = AccountInfo {
+                account_update.info = Some(AccountInfo {
                     code_hash,
                     balance: U256::from_little_endian(new_acc_info.balance.as_le_slice()),
                     nonce: new_acc_info.nonce,
-                };
-                state.database().add_account_info(address, account_info)?;
-
-                if account.is_contract_changed() {
-                    // Update code in db
-                    if let Some(code) = new_acc_info.code {
-                        state
-                            .database()
-                            .add_account_code(code_hash, code.original_bytes().clone().0)?;
-                    }
+                });
+                if account.is_contract_changed() || was_destroyed {
+                    account_update.code = new_acc_info
+                        .code
+                        .map(|code| code.original_bytes().clone().0);
                 }
             }
         }
-        // Update account storage in DB
+        // Destroyed accounts lose all their previous storage, so every present value must be written
         for (key, slot) in account.storage.iter() {
-            if slot.is_changed() {
-                state.database().add_storage_at(
-                    address,
+            if slot.is_changed() || was_destroyed {
+                account_update.added_storage.insert(
                     H256::from_uint(&U256::from_little_endian(key.as_le_slice())),
                     H256::from_uint(&U256::from_little_endian(
                         slot.present_value().as_le_slice(),
                     )),
-                )?;
+                );
             }
         }
+        account_updates.push(account_update);
     }
-    Ok(())
+    account_updates
+}
+
+// Merges transitions stored when executing the given block's transactions and applies the
+// resulting changes to the DB in a single write
+pub fn apply_state_transitions(
+    state: &mut EvmState,
+    block_number: BlockNumber,
+) -> Result<(), StoreError> {
+    let account_updates = get_state_transitions(state);
+    state
+        .database()
+        .apply_account_updates(block_number, &account_updates)
 }
 
 /// Processes a block's withdrawals, updating the account balances in the state
-pub fn process_withdrawals(state: &Store, withdrawals: &[Withdrawal]) -> Result<(), StoreError> {
-    for withdrawal in withdrawals {
-        if !withdrawal.amount.is_zero() {
-            state.increment_balance(withdrawal.address, withdrawal.amount * GWEI_TO_WEI)?
+pub fn process_withdrawals(
+    state: &mut EvmState,
+    withdrawals: &[Withdrawal],
+) -> Result<(), StoreError> {
+    // For every withdrawal we increment the target account's balance
+    let balances = withdrawals
+        .iter()
+        .filter(|withdrawal| !withdrawal.amount.is_zero())
+        .map(|withdrawal| {
+            (
+                RevmAddress::from_slice(withdrawal.address.as_bytes()),
+                (withdrawal.amount * GWEI_TO_WEI).as_u128(),
+            )
+        });
+    state.0.increment_balances(balances)
+}
+
+/// Calls the EIP-4788 beacon roots contract, storing the parent beacon block root in its state
+/// See [EIP-4788](https://eips.ethereum.org/EIPS/eip-4788)
+pub fn beacon_root_contract_call(
+    state: &mut EvmState,
+    header: &BlockHeader,
+    spec_id: SpecId,
+) -> Result<ExecutionResult, EvmError> {
+    let beacon_root = match header.parent_beacon_block_root {
+        None => {
+            return Err(EvmError::Header(
+                "parent_beacon_block_root field is missing".to_string(),
+            ))
         }
+        Some(beacon_root) => beacon_root,
+    };
+    let tx_env = TxEnv {
+        caller: *SYSTEM_ADDRESS,
+        transact_to: RevmTxKind::Call(*BEACON_ROOTS_ADDRESS),
+        nonce: None,
+        gas_limit: SYSTEM_CALL_GAS_LIMIT,
+        value: RevmU256::ZERO,
+        data: revm::primitives::Bytes::copy_from_slice(beacon_root.as_bytes()),
+        gas_price: RevmU256::ZERO,
+        chain_id: None,
+        gas_priority_fee: None,
+        access_list: Vec::new(),
+        blob_hashes: Vec::new(),
+        max_fee_per_blob_gas: None,
+    };
+    // The system call is not subject to the block's gas limit nor base fee
+    let mut block_env = block_env(header);
+    block_env.basefee = RevmU256::ZERO;
+    block_env.gas_limit = RevmU256::from(SYSTEM_CALL_GAS_LIMIT);
+    let coinbase = block_env.coinbase;
+
+    let tx_result = {
+        let mut evm = Evm::builder()
+            .with_db(&mut state.0)
+            .with_block_env(block_env)
+            .with_tx_env(tx_env)
+            .with_spec_id(spec_id)
+            .build();
+        evm.transact().map_err(EvmError::from)?
+    };
+    // The system address and the coinbase must not be affected by the call
+    let mut new_state = tx_result.state;
+    new_state.remove(&*SYSTEM_ADDRESS);
+    new_state.remove(&coinbase);
+    state.0.commit(new_state);
+
+    Ok(tx_result.result.into())
+}
+
+/// Returns the spec id for a block with the given number and timestamp according to the chain's fork schedule
+pub fn spec_id(chain_config: &ChainConfig, block_number: BlockNumber, timestamp: u64) -> SpecId {
+    match chain_config.get_fork(block_number, timestamp) {
+        Fork::Frontier => SpecId::FRONTIER,
+        Fork::Homestead => SpecId::HOMESTEAD,
+        Fork::DaoFork => SpecId::DAO_FORK,
+        Fork::Tangerine => SpecId::TANGERINE,
+        Fork::SpuriousDragon => SpecId::SPURIOUS_DRAGON,
+        Fork::Byzantium => SpecId::BYZANTIUM,
+        Fork::Constantinople => SpecId::CONSTANTINOPLE,
+        Fork::Petersburg => SpecId::PETERSBURG,
+        Fork::Istanbul => SpecId::ISTANBUL,
+        Fork::MuirGlacier => SpecId::MUIR_GLACIER,
+        Fork::Berlin => SpecId::BERLIN,
+        Fork::London => SpecId::LONDON,
+        Fork::ArrowGlacier => SpecId::ARROW_GLACIER,
+        Fork::GrayGlacier => SpecId::GRAY_GLACIER,
+        Fork::Paris => SpecId::MERGE,
+        Fork::Shanghai => SpecId::SHANGHAI,
+        Fork::Cancun => SpecId::CANCUN,
+        Fork::Prague => SpecId::PRAGUE,
     }
-    Ok(())
 }
 
-/// Builds EvmState from a Store
-pub fn evm_state(store: Store) -> EvmState {
+/// Builds EvmState from a Store, on top of the state at the end of the given block
+pub fn evm_state(store: Store, block_number: BlockNumber) -> EvmState {
     EvmState(
         revm::db::State::builder()
-            .with_database(StoreWrapper(store))
+            .with_database(StoreWrapper {
+                store,
+                block_number,
+            })
             .with_bundle_update()
-            .without_state_clear()
             .build(),
     )
 }
@@ -251,11 +461,17 @@ fn block_env(header: &BlockHeader) -> BlockEnv {
         basefee: RevmU256::from(header.base_fee_per_gas),
         difficulty: RevmU256::from_limbs(header.difficulty.0),
         prevrandao: Some(header.prev_randao.as_fixed_bytes().into()),
-        ..Default::default()
+        blob_excess_gas_and_price: Some(BlobExcessGasAndPrice::new(
+            header.excess_blob_gas.unwrap_or_default(),
+        )),
     }
 }
 
-fn tx_env(tx: &Transaction) -> TxEnv {
+/// Fails if the transaction's sender can't be recovered from its signature
+fn tx_env(tx: &Transaction) -> Result<TxEnv, EvmError> {
+    let sender = tx
+        .sender()
+        .map_err(|error| EvmError::Transaction(error.to_string()))?;
     let mut max_fee_per_blob_gas_bytes: [u8; 32] = [0; 32];
     let max_fee_per_blob_gas = match tx.max_fee_per_blob_gas() {
         Some(x) => {
@@ -264,8 +480,8 @@ fn tx_env(tx: &Transaction) -> TxEnv {
         }
         None => None,
     };
-    TxEnv {
-        caller: RevmAddress(tx.sender().0.into()),
+    Ok(TxEnv {
+        caller: RevmAddress(sender.0.into()),
         gas_limit: tx.gas_limit(),
         gas_price: RevmU256::from(tx.gas_price()),
         transact_to: match tx.to() {
@@ -295,7 +511,7 @@ fn tx_env(tx: &Transaction) -> TxEnv {
             .map(|hash| B256::from(hash.0))
             .collect(),
         max_fee_per_blob_gas,
-    }
+    })
 }
 
 // Used to estimate gas and create access lists
@@ -310,7 +526,7 @@ fn tx_env_from_generic(tx: &GenericTransaction) -> TxEnv {
         },
         value: RevmU256::from_limbs(tx.value.0),
         data: tx.input.clone().into(),
-        nonce: Some(tx.nonce),
+        nonce: tx.nonce,
         chain_id: tx.chain_id,
         access_list: tx
             .access_list
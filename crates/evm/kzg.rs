@@ -0,0 +1,38 @@
+use c_kzg::{Blob, Bytes48, KzgProof};
+use ethereum_rust_core::{Bytes, H256};
+use revm::{precompile::kzg_point_evaluation::kzg_to_versioned_hash, primitives::EnvKzgSettings};
+
+/// Computes the versioned hash of a KZG commitment, as defined by EIP-4844
+pub fn kzg_commitment_to_versioned_hash(commitment: &[u8]) -> H256 {
+    H256(kzg_to_versioned_hash(commitment))
+}
+
+/// Verifies that each blob corresponds to its KZG commitment using the given KZG proofs
+/// Malformed blobs, commitments or proofs are considered invalid
+pub fn verify_blob_kzg_proof_batch(
+    blobs: &[Bytes],
+    commitments: &[Bytes],
+    proofs: &[Bytes],
+) -> bool {
+    let verify = || -> Result<bool, c_kzg::Error> {
+        let blobs = blobs
+            .iter()
+            .map(|blob| Blob::from_bytes(blob))
+            .collect::<Result<Vec<_>, _>>()?;
+        let commitments = commitments
+            .iter()
+            .map(|commitment| Bytes48::from_bytes(commitment))
+            .collect::<Result<Vec<_>, _>>()?;
+        let proofs = proofs
+            .iter()
+            .map(|proof| Bytes48::from_bytes(proof))
+            .collect::<Result<Vec<_>, _>>()?;
+        KzgProof::verify_blob_kzg_proof_batch(
+            &blobs,
+            &commitments,
+            &proofs,
+            EnvKzgSettings::Default.get(),
+        )
+    };
+    verify().unwrap_or(false)
+}
@@ -0,0 +1,74 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::{
+    extract::{Request, State},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use ethereum_rust_core::Bytes;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use thiserror::Error;
+use tracing::warn;
+
+/// Maximum difference in seconds between the time a token was issued and the current time
+const MAX_ISSUED_AT_DRIFT: u64 = 60;
+
+#[derive(Debug, Error)]
+pub enum AuthenticationError {
+    #[error("Missing bearer token")]
+    MissingAuthentication,
+    #[error("Invalid token")]
+    InvalidToken,
+    #[error("Token issued more than {MAX_ISSUED_AT_DRIFT} seconds away from the current time")]
+    InvalidIssuedAtClaim,
+}
+
+/// Claims of the tokens sent by consensus clients, only the issuance time is checked
+#[derive(Debug, Deserialize)]
+struct Claims {
+    iat: u64,
+}
+
+/// Rejects with a 401 the requests that don't carry a token signed with the given secret and
+/// issued around the current time, as required by the Engine API
+pub async fn authenticate(State(secret): State<Bytes>, request: Request, next: Next) -> Response {
+    match validate_authorization(request.headers(), &secret) {
+        Ok(()) => next.run(request).await,
+        Err(error) => {
+            warn!("Rejected unauthenticated request: {error}");
+            (StatusCode::UNAUTHORIZED, error.to_string()).into_response()
+        }
+    }
+}
+
+fn validate_authorization(headers: &HeaderMap, secret: &[u8]) -> Result<(), AuthenticationError> {
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .ok_or(AuthenticationError::MissingAuthentication)?;
+    validate_jwt(token, secret)
+}
+
+/// Checks that the token is signed with the secret using HS256 and that it was issued around the
+/// current time
+pub(crate) fn validate_jwt(token: &str, secret: &[u8]) -> Result<(), AuthenticationError> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    // Tokens are not required to have an expiration time
+    validation.required_spec_claims.clear();
+    validation.validate_exp = false;
+    let claims =
+        jsonwebtoken::decode::<Claims>(token, &DecodingKey::from_secret(secret), &validation)
+            .map_err(|_| AuthenticationError::InvalidToken)?
+            .claims;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    if now.abs_diff(claims.iat) > MAX_ISSUED_AT_DRIFT {
+        return Err(AuthenticationError::InvalidIssuedAtClaim);
+    }
+    Ok(())
+}
@@ -1,11 +1,27 @@
+use ethereum_rust_blockchain::{
+    add_block,
+    error::{ChainError, InvalidForkChoice},
+    fork_choice::apply_fork_choice,
+    mempool::Mempool,
+    notifier::ChainNotifier,
+    payload::{build_payload, create_payload, BuildPayloadArgs},
+};
 use ethereum_rust_core::{
-    types::{ExecutionPayloadV3, PayloadStatus, PayloadValidationStatus},
+    types::{
+        BlobAndProofV1, Block, BuiltPayload, ExecutionPayloadResponseV3, ExecutionPayloadV3,
+        ForkChoiceResponse, ForkChoiceState, PayloadAttributesV3, PayloadStatus,
+    },
     H256,
 };
+use ethereum_rust_evm::EvmError;
+use ethereum_rust_storage::Store;
 use serde_json::{json, Value};
 use tracing::info;
 
-use crate::RpcErr;
+use crate::{
+    utils::{expect_params, parse_hex_param, parse_param},
+    RpcErr,
+};
 
 pub type ExchangeCapabilitiesRequest = Vec<String>;
 
@@ -15,55 +31,238 @@ pub struct NewPayloadV3Request {
     pub parent_beacon_block_root: H256,
 }
 
+pub struct ForkChoiceUpdatedV3 {
+    pub fork_choice_state: ForkChoiceState,
+    pub payload_attributes: Option<PayloadAttributesV3>,
+}
+
+impl ForkChoiceUpdatedV3 {
+    pub fn parse(params: &Option<Vec<Value>>) -> Result<ForkChoiceUpdatedV3, RpcErr> {
+        let params = params.as_deref().unwrap_or_default();
+        if params.is_empty() || params.len() > 2 {
+            return Err(RpcErr::BadParams(format!(
+                "expected 1 or 2 params, got {}",
+                params.len()
+            )));
+        }
+        Ok(ForkChoiceUpdatedV3 {
+            fork_choice_state: parse_param(params, 0)?,
+            payload_attributes: match params.get(1) {
+                Some(_) => parse_param(params, 1)?,
+                None => None,
+            },
+        })
+    }
+}
+
+pub struct GetPayloadV3Request {
+    pub payload_id: u64,
+}
+
+impl GetPayloadV3Request {
+    pub fn parse(params: &Option<Vec<Value>>) -> Result<GetPayloadV3Request, RpcErr> {
+        let params = expect_params(params, 1)?;
+        Ok(GetPayloadV3Request {
+            payload_id: parse_hex_param(params, 0)?,
+        })
+    }
+}
+
+pub struct GetBlobsV1Request {
+    pub blob_versioned_hashes: Vec<H256>,
+}
+
+impl GetBlobsV1Request {
+    pub fn parse(params: &Option<Vec<Value>>) -> Result<GetBlobsV1Request, RpcErr> {
+        let params = expect_params(params, 1)?;
+        Ok(GetBlobsV1Request {
+            blob_versioned_hashes: parse_param(params, 0)?,
+        })
+    }
+}
+
+/// Maximum amount of versioned hashes that can be requested via `engine_getBlobsV1`
+const GET_BLOBS_V1_MAX_REQUEST_SIZE: usize = 128;
+
 pub fn exchange_capabilities(capabilities: &ExchangeCapabilitiesRequest) -> Result<Value, RpcErr> {
     Ok(json!(capabilities))
 }
 
-pub fn forkchoice_updated_v3() -> Result<Value, RpcErr> {
-    Ok(json!({
-        "payloadId": null,
-        "payloadStatus": {
-            "latestValidHash": null,
-            "status": "SYNCING",
-            "validationError": null
+pub fn forkchoice_updated_v3(
+    request: ForkChoiceUpdatedV3,
+    storage: Store,
+    mempool: Mempool,
+    notifier: ChainNotifier,
+) -> Result<Value, RpcErr> {
+    let state = request.fork_choice_state;
+    info!(
+        "Received fork choice update with head: {:#x}, safe: {:#x}, finalized: {:#x}",
+        state.head_block_hash, state.safe_block_hash, state.finalized_block_hash
+    );
+    let head = match apply_fork_choice(
+        &storage,
+        &mempool,
+        &notifier,
+        state.head_block_hash,
+        state.safe_block_hash,
+        state.finalized_block_hash,
+    ) {
+        Ok(head) => head,
+        Err(InvalidForkChoice::Syncing) => {
+            return fork_choice_response(PayloadStatus::syncing(), None)
+        }
+        Err(InvalidForkChoice::NewHeadAlreadyCanonical) => {
+            return fork_choice_response(
+                PayloadStatus::valid_with_hash(state.head_block_hash),
+                None,
+            )
+        }
+        Err(
+            InvalidForkChoice::StoreError(error)
+            | InvalidForkChoice::InvalidChain(
+                ChainError::StoreError(error) | ChainError::EvmError(EvmError::DB(error)),
+            ),
+        ) => return Err(error.into()),
+        Err(InvalidForkChoice::InvalidChain(error)) => {
+            return fork_choice_response(PayloadStatus::invalid_with(None, error.to_string()), None)
+        }
+        Err(_) => return Err(RpcErr::InvalidForkChoiceState),
+    };
+
+    // The fork choice state is applied even if the payload attributes turn out to be invalid
+    let mut payload_id = None;
+    if let Some(attributes) = &request.payload_attributes {
+        if attributes.timestamp <= head.timestamp {
+            return Err(RpcErr::InvalidPayloadAttributes);
+        }
+        let chain_config = storage.get_chain_config()?;
+        if !chain_config.is_cancun_activated(attributes.timestamp) {
+            return Err(RpcErr::UnsuportedFork);
         }
-    }))
+        let args = BuildPayloadArgs {
+            parent: state.head_block_hash,
+            timestamp: attributes.timestamp,
+            fee_recipient: attributes.suggested_fee_recipient,
+            random: attributes.prev_randao,
+            withdrawals: attributes.withdrawals.clone(),
+            beacon_root: Some(attributes.parent_beacon_block_root),
+            version: 3,
+        };
+        let id = args.id();
+        let payload = build_payload_for(&args, &storage, &mempool)
+            .map_err(|error| RpcErr::Internal(error.to_string()))?;
+        storage.add_payload(id, payload)?;
+        info!("Built payload with id: {:#018x}", id);
+        payload_id = Some(id);
+    }
+    fork_choice_response(
+        PayloadStatus::valid_with_hash(state.head_block_hash),
+        payload_id,
+    )
+}
+
+/// Builds a payload on top of the new head, the chain is locked while doing so, so that the
+/// state it is built on can't change in the meantime
+fn build_payload_for(
+    args: &BuildPayloadArgs,
+    storage: &Store,
+    mempool: &Mempool,
+) -> Result<BuiltPayload, ChainError> {
+    let _chain_lock = storage.lock_chain();
+    let mut block = create_payload(args, storage)?;
+    let (block_value, blobs_bundle) = build_payload(&mut block, storage, mempool)?;
+    Ok(BuiltPayload {
+        block,
+        block_value,
+        blobs_bundle,
+    })
+}
+
+/// Returns the payload built for the given id when the fork choice update that requested it was
+/// received, the same payload is returned every time it is requested
+pub fn get_payload_v3(request: GetPayloadV3Request, storage: Store) -> Result<Value, RpcErr> {
+    info!("Requested payload with id: {:#018x}", request.payload_id);
+    let payload = storage
+        .get_payload(request.payload_id)?
+        .ok_or(RpcErr::UnknownPayload)?;
+    let response = ExecutionPayloadResponseV3 {
+        execution_payload: ExecutionPayloadV3::from_block(payload.block),
+        block_value: payload.block_value,
+        blobs_bundle: payload.blobs_bundle,
+        should_override_builder: false,
+    };
+    serde_json::to_value(response).map_err(|error| RpcErr::Internal(error.to_string()))
+}
+
+/// Returns the blobs and proofs held by the mempool for the requested versioned hashes, in the
+/// same order, with null entries for the ones that are not available
+pub fn get_blobs_v1(request: GetBlobsV1Request, mempool: Mempool) -> Result<Value, RpcErr> {
+    if request.blob_versioned_hashes.len() > GET_BLOBS_V1_MAX_REQUEST_SIZE {
+        return Err(RpcErr::TooLargeRequest);
+    }
+    let blobs_and_proofs: Vec<Option<BlobAndProofV1>> = request
+        .blob_versioned_hashes
+        .iter()
+        .map(|versioned_hash| {
+            mempool
+                .get_blob_and_proof(*versioned_hash)
+                .map(|(blob, proof)| BlobAndProofV1 { blob, proof })
+        })
+        .collect();
+    serde_json::to_value(blobs_and_proofs).map_err(|error| RpcErr::Internal(error.to_string()))
 }
 
-pub fn new_payload_v3(request: NewPayloadV3Request) -> Result<PayloadStatus, RpcErr> {
+fn fork_choice_response(
+    payload_status: PayloadStatus,
+    payload_id: Option<u64>,
+) -> Result<Value, RpcErr> {
+    serde_json::to_value(ForkChoiceResponse::new(payload_status, payload_id))
+        .map_err(|error| RpcErr::Internal(error.to_string()))
+}
+
+pub fn new_payload_v3(
+    request: NewPayloadV3Request,
+    storage: Store,
+    mempool: Mempool,
+    notifier: ChainNotifier,
+) -> Result<Value, RpcErr> {
+    let status = execute_payload(request, &storage, &mempool, &notifier)?;
+    serde_json::to_value(status).map_err(|error| RpcErr::Internal(error.to_string()))
+}
+
+/// Validates the payload received via `engine_newPayloadV3`, executes it on top of its parent's state and stores it if valid
+fn execute_payload(
+    request: NewPayloadV3Request,
+    storage: &Store,
+    mempool: &Mempool,
+    notifier: &ChainNotifier,
+) -> Result<PayloadStatus, RpcErr> {
     let block_hash = request.payload.block_hash;
 
-    info!("Received new payload with block hash: {}", block_hash);
+    info!("Received new payload with block hash: {:#x}", block_hash);
 
     let (block_header, block_body) =
         match request.payload.into_block(request.parent_beacon_block_root) {
             Ok(block) => block,
-            Err(error) => {
-                return Ok(PayloadStatus {
-                    status: PayloadValidationStatus::Invalid,
-                    latest_valid_hash: Some(H256::zero()),
-                    validation_error: Some(error.to_string()),
-                })
-            }
+            Err(error) => return Ok(PayloadStatus::invalid_with(None, error.to_string())),
         };
 
     // Payload Validation
 
-    // Check timestamp does not fall within the time frame of the Cancun fork
-    let cancun_time = 0; // Placeholder -> we should fetch this from genesis?
-    if block_header.timestamp <= cancun_time {
+    // Check timestamp falls within the time frame of the Cancun fork
+    let chain_config = storage.get_chain_config()?;
+    if !chain_config.is_cancun_activated(block_header.timestamp) {
         return Err(RpcErr::UnsuportedFork);
     }
     // Check that block_hash is valid
     let actual_block_hash = block_header.compute_block_hash();
     if block_hash != actual_block_hash {
-        return Ok(PayloadStatus {
-            status: PayloadValidationStatus::Invalid,
-            latest_valid_hash: None,
-            validation_error: Some("Invalid block hash".to_string()),
-        });
+        return Ok(PayloadStatus::invalid_with(
+            None,
+            "Invalid block hash".to_string(),
+        ));
     }
-    info!("Block hash {} is valid", block_hash);
+    info!("Block hash {:#x} is valid", block_hash);
     // Concatenate blob versioned hashes lists (tx.blob_versioned_hashes) of each blob transaction included in the payload, respecting the order of inclusion
     // and check that the resulting array matches expected_blob_versioned_hashes
     let blob_versioned_hashes: Vec<H256> = block_body
@@ -72,16 +271,51 @@ pub fn new_payload_v3(request: NewPayloadV3Request) -> Result<PayloadStatus, Rpc
         .flat_map(|tx| tx.blob_versioned_hashes())
         .collect();
     if request.expected_blob_versioned_hashes != blob_versioned_hashes {
-        return Ok(PayloadStatus {
-            status: PayloadValidationStatus::Invalid,
-            latest_valid_hash: None,
-            validation_error: Some("Invalid blob_versioned_hashes".to_string()),
+        return Ok(PayloadStatus::invalid_with(
+            None,
+            "Invalid blob_versioned_hashes".to_string(),
+        ));
+    }
+
+    // Check if the block was already processed
+    if let Some(header) = storage.get_block_header_by_hash(block_hash)? {
+        // Side chain blocks are stored without being executed until they become canonical
+        let executed = storage.get_canonical_block_hash(header.number)? == Some(block_hash)
+            && storage
+                .get_latest_block_number()?
+                .is_some_and(|latest| header.number <= latest);
+        return Ok(if executed {
+            PayloadStatus::valid_with_hash(block_hash)
+        } else {
+            PayloadStatus::accepted()
         });
     }
 
-    Ok(PayloadStatus {
-        status: PayloadValidationStatus::Valid,
-        latest_valid_hash: Some(block_hash),
-        validation_error: None,
-    })
+    // Execute the block and store it if valid
+    let parent_hash = block_header.parent_hash;
+    let block = Block {
+        header: block_header,
+        body: block_body,
+    };
+    match add_block(&block, storage, notifier) {
+        Ok(()) => {
+            mempool.remove_included_transactions(&block.body.transactions);
+            Ok(PayloadStatus::valid_with_hash(block_hash))
+        }
+        // We can't execute the block until we have its ancestors
+        Err(ChainError::ParentNotFound) => Ok(PayloadStatus::syncing()),
+        // The block is valid as far as we can tell, but we don't have its parent's state
+        Err(ChainError::ParentStateNotFound) => Ok(PayloadStatus::accepted()),
+        // Local storage faults don't make the block invalid
+        Err(ChainError::StoreError(error) | ChainError::EvmError(EvmError::DB(error))) => {
+            Err(error.into())
+        }
+        Err(error @ (ChainError::InvalidBlock(_) | ChainError::EvmError(_))) => {
+            // The parent was found, so it is the latest valid block
+            Ok(PayloadStatus::invalid_with(
+                Some(parent_hash),
+                error.to_string(),
+            ))
+        }
+    }
 }
@@ -2,7 +2,7 @@ use ethereum_rust_storage::Store;
 use serde_json::Value;
 use tracing::info;
 
-use crate::utils::RpcErr;
+use crate::utils::{expect_params, parse_param, RpcErr};
 use ethereum_rust_core::{Address, H256};
 
 use super::block::BlockIdentifier;
@@ -23,42 +23,50 @@ pub struct GetStorageAtRequest {
     pub block: BlockIdentifier,
 }
 
+pub struct GetProofRequest {
+    pub address: Address,
+    pub storage_keys: Vec<H256>,
+    pub block: BlockIdentifier,
+}
+
 impl GetBalanceRequest {
-    pub fn parse(params: &Option<Vec<Value>>) -> Option<GetBalanceRequest> {
-        let params = params.as_ref()?;
-        if params.len() != 2 {
-            return None;
-        };
-        Some(GetBalanceRequest {
-            address: serde_json::from_value(params[0].clone()).ok()?,
-            block: serde_json::from_value(params[1].clone()).ok()?,
+    pub fn parse(params: &Option<Vec<Value>>) -> Result<GetBalanceRequest, RpcErr> {
+        let params = expect_params(params, 2)?;
+        Ok(GetBalanceRequest {
+            address: parse_param(params, 0)?,
+            block: parse_param(params, 1)?,
         })
     }
 }
 
 impl GetCodeRequest {
-    pub fn parse(params: &Option<Vec<Value>>) -> Option<GetCodeRequest> {
-        let params = params.as_ref()?;
-        if params.len() != 2 {
-            return None;
-        };
-        Some(GetCodeRequest {
-            address: serde_json::from_value(params[0].clone()).ok()?,
-            block: serde_json::from_value(params[1].clone()).ok()?,
+    pub fn parse(params: &Option<Vec<Value>>) -> Result<GetCodeRequest, RpcErr> {
+        let params = expect_params(params, 2)?;
+        Ok(GetCodeRequest {
+            address: parse_param(params, 0)?,
+            block: parse_param(params, 1)?,
         })
     }
 }
 
 impl GetStorageAtRequest {
-    pub fn parse(params: &Option<Vec<Value>>) -> Option<GetStorageAtRequest> {
-        let params = params.as_ref()?;
-        if params.len() != 3 {
-            return None;
-        };
-        Some(GetStorageAtRequest {
-            address: serde_json::from_value(params[0].clone()).ok()?,
-            storage_slot: serde_json::from_value(params[1].clone()).ok()?,
-            block: serde_json::from_value(params[2].clone()).ok()?,
+    pub fn parse(params: &Option<Vec<Value>>) -> Result<GetStorageAtRequest, RpcErr> {
+        let params = expect_params(params, 3)?;
+        Ok(GetStorageAtRequest {
+            address: parse_param(params, 0)?,
+            storage_slot: parse_param(params, 1)?,
+            block: parse_param(params, 2)?,
+        })
+    }
+}
+
+impl GetProofRequest {
+    pub fn parse(params: &Option<Vec<Value>>) -> Result<GetProofRequest, RpcErr> {
+        let params = expect_params(params, 3)?;
+        Ok(GetProofRequest {
+            address: parse_param(params, 0)?,
+            storage_keys: parse_param(params, 1)?,
+            block: parse_param(params, 2)?,
         })
     }
 }
@@ -68,15 +76,14 @@ pub fn get_balance(request: &GetBalanceRequest, storage: Store) -> Result<Value,
         "Requested balance of account {} at block {}",
         request.address, request.block
     );
-    let account = match storage.get_account_info(request.address) {
-        Ok(Some(account)) => account,
+    let header = request.block.resolve_state_block(&storage)?;
+    let Some(account) = storage.get_account_info_at_block(header.number, request.address)? else {
         // Account not found
-        Ok(_) => return Ok(Value::Null),
-        // DB error
-        _ => return Err(RpcErr::Internal),
+        return Ok(Value::Null);
     };
 
-    serde_json::to_value(format!("{:#x}", account.balance)).map_err(|_| RpcErr::Internal)
+    serde_json::to_value(format!("{:#x}", account.balance))
+        .map_err(|error| RpcErr::Internal(error.to_string()))
 }
 
 pub fn get_code(request: &GetCodeRequest, storage: Store) -> Result<Value, RpcErr> {
@@ -84,15 +91,16 @@ pub fn get_code(request: &GetCodeRequest, storage: Store) -> Result<Value, RpcEr
         "Requested code of account {} at block {}",
         request.address, request.block
     );
-    let code = match storage.get_code_by_account_address(request.address) {
-        Ok(Some(code)) => code,
+    let header = request.block.resolve_state_block(&storage)?;
+    let Some(code) =
+        storage.get_code_by_account_address_at_block(header.number, request.address)?
+    else {
         // Account not found
-        Ok(_) => return Ok(Value::Null),
-        // DB error
-        _ => return Err(RpcErr::Internal),
+        return Ok(Value::Null);
     };
 
-    serde_json::to_value(format!("0x{:x}", code)).map_err(|_| RpcErr::Internal)
+    serde_json::to_value(format!("0x{:x}", code))
+        .map_err(|error| RpcErr::Internal(error.to_string()))
 }
 
 pub fn get_storage_at(request: &GetStorageAtRequest, storage: Store) -> Result<Value, RpcErr> {
@@ -100,13 +108,26 @@ pub fn get_storage_at(request: &GetStorageAtRequest, storage: Store) -> Result<V
         "Requested storage sot {} of account {} at block {}",
         request.storage_slot, request.address, request.block
     );
-    let storage_value = match storage.get_storage_at(request.address, request.storage_slot) {
-        Ok(Some(storage_value)) => storage_value,
+    let header = request.block.resolve_state_block(&storage)?;
+    let Some(storage_value) =
+        storage.get_storage_at_block(header.number, request.address, request.storage_slot)?
+    else {
         // Account not found
-        Ok(_) => return Ok(Value::Null),
-        // DB error
-        _ => return Err(RpcErr::Internal),
+        return Ok(Value::Null);
     };
 
-    serde_json::to_value(format!("{:#x}", storage_value)).map_err(|_| RpcErr::Internal)
+    serde_json::to_value(format!("{:#x}", storage_value))
+        .map_err(|error| RpcErr::Internal(error.to_string()))
+}
+
+pub fn get_proof(request: &GetProofRequest, storage: Store) -> Result<Value, RpcErr> {
+    info!(
+        "Requested proof of account {} at block {}",
+        request.address, request.block
+    );
+    let header = request.block.resolve_state_block(&storage)?;
+    let proof =
+        storage.get_account_proof(header.state_root, request.address, &request.storage_keys)?;
+
+    serde_json::to_value(proof).map_err(|error| RpcErr::Internal(error.to_string()))
 }
@@ -1,16 +1,16 @@
 use std::fmt::Display;
 
-use ethereum_rust_evm::{evm_state, ExecutionResult, SpecId};
-use ethereum_rust_storage::Store;
+use ethereum_rust_evm::{evm_state, spec_id, ExecutionResult};
+use ethereum_rust_storage::{error::StoreError, Store};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tracing::info;
 
-use crate::utils::RpcErr;
+use crate::utils::{expect_params, parse_param, revert_message, RpcErr};
 use ethereum_rust_core::{
     types::{
-        AccessListEntry, BlockHash, BlockNumber, BlockSerializable, GenericTransaction,
-        ReceiptWithTxAndBlockInfo,
+        AccessListEntry, BlockHash, BlockHeader, BlockNumber, BlockSerializable,
+        GenericTransaction, ReceiptWithTxAndBlockInfo,
     },
     H256,
 };
@@ -86,39 +86,32 @@ pub enum BlockTag {
 }
 
 impl GetBlockByNumberRequest {
-    pub fn parse(params: &Option<Vec<Value>>) -> Option<GetBlockByNumberRequest> {
-        let params = params.as_ref()?;
-        if params.len() != 2 {
-            return None;
-        };
-        Some(GetBlockByNumberRequest {
-            block: serde_json::from_value(params[0].clone()).ok()?,
-            hydrated: serde_json::from_value(params[1].clone()).ok()?,
+    pub fn parse(params: &Option<Vec<Value>>) -> Result<GetBlockByNumberRequest, RpcErr> {
+        let params = expect_params(params, 2)?;
+        Ok(GetBlockByNumberRequest {
+            block: parse_param(params, 0)?,
+            hydrated: parse_param(params, 1)?,
         })
     }
 }
 
 impl GetBlockByHashRequest {
-    pub fn parse(params: &Option<Vec<Value>>) -> Option<GetBlockByHashRequest> {
-        let params = params.as_ref()?;
-        if params.len() != 2 {
-            return None;
-        };
-        Some(GetBlockByHashRequest {
-            block: serde_json::from_value(params[0].clone()).ok()?,
-            hydrated: serde_json::from_value(params[1].clone()).ok()?,
+    pub fn parse(params: &Option<Vec<Value>>) -> Result<GetBlockByHashRequest, RpcErr> {
+        let params = expect_params(params, 2)?;
+        Ok(GetBlockByHashRequest {
+            block: parse_param(params, 0)?,
+            hydrated: parse_param(params, 1)?,
         })
     }
 }
 
 impl GetBlockTransactionCountByNumberRequest {
-    pub fn parse(params: &Option<Vec<Value>>) -> Option<GetBlockTransactionCountByNumberRequest> {
-        let params = params.as_ref()?;
-        if params.len() != 1 {
-            return None;
-        };
-        Some(GetBlockTransactionCountByNumberRequest {
-            block: serde_json::from_value(params[0].clone()).ok()?,
+    pub fn parse(
+        params: &Option<Vec<Value>>,
+    ) -> Result<GetBlockTransactionCountByNumberRequest, RpcErr> {
+        let params = expect_params(params, 1)?;
+        Ok(GetBlockTransactionCountByNumberRequest {
+            block: parse_param(params, 0)?,
         })
     }
 }
@@ -126,80 +119,70 @@ impl GetBlockTransactionCountByNumberRequest {
 impl GetTransactionByBlockNumberAndIndexRequest {
     pub fn parse(
         params: &Option<Vec<Value>>,
-    ) -> Option<GetTransactionByBlockNumberAndIndexRequest> {
-        let params = params.as_ref()?;
-        if params.len() != 2 {
-            return None;
-        };
-        Some(GetTransactionByBlockNumberAndIndexRequest {
-            block: serde_json::from_value(params[0].clone()).ok()?,
-            transaction_index: serde_json::from_value(params[1].clone()).ok()?,
+    ) -> Result<GetTransactionByBlockNumberAndIndexRequest, RpcErr> {
+        let params = expect_params(params, 2)?;
+        Ok(GetTransactionByBlockNumberAndIndexRequest {
+            block: parse_param(params, 0)?,
+            transaction_index: parse_param(params, 1)?,
         })
     }
 }
 
 impl GetTransactionByBlockHashAndIndexRequest {
-    pub fn parse(params: &Option<Vec<Value>>) -> Option<GetTransactionByBlockHashAndIndexRequest> {
-        let params = params.as_ref()?;
-        if params.len() != 2 {
-            return None;
-        };
-        Some(GetTransactionByBlockHashAndIndexRequest {
-            block: serde_json::from_value(params[0].clone()).ok()?,
-            transaction_index: serde_json::from_value(params[1].clone()).ok()?,
+    pub fn parse(
+        params: &Option<Vec<Value>>,
+    ) -> Result<GetTransactionByBlockHashAndIndexRequest, RpcErr> {
+        let params = expect_params(params, 2)?;
+        Ok(GetTransactionByBlockHashAndIndexRequest {
+            block: parse_param(params, 0)?,
+            transaction_index: parse_param(params, 1)?,
         })
     }
 }
 
 impl GetBlockReceiptsRequest {
-    pub fn parse(params: &Option<Vec<Value>>) -> Option<GetBlockReceiptsRequest> {
-        let params = params.as_ref()?;
-        if params.len() != 1 {
-            return None;
-        };
-        Some(GetBlockReceiptsRequest {
-            block: serde_json::from_value(params[0].clone()).ok()?,
+    pub fn parse(params: &Option<Vec<Value>>) -> Result<GetBlockReceiptsRequest, RpcErr> {
+        let params = expect_params(params, 1)?;
+        Ok(GetBlockReceiptsRequest {
+            block: parse_param(params, 0)?,
         })
     }
 }
 
 impl GetTransactionByHashRequest {
-    pub fn parse(params: &Option<Vec<Value>>) -> Option<GetTransactionByHashRequest> {
-        let params = params.as_ref()?;
-        if params.len() != 1 {
-            return None;
-        };
-        Some(GetTransactionByHashRequest {
-            transaction_hash: serde_json::from_value(params[0].clone()).ok()?,
+    pub fn parse(params: &Option<Vec<Value>>) -> Result<GetTransactionByHashRequest, RpcErr> {
+        let params = expect_params(params, 1)?;
+        Ok(GetTransactionByHashRequest {
+            transaction_hash: parse_param(params, 0)?,
         })
     }
 }
 
 impl GetTransactionReceiptRequest {
-    pub fn parse(params: &Option<Vec<Value>>) -> Option<GetTransactionReceiptRequest> {
-        let params = params.as_ref()?;
-        if params.len() != 1 {
-            return None;
-        };
-        Some(GetTransactionReceiptRequest {
-            transaction_hash: serde_json::from_value(params[0].clone()).ok()?,
+    pub fn parse(params: &Option<Vec<Value>>) -> Result<GetTransactionReceiptRequest, RpcErr> {
+        let params = expect_params(params, 1)?;
+        Ok(GetTransactionReceiptRequest {
+            transaction_hash: parse_param(params, 0)?,
         })
     }
 }
 
 impl CreateAccessListRequest {
-    pub fn parse(params: &Option<Vec<Value>>) -> Option<CreateAccessListRequest> {
-        let params = params.as_ref()?;
-        if params.len() > 2 {
-            return None;
+    pub fn parse(params: &Option<Vec<Value>>) -> Result<CreateAccessListRequest, RpcErr> {
+        let params = params.as_deref().unwrap_or_default();
+        if params.is_empty() || params.len() > 2 {
+            return Err(RpcErr::BadParams(format!(
+                "expected 1 or 2 params, got {}",
+                params.len()
+            )));
         };
         let block = match params.get(1) {
             // Differentiate between missing and bad block param
-            Some(value) => Some(serde_json::from_value(value.clone()).ok()?),
+            Some(_) => Some(parse_param(params, 1)?),
             None => None,
         };
-        Some(CreateAccessListRequest {
-            transaction: serde_json::from_value(params.first()?.clone()).ok()?,
+        Ok(CreateAccessListRequest {
+            transaction: parse_param(params, 0)?,
             block,
         })
     }
@@ -210,43 +193,36 @@ pub fn get_block_by_number(
     storage: Store,
 ) -> Result<Value, RpcErr> {
     info!("Requested block with number: {}", request.block);
-    let block_number = match request.block {
-        BlockIdentifier::Tag(_) => unimplemented!("Obtain block number from tag"),
-        BlockIdentifier::Number(block_number) => block_number,
+    let Some(block_number) = request.block.resolve_block_number(&storage)? else {
+        // Block not found
+        return Ok(Value::Null);
     };
-    let header = storage.get_block_header(block_number);
-    let body = storage.get_block_body(block_number);
-    let (header, body) = match (header, body) {
-        (Ok(Some(header)), Ok(Some(body))) => (header, body),
+    let Some(header) = storage.get_block_header(block_number)? else {
         // Block not found
-        (Ok(_), Ok(_)) => return Ok(Value::Null),
-        // DB error
-        _ => return Err(RpcErr::Internal),
+        return Ok(Value::Null);
+    };
+    let Some(body) = storage.get_block_body(block_number)? else {
+        storage.check_block_not_pruned(block_number)?;
+        return Ok(Value::Null);
     };
     let block = BlockSerializable::from_block(header, body, request.hydrated);
 
-    serde_json::to_value(&block).map_err(|_| RpcErr::Internal)
+    serde_json::to_value(&block).map_err(|error| RpcErr::Internal(error.to_string()))
 }
 
 pub fn get_block_by_hash(request: &GetBlockByHashRequest, storage: Store) -> Result<Value, RpcErr> {
     info!("Requested block with hash: {}", request.block);
-    let block_number = match storage.get_block_number(request.block) {
-        Ok(Some(number)) => number,
-        Ok(_) => return Ok(Value::Null),
-        _ => return Err(RpcErr::Internal),
-    };
-    let header = storage.get_block_header(block_number);
-    let body = storage.get_block_body(block_number);
-    let (header, body) = match (header, body) {
-        (Ok(Some(header)), Ok(Some(body))) => (header, body),
+    let Some(header) = storage.get_block_header_by_hash(request.block)? else {
         // Block not found
-        (Ok(_), Ok(_)) => return Ok(Value::Null),
-        // DB error
-        _ => return Err(RpcErr::Internal),
+        return Ok(Value::Null);
+    };
+    let Some(body) = storage.get_block_body_by_hash(request.block)? else {
+        storage.check_block_not_pruned(header.number)?;
+        return Ok(Value::Null);
     };
     let block = BlockSerializable::from_block(header, body, request.hydrated);
 
-    serde_json::to_value(&block).map_err(|_| RpcErr::Internal)
+    serde_json::to_value(&block).map_err(|error| RpcErr::Internal(error.to_string()))
 }
 
 pub fn get_block_transaction_count_by_number(
@@ -257,18 +233,18 @@ pub fn get_block_transaction_count_by_number(
         "Requested transaction count for block with number: {}",
         request.block
     );
-    let block_number = match request.block {
-        BlockIdentifier::Tag(_) => unimplemented!("Obtain block number from tag"),
-        BlockIdentifier::Number(block_number) => block_number,
+    let Some(block_number) = request.block.resolve_block_number(&storage)? else {
+        // Block not found
+        return Ok(Value::Null);
     };
-    let block_body = match storage.get_block_body(block_number) {
-        Ok(Some(block_body)) => block_body,
-        Ok(_) => return Ok(Value::Null),
-        _ => return Err(RpcErr::Internal),
+    let Some(block_body) = storage.get_block_body(block_number)? else {
+        storage.check_block_not_pruned(block_number)?;
+        return Ok(Value::Null);
     };
     let transaction_count = block_body.transactions.len();
 
-    serde_json::to_value(format!("{:#x}", transaction_count)).map_err(|_| RpcErr::Internal)
+    serde_json::to_value(format!("{:#x}", transaction_count))
+        .map_err(|error| RpcErr::Internal(error.to_string()))
 }
 
 pub fn get_transaction_by_block_number_and_index(
@@ -279,21 +255,20 @@ pub fn get_transaction_by_block_number_and_index(
         "Requested transaction at index: {} of block with number: {}",
         request.transaction_index, request.block,
     );
-    let block_number = match request.block {
-        BlockIdentifier::Tag(_) => unimplemented!("Obtain block number from tag"),
-        BlockIdentifier::Number(block_number) => block_number,
+    let Some(block_number) = request.block.resolve_block_number(&storage)? else {
+        // Block not found
+        return Ok(Value::Null);
     };
-    let block_body = match storage.get_block_body(block_number) {
-        Ok(Some(block_body)) => block_body,
-        Ok(_) => return Ok(Value::Null),
-        _ => return Err(RpcErr::Internal),
+    let Some(block_body) = storage.get_block_body(block_number)? else {
+        storage.check_block_not_pruned(block_number)?;
+        return Ok(Value::Null);
     };
     let tx = match block_body.transactions.get(request.transaction_index) {
         Some(tx) => tx,
         None => return Ok(Value::Null),
     };
 
-    serde_json::to_value(tx).map_err(|_| RpcErr::Internal)
+    serde_json::to_value(tx).map_err(|error| RpcErr::Internal(error.to_string()))
 }
 
 pub fn get_transaction_by_block_hash_and_index(
@@ -304,22 +279,18 @@ pub fn get_transaction_by_block_hash_and_index(
         "Requested transaction at index: {} of block with hash: {}",
         request.transaction_index, request.block,
     );
-    let block_number = match storage.get_block_number(request.block) {
-        Ok(Some(number)) => number,
-        Ok(_) => return Ok(Value::Null),
-        _ => return Err(RpcErr::Internal),
-    };
-    let block_body = match storage.get_block_body(block_number) {
-        Ok(Some(block_body)) => block_body,
-        Ok(_) => return Ok(Value::Null),
-        _ => return Err(RpcErr::Internal),
+    let Some(block_body) = storage.get_block_body_by_hash(request.block)? else {
+        if let Some(header) = storage.get_block_header_by_hash(request.block)? {
+            storage.check_block_not_pruned(header.number)?;
+        }
+        return Ok(Value::Null);
     };
     let tx = match block_body.transactions.get(request.transaction_index) {
         Some(tx) => tx,
         None => return Ok(Value::Null),
     };
 
-    serde_json::to_value(tx).map_err(|_| RpcErr::Internal)
+    serde_json::to_value(tx).map_err(|error| RpcErr::Internal(error.to_string()))
 }
 
 pub fn get_block_receipts(
@@ -330,18 +301,17 @@ pub fn get_block_receipts(
         "Requested receipts for block with number: {}",
         request.block
     );
-    let block_number = match request.block {
-        BlockIdentifier::Tag(_) => unimplemented!("Obtain block number from tag"),
-        BlockIdentifier::Number(block_number) => block_number,
+    let Some(block_number) = request.block.resolve_block_number(&storage)? else {
+        // Block not found
+        return Ok(Value::Null);
     };
-    let header = storage.get_block_header(block_number);
-    let body = storage.get_block_body(block_number);
-    let (header, body) = match (header, body) {
-        (Ok(Some(header)), Ok(Some(body))) => (header, body),
+    let Some(header) = storage.get_block_header(block_number)? else {
         // Block not found
-        (Ok(_), Ok(_)) => return Ok(Value::Null),
-        // DB error
-        _ => return Err(RpcErr::Internal),
+        return Ok(Value::Null);
+    };
+    let Some(body) = storage.get_block_body(block_number)? else {
+        storage.check_block_not_pruned(block_number)?;
+        return Ok(Value::Null);
     };
     // Fetch receipt info from block
     let block_info = header.receipt_info();
@@ -349,13 +319,14 @@ pub fn get_block_receipts(
     let mut receipts = Vec::new();
     for (index, tx) in body.transactions.iter().enumerate() {
         let index = index as u64;
-        let receipt = match storage.get_receipt(block_number, index) {
-            Ok(Some(receipt)) => receipt,
-            Ok(_) => return Ok(Value::Null),
-            _ => return Err(RpcErr::Internal),
+        let Some(receipt) = storage.get_receipt(block_number, index)? else {
+            storage.check_block_not_pruned(block_number)?;
+            return Ok(Value::Null);
         };
         let block_info = block_info.clone();
-        let tx_info = tx.receipt_info(index);
+        let tx_info = tx
+            .receipt_info(index)
+            .map_err(|error| RpcErr::Internal(error.to_string()))?;
         receipts.push(ReceiptWithTxAndBlockInfo {
             receipt,
             tx_info,
@@ -363,7 +334,7 @@ pub fn get_block_receipts(
         })
     }
 
-    serde_json::to_value(&receipts).map_err(|_| RpcErr::Internal)
+    serde_json::to_value(&receipts).map_err(|error| RpcErr::Internal(error.to_string()))
 }
 
 pub fn get_transaction_by_hash(
@@ -374,14 +345,11 @@ pub fn get_transaction_by_hash(
         "Requested transaction with hash: {}",
         request.transaction_hash,
     );
-    let transaction: ethereum_rust_core::types::Transaction =
-        match storage.get_transaction_by_hash(request.transaction_hash) {
-            Ok(Some(transaction)) => transaction,
-            Ok(_) => return Ok(Value::Null),
-            _ => return Err(RpcErr::Internal),
-        };
+    let Some(transaction) = storage.get_transaction_by_hash(request.transaction_hash)? else {
+        return Ok(Value::Null);
+    };
 
-    serde_json::to_value(transaction).map_err(|_| RpcErr::Internal)
+    serde_json::to_value(transaction).map_err(|error| RpcErr::Internal(error.to_string()))
 }
 
 pub fn get_transaction_receipt(
@@ -392,25 +360,19 @@ pub fn get_transaction_receipt(
         "Requested receipt for transaction {}",
         request.transaction_hash,
     );
-    let (block_number, index) = match storage.get_transaction_location(request.transaction_hash) {
-        Ok(Some(location)) => location,
-        Ok(_) => return Ok(Value::Null),
-        _ => return Err(RpcErr::Internal),
+    let Some((block_number, index)) = storage.get_transaction_location(request.transaction_hash)?
+    else {
+        return Ok(Value::Null);
     };
-    let block_header = match storage.get_block_header(block_number) {
-        Ok(Some(block_header)) => block_header,
-        Ok(_) => return Ok(Value::Null),
-        _ => return Err(RpcErr::Internal),
+    let Some(block_header) = storage.get_block_header(block_number)? else {
+        return Ok(Value::Null);
     };
-    let block_body = match storage.get_block_body(block_number) {
-        Ok(Some(block_body)) => block_body,
-        Ok(_) => return Ok(Value::Null),
-        _ => return Err(RpcErr::Internal),
+    let Some(block_body) = storage.get_block_body(block_number)? else {
+        storage.check_block_not_pruned(block_number)?;
+        return Ok(Value::Null);
     };
-    let receipt = match storage.get_receipt(block_number, index) {
-        Ok(Some(receipt)) => receipt,
-        Ok(_) => return Ok(Value::Null),
-        _ => return Err(RpcErr::Internal),
+    let Some(receipt) = storage.get_receipt(block_number, index)? else {
+        return Ok(Value::Null);
     };
     let tx = match index
         .try_into()
@@ -421,13 +383,15 @@ pub fn get_transaction_receipt(
         _ => return Ok(Value::Null),
     };
     let block_info = block_header.receipt_info();
-    let tx_info = tx.receipt_info(index);
+    let tx_info = tx
+        .receipt_info(index)
+        .map_err(|error| RpcErr::Internal(error.to_string()))?;
     let receipt = ReceiptWithTxAndBlockInfo {
         receipt,
         tx_info,
         block_info,
     };
-    serde_json::to_value(&receipt).map_err(|_| RpcErr::Internal)
+    serde_json::to_value(&receipt).map_err(|error| RpcErr::Internal(error.to_string()))
 }
 
 pub fn create_access_list(
@@ -436,46 +400,29 @@ pub fn create_access_list(
 ) -> Result<Value, RpcErr> {
     let block = request.block.clone().unwrap_or_default();
     info!("Requested access list creation for tx on block: {}", block);
-    let block_number = match block {
-        BlockIdentifier::Tag(_) => unimplemented!("Obtain block number from tag"),
-        BlockIdentifier::Number(block_number) => block_number,
-    };
-    let header = match storage.get_block_header(block_number) {
-        Ok(Some(header)) => header,
-        // Block not found
-        Ok(_) => return Ok(Value::Null),
-        // DB error
-        _ => return Err(RpcErr::Internal),
-    };
+    let header = block.resolve_state_block(&storage)?;
+    let chain_config = storage.get_chain_config()?;
+    let spec_id = spec_id(&chain_config, header.number, header.timestamp);
     // Run transaction and obtain access list
     let (gas_used, access_list, error) = match ethereum_rust_evm::create_access_list(
         &request.transaction,
         &header,
-        &mut evm_state(storage),
-        SpecId::CANCUN,
-    )
-    .map_err(|_| RpcErr::Vm)?
-    {
+        &mut evm_state(storage, header.number),
+        spec_id,
+    )? {
         (
             ExecutionResult::Success {
                 reason: _,
                 gas_used,
                 gas_refunded: _,
+                logs: _,
                 output: _,
             },
             access_list,
         ) => (gas_used, access_list, None),
-        (
-            ExecutionResult::Revert {
-                gas_used,
-                output: _,
-            },
-            access_list,
-        ) => (
-            gas_used,
-            access_list,
-            Some("Transaction Reverted".to_string()),
-        ),
+        (ExecutionResult::Revert { gas_used, output }, access_list) => {
+            (gas_used, access_list, Some(revert_message(&output)))
+        }
         (ExecutionResult::Halt { reason, gas_used }, access_list) => {
             (gas_used, access_list, Some(reason))
         }
@@ -492,7 +439,33 @@ pub fn create_access_list(
         gas_used,
     };
 
-    serde_json::to_value(result).map_err(|_| RpcErr::Internal)
+    serde_json::to_value(result).map_err(|error| RpcErr::Internal(error.to_string()))
+}
+
+impl BlockIdentifier {
+    /// Resolves the block identifier into a block number, using the chain pointers kept in the store for block tags
+    pub fn resolve_block_number(&self, storage: &Store) -> Result<Option<BlockNumber>, StoreError> {
+        match self {
+            BlockIdentifier::Number(block_number) => Ok(Some(*block_number)),
+            BlockIdentifier::Tag(tag) => match tag {
+                BlockTag::Earliest => Ok(Some(0)),
+                BlockTag::Finalized => storage.get_finalized_block_number(),
+                BlockTag::Safe => storage.get_safe_block_number(),
+                // TODO: Return the pending block once we start building payloads
+                BlockTag::Latest | BlockTag::Pending => storage.get_latest_block_number(),
+            },
+        }
+    }
+
+    /// Resolves the block whose state is queried, which must be part of the chain
+    pub(crate) fn resolve_state_block(&self, storage: &Store) -> Result<BlockHeader, RpcErr> {
+        let block_number = self
+            .resolve_block_number(storage)?
+            .ok_or(RpcErr::UnknownBlock)?;
+        storage
+            .get_block_header(block_number)?
+            .ok_or(RpcErr::HeaderNotFound)
+    }
 }
 
 impl Display for BlockIdentifier {
@@ -6,14 +6,13 @@ use crate::utils::RpcErr;
 
 pub fn chain_id(storage: Store) -> Result<Value, RpcErr> {
     info!("Requested chain id");
-    match storage.get_chain_id() {
-        Ok(Some(chain_id)) => {
-            serde_json::to_value(format!("{:#x}", chain_id)).map_err(|_| RpcErr::Internal)
-        }
-        // Treat missing value as internal error as we should have a chain id
-        // loaded in the db from loading the genesis file
-        _ => Err(RpcErr::Internal),
-    }
+    // Treat missing value as internal error as we should have a chain id
+    // loaded in the db from loading the genesis file
+    let chain_id = storage
+        .get_chain_id()?
+        .ok_or_else(|| RpcErr::Internal("missing chain id".to_owned()))?;
+    serde_json::to_value(format!("{:#x}", chain_id))
+        .map_err(|error| RpcErr::Internal(error.to_string()))
 }
 
 pub fn syncing() -> Result<Value, RpcErr> {
@@ -0,0 +1,363 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use ethereum_rust_blockchain::{mempool::Mempool, notifier::ChainEvent};
+use ethereum_rust_core::types::BlockNumber;
+use ethereum_rust_storage::Store;
+use serde_json::Value;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use tracing::{info, warn};
+
+use crate::{
+    eth::{
+        block::BlockIdentifier,
+        logs::{self, collect_block_logs, GetLogsRequest, LogsFilter, LogsLimits, RpcLog},
+    },
+    utils::{expect_params, parse_hex_param, parse_param, RpcErr},
+};
+
+/// Time after which a filter that hasn't been polled is removed by default
+pub const DEFAULT_FILTER_TIMEOUT: Duration = Duration::from_secs(300);
+/// Number of filters that can be installed at the same time by default
+pub const DEFAULT_MAX_FILTERS: usize = 1000;
+
+/// Keeps track of the filters installed through the rpc, so that clients can poll them for the
+/// changes since their last poll
+/// Clones share the same filters
+#[derive(Clone, Debug)]
+pub struct FilterManager {
+    inner: Arc<Mutex<FilterManagerInner>>,
+    /// Filters that are not polled within this time are removed
+    timeout: Duration,
+    /// New filters are rejected while this many are installed
+    max_filters: usize,
+}
+
+#[derive(Debug, Default)]
+struct FilterManagerInner {
+    filters: HashMap<u64, ActiveFilter>,
+    next_id: u64,
+}
+
+#[derive(Debug)]
+struct ActiveFilter {
+    kind: FilterKind,
+    last_polled: Instant,
+    /// Lowest block removed from the canonical chain by a reorg since the last poll started, the
+    /// next poll starts from it so that the blocks of the new chain are read
+    removed_block: Option<BlockNumber>,
+}
+
+#[derive(Clone, Debug)]
+enum FilterKind {
+    /// Logs matching the filter, the next poll starts at the given block
+    Logs {
+        filter: LogsFilter,
+        next_block: BlockNumber,
+    },
+    /// Hashes of new canonical blocks, the next poll starts at the given block
+    Blocks { next_block: BlockNumber },
+    /// Hashes of new pooled transactions, the next poll starts at the given arrival sequence
+    /// number of the mempool
+    PendingTransactions { next_sequence: u64 },
+}
+
+pub struct NewFilterRequest {
+    pub filter: LogsFilter,
+}
+
+pub struct FilterIdRequest {
+    pub id: u64,
+}
+
+impl NewFilterRequest {
+    pub fn parse(params: &Option<Vec<Value>>) -> Result<NewFilterRequest, RpcErr> {
+        let params = expect_params(params, 1)?;
+        let filter: LogsFilter = parse_param(params, 0)?;
+        // Filters are polled for new blocks, so they can't target a single block
+        if filter.block_hash.is_some() {
+            return Err(RpcErr::BadParams(
+                "invalid param 0: filters can't select a block by its hash".to_string(),
+            ));
+        }
+        Ok(NewFilterRequest { filter })
+    }
+}
+
+impl FilterIdRequest {
+    pub fn parse(params: &Option<Vec<Value>>) -> Result<FilterIdRequest, RpcErr> {
+        let params = expect_params(params, 1)?;
+        Ok(FilterIdRequest {
+            id: parse_hex_param(params, 0)?,
+        })
+    }
+}
+
+impl Default for FilterManager {
+    fn default() -> Self {
+        Self::new(DEFAULT_FILTER_TIMEOUT, DEFAULT_MAX_FILTERS)
+    }
+}
+
+impl FilterManager {
+    /// Creates a manager without filters, which removes the filters that are not polled within
+    /// the given time and holds at most the given number of them
+    pub fn new(timeout: Duration, max_filters: usize) -> Self {
+        Self {
+            inner: Default::default(),
+            timeout,
+            max_filters,
+        }
+    }
+
+    /// Adds a filter and returns its id, fails if the maximum number of filters is installed
+    fn install(&self, kind: FilterKind) -> Result<u64, RpcErr> {
+        let mut inner = self.lock();
+        if inner.filters.len() >= self.max_filters {
+            return Err(RpcErr::TooManyFilters);
+        }
+        let id = inner.next_id;
+        inner.next_id += 1;
+        inner.filters.insert(
+            id,
+            ActiveFilter {
+                kind,
+                last_polled: Instant::now(),
+                removed_block: None,
+            },
+        );
+        Ok(id)
+    }
+
+    /// Makes the filters that already read the given block read it again on their next poll, as
+    /// it was removed from the canonical chain
+    pub(crate) fn remove_block(&self, block_number: BlockNumber) {
+        for filter in self.lock().filters.values_mut() {
+            if let FilterKind::Logs { .. } | FilterKind::Blocks { .. } = filter.kind {
+                filter.removed_block = Some(
+                    filter
+                        .removed_block
+                        .map_or(block_number, |removed| removed.min(block_number)),
+                );
+            }
+        }
+    }
+
+    /// Locks the filters, removing the ones that expired
+    fn lock(&self) -> std::sync::MutexGuard<'_, FilterManagerInner> {
+        let mut inner = self.inner.lock().unwrap();
+        inner
+            .filters
+            .retain(|_, filter| filter.last_polled.elapsed() <= self.timeout);
+        inner
+    }
+}
+
+pub fn new_filter(
+    request: &NewFilterRequest,
+    storage: Store,
+    filters: FilterManager,
+) -> Result<Value, RpcErr> {
+    info!("Requested new logs filter: {:?}", request.filter);
+    let next_block = next_block_number(&storage)?;
+    // Only logs of blocks added after the filter is installed are returned as changes
+    let next_block = match request.filter.from_block {
+        Some(BlockIdentifier::Number(from_block)) => from_block.max(next_block),
+        _ => next_block,
+    };
+    let id = filters.install(FilterKind::Logs {
+        filter: request.filter.clone(),
+        next_block,
+    })?;
+    Ok(filter_id_value(id))
+}
+
+pub fn new_block_filter(storage: Store, filters: FilterManager) -> Result<Value, RpcErr> {
+    info!("Requested new block filter");
+    let next_block = next_block_number(&storage)?;
+    let id = filters.install(FilterKind::Blocks { next_block })?;
+    Ok(filter_id_value(id))
+}
+
+pub fn new_pending_transaction_filter(
+    mempool: Mempool,
+    filters: FilterManager,
+) -> Result<Value, RpcErr> {
+    info!("Requested new pending transaction filter");
+    // Nothing arrives after the maximum sequence number, so this only reads the next one
+    let (_, next_sequence) = mempool.transaction_hashes_since(u64::MAX);
+    let id = filters.install(FilterKind::PendingTransactions { next_sequence })?;
+    Ok(filter_id_value(id))
+}
+
+pub fn get_filter_changes(
+    request: &FilterIdRequest,
+    storage: Store,
+    mempool: Mempool,
+    filters: FilterManager,
+    limits: LogsLimits,
+) -> Result<Value, RpcErr> {
+    info!("Requested changes of filter {:#x}", request.id);
+    // The changes are read without holding the filters, so that other requests are not blocked
+    let mut kind = {
+        let mut inner = filters.lock();
+        let active_filter = inner
+            .filters
+            .get_mut(&request.id)
+            .ok_or(RpcErr::FilterNotFound)?;
+        active_filter.last_polled = Instant::now();
+        let mut kind = active_filter.kind.clone();
+        // The poll starts back at the first block removed by a reorg, the blocks removed while
+        // it runs are read by the next one
+        if let (
+            FilterKind::Logs { next_block, .. } | FilterKind::Blocks { next_block },
+            Some(removed_block),
+        ) = (&mut kind, active_filter.removed_block.take())
+        {
+            *next_block = (*next_block).min(removed_block);
+        }
+        kind
+    };
+    let changes = match &mut kind {
+        FilterKind::Logs { filter, next_block } => {
+            let latest_block_number = latest_block_number(&storage)?;
+            let to_block = match filter.to_block {
+                Some(BlockIdentifier::Number(to_block)) => to_block.min(latest_block_number),
+                _ => latest_block_number,
+            };
+            // Filters that were not polled for many blocks catch up gradually, so that each
+            // poll stays within the range limit
+            let to_block = to_block.min(
+                next_block
+                    .saturating_add(limits.max_block_range)
+                    .saturating_sub(1),
+            );
+            let (logs, next_unread_block) =
+                get_logs_since(filter, &storage, *next_block, to_block, limits)?;
+            *next_block = (*next_block).max(next_unread_block);
+            serde_json::to_value(logs)
+        }
+        FilterKind::Blocks { next_block } => {
+            let latest_block_number = latest_block_number(&storage)?;
+            let mut block_hashes = Vec::new();
+            for block_number in *next_block..=latest_block_number {
+                if let Some(block_hash) = storage.get_canonical_block_hash(block_number)? {
+                    block_hashes.push(block_hash);
+                }
+            }
+            *next_block = (*next_block).max(latest_block_number + 1);
+            serde_json::to_value(block_hashes)
+        }
+        FilterKind::PendingTransactions { next_sequence } => {
+            let (transaction_hashes, sequence) = mempool.transaction_hashes_since(*next_sequence);
+            *next_sequence = sequence;
+            serde_json::to_value(transaction_hashes)
+        }
+    }
+    .map_err(|error| RpcErr::Internal(error.to_string()))?;
+    // The filter may have been uninstalled in the meantime
+    if let Some(active_filter) = filters.lock().filters.get_mut(&request.id) {
+        active_filter.kind = kind;
+    }
+    Ok(changes)
+}
+
+/// Collects the logs of the canonical blocks in the given range that match the filter, stopping
+/// before the block whose logs would exceed the results limit, and returns them along with the
+/// number of the first block that was not read
+/// The logs of a block are never split, so a block with more matching logs than the limit is
+/// returned on its own instead of blocking the filter
+fn get_logs_since(
+    filter: &LogsFilter,
+    storage: &Store,
+    from_block: BlockNumber,
+    to_block: BlockNumber,
+    limits: LogsLimits,
+) -> Result<(Vec<RpcLog>, BlockNumber), RpcErr> {
+    let mut logs = Vec::new();
+    for block_number in from_block..=to_block {
+        let (Some(block_hash), Some(header)) = (
+            storage.get_canonical_block_hash(block_number)?,
+            storage.get_block_header(block_number)?,
+        ) else {
+            // Block not found
+            continue;
+        };
+        let mut block_logs = Vec::new();
+        collect_block_logs(filter, storage, block_hash, &header, false, &mut block_logs)?;
+        if !logs.is_empty() && logs.len() + block_logs.len() > limits.max_results {
+            return Ok((logs, block_number));
+        }
+        logs.append(&mut block_logs);
+    }
+    Ok((logs, to_block.saturating_add(1)))
+}
+
+pub fn get_filter_logs(
+    request: &FilterIdRequest,
+    storage: Store,
+    filters: FilterManager,
+    limits: LogsLimits,
+) -> Result<Value, RpcErr> {
+    info!("Requested logs of filter {:#x}", request.id);
+    let filter = {
+        let mut inner = filters.lock();
+        let active_filter = inner
+            .filters
+            .get_mut(&request.id)
+            .ok_or(RpcErr::FilterNotFound)?;
+        active_filter.last_polled = Instant::now();
+        match &active_filter.kind {
+            FilterKind::Logs { filter, .. } => filter.clone(),
+            // Only logs filters have logs
+            _ => return Err(RpcErr::FilterNotFound),
+        }
+    };
+    logs::get_logs(&GetLogsRequest { filter }, storage, limits)
+}
+
+pub fn uninstall_filter(
+    request: &FilterIdRequest,
+    filters: FilterManager,
+) -> Result<Value, RpcErr> {
+    info!("Requested removal of filter {:#x}", request.id);
+    let removed = filters.lock().filters.remove(&request.id).is_some();
+    Ok(Value::Bool(removed))
+}
+
+/// Keeps the filters up to date with the blocks removed from the canonical chain, until the
+/// notifier is dropped
+pub(crate) async fn track_removed_blocks(filters: FilterManager, mut events: Receiver<ChainEvent>) {
+    loop {
+        match events.recv().await {
+            Ok(ChainEvent::RemovedBlock(_, header)) => filters.remove_block(header.number),
+            Ok(_) => {}
+            // A removed block may have been skipped, so the filters may return stale changes
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Filters skipped {skipped} chain events");
+            }
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
+fn filter_id_value(id: u64) -> Value {
+    Value::String(format!("{id:#x}"))
+}
+
+fn latest_block_number(storage: &Store) -> Result<BlockNumber, RpcErr> {
+    storage
+        .get_latest_block_number()
+        .map(Option::unwrap_or_default)
+        .map_err(RpcErr::from)
+}
+
+/// Number of the first block that is not part of the chain yet
+fn next_block_number(storage: &Store) -> Result<BlockNumber, RpcErr> {
+    Ok(storage
+        .get_latest_block_number()?
+        .map_or(0, |block_number| block_number + 1))
+}
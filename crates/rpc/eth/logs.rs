@@ -0,0 +1,299 @@
+use ethereum_rust_core::{
+    types::{BlockHash, BlockHeader, BlockNumber, Log},
+    Address, Bloom, BloomInput, H256,
+};
+use ethereum_rust_storage::{error::StoreError, Store};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::info;
+
+use crate::{
+    eth::block::BlockIdentifier,
+    utils::{expect_params, parse_param, RpcErr},
+};
+
+/// Maximum number of blocks a logs query can span by default
+pub const DEFAULT_MAX_LOGS_BLOCK_RANGE: u64 = 10000;
+/// Maximum number of logs a logs query can return by default
+pub const DEFAULT_MAX_LOGS_RESULTS: usize = 10000;
+
+/// Limits applied to logs queries, so that a single request can't make the node scan or return
+/// an unbounded amount of data
+#[derive(Debug, Clone, Copy)]
+pub struct LogsLimits {
+    pub max_block_range: u64,
+    pub max_results: usize,
+}
+
+impl Default for LogsLimits {
+    fn default() -> Self {
+        Self {
+            max_block_range: DEFAULT_MAX_LOGS_BLOCK_RANGE,
+            max_results: DEFAULT_MAX_LOGS_RESULTS,
+        }
+    }
+}
+
+pub struct GetLogsRequest {
+    pub filter: LogsFilter,
+}
+
+/// Selects logs by the blocks they were emitted in, either a range or a single block hash, the
+/// address that emitted them and their topics
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LogsFilter {
+    pub from_block: Option<BlockIdentifier>,
+    pub to_block: Option<BlockIdentifier>,
+    pub block_hash: Option<BlockHash>,
+    /// Logs must be emitted by one of these addresses, any address matches if empty
+    #[serde(default)]
+    pub address: Option<OneOrMany<Address>>,
+    /// Each position matches the topic at the same position of the log, with `None` matching any
+    /// topic and a list matching any of its topics
+    #[serde(default)]
+    pub topics: Vec<Option<OneOrMany<H256>>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+impl<T> OneOrMany<T> {
+    fn as_slice(&self) -> &[T] {
+        match self {
+            OneOrMany::One(value) => std::slice::from_ref(value),
+            OneOrMany::Many(values) => values,
+        }
+    }
+}
+
+/// A log along with the location where it was emitted
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcLog {
+    #[serde(flatten)]
+    pub log: Log,
+    pub block_hash: BlockHash,
+    #[serde(with = "ethereum_rust_core::serde_utils::u64::hex_str")]
+    pub block_number: BlockNumber,
+    pub transaction_hash: H256,
+    #[serde(with = "ethereum_rust_core::serde_utils::u64::hex_str")]
+    pub transaction_index: u64,
+    /// Index of the log among all logs of the block
+    #[serde(with = "ethereum_rust_core::serde_utils::u64::hex_str")]
+    pub log_index: u64,
+    /// Whether the log was removed from the canonical chain due to a reorg
+    pub removed: bool,
+}
+
+impl GetLogsRequest {
+    pub fn parse(params: &Option<Vec<Value>>) -> Result<GetLogsRequest, RpcErr> {
+        let params = expect_params(params, 1)?;
+        let filter: LogsFilter = parse_param(params, 0)?;
+        // A block hash can't be combined with a block range
+        if filter.block_hash.is_some() && (filter.from_block.is_some() || filter.to_block.is_some())
+        {
+            return Err(RpcErr::BadParams(
+                "invalid param 0: blockHash can't be combined with fromBlock or toBlock"
+                    .to_string(),
+            ));
+        }
+        Ok(GetLogsRequest { filter })
+    }
+}
+
+impl LogsFilter {
+    /// Checks whether a block with the given logs bloom may contain logs matching the filter,
+    /// blocks for which this is false can be skipped without reading their receipts
+    pub fn may_match(&self, bloom: &Bloom) -> bool {
+        let contains = |bytes: &[u8]| bloom.contains_input(BloomInput::Raw(bytes));
+        let address_matches = match &self.address {
+            Some(addresses) if !addresses.as_slice().is_empty() => addresses
+                .as_slice()
+                .iter()
+                .any(|address| contains(address.as_bytes())),
+            _ => true,
+        };
+        address_matches
+            && self.topics.iter().all(|topics| match topics {
+                Some(topics) if !topics.as_slice().is_empty() => topics
+                    .as_slice()
+                    .iter()
+                    .any(|topic| contains(topic.as_bytes())),
+                _ => true,
+            })
+    }
+
+    /// Checks whether the log matches the filter's addresses and topics
+    pub fn matches(&self, log: &Log) -> bool {
+        let address_matches = match &self.address {
+            Some(addresses) if !addresses.as_slice().is_empty() => {
+                addresses.as_slice().contains(&log.address)
+            }
+            _ => true,
+        };
+        address_matches
+            && self
+                .topics
+                .iter()
+                .enumerate()
+                .all(|(position, topics)| match topics {
+                    Some(topics) if !topics.as_slice().is_empty() => log
+                        .topics
+                        .get(position)
+                        .is_some_and(|topic| topics.as_slice().contains(topic)),
+                    _ => true,
+                })
+    }
+}
+
+pub fn get_logs(
+    request: &GetLogsRequest,
+    storage: Store,
+    limits: LogsLimits,
+) -> Result<Value, RpcErr> {
+    info!("Requested logs with filter: {:?}", request.filter);
+    let logs = match request.filter.block_hash {
+        Some(block_hash) => {
+            let Some(header) = storage.get_block_header_by_hash(block_hash)? else {
+                // Block not found
+                return Ok(Value::Null);
+            };
+            let mut logs = Vec::new();
+            collect_block_logs(
+                &request.filter,
+                &storage,
+                block_hash,
+                &header,
+                false,
+                &mut logs,
+            )?;
+            check_results_limit(&logs, limits)?;
+            logs
+        }
+        None => {
+            let Some(latest_block_number) = storage.get_latest_block_number()? else {
+                // No blocks stored yet
+                return Ok(Value::Array(vec![]));
+            };
+            let from_block =
+                resolve_range_bound(&request.filter.from_block, &storage, latest_block_number)?;
+            let to_block =
+                resolve_range_bound(&request.filter.to_block, &storage, latest_block_number)?;
+            get_range_logs(
+                &request.filter,
+                &storage,
+                from_block,
+                to_block.min(latest_block_number),
+                limits,
+            )?
+        }
+    };
+    serde_json::to_value(logs).map_err(|error| RpcErr::Internal(error.to_string()))
+}
+
+/// Resolves a bound of the block range of a logs query, missing bounds default to the latest
+/// block
+fn resolve_range_bound(
+    block: &Option<BlockIdentifier>,
+    storage: &Store,
+    latest_block_number: BlockNumber,
+) -> Result<BlockNumber, RpcErr> {
+    match block {
+        // The tagged block may not be known yet
+        Some(block) => block
+            .resolve_block_number(storage)?
+            .ok_or(RpcErr::UnknownBlock),
+        None => Ok(latest_block_number),
+    }
+}
+
+/// Collects the logs of the canonical blocks in the given range that match the filter, within
+/// the given limits
+pub(crate) fn get_range_logs(
+    filter: &LogsFilter,
+    storage: &Store,
+    from_block: BlockNumber,
+    to_block: BlockNumber,
+    limits: LogsLimits,
+) -> Result<Vec<RpcLog>, RpcErr> {
+    if from_block > to_block {
+        return Ok(vec![]);
+    }
+    if to_block - from_block >= limits.max_block_range {
+        return Err(RpcErr::LogsLimitExceeded(format!(
+            "block range exceeds the limit of {} blocks",
+            limits.max_block_range
+        )));
+    }
+    let mut logs = Vec::new();
+    for block_number in from_block..=to_block {
+        let (Some(block_hash), Some(header)) = (
+            storage.get_canonical_block_hash(block_number)?,
+            storage.get_block_header(block_number)?,
+        ) else {
+            // Block not found
+            continue;
+        };
+        collect_block_logs(filter, storage, block_hash, &header, false, &mut logs)?;
+        check_results_limit(&logs, limits)?;
+    }
+    Ok(logs)
+}
+
+fn check_results_limit(logs: &[RpcLog], limits: LogsLimits) -> Result<(), RpcErr> {
+    if logs.len() > limits.max_results {
+        return Err(RpcErr::LogsLimitExceeded(format!(
+            "query returned more than {} results",
+            limits.max_results
+        )));
+    }
+    Ok(())
+}
+
+/// Adds the logs of the block that match the filter to the given list
+/// The block's receipts are only read if its logs bloom may contain matching logs
+/// The logs of blocks that left the canonical chain are marked as removed
+pub(crate) fn collect_block_logs(
+    filter: &LogsFilter,
+    storage: &Store,
+    block_hash: BlockHash,
+    header: &BlockHeader,
+    removed: bool,
+    logs: &mut Vec<RpcLog>,
+) -> Result<(), StoreError> {
+    if !filter.may_match(&header.logs_bloom) {
+        return Ok(());
+    }
+    let Some(body) = storage.get_block_body_by_hash(block_hash)? else {
+        storage.check_block_not_pruned(header.number)?;
+        return Ok(());
+    };
+    let mut log_index = 0;
+    for (transaction_index, transaction) in body.transactions.iter().enumerate() {
+        let transaction_index = transaction_index as u64;
+        let Some(receipt) = storage.get_receipt_by_hash(block_hash, transaction_index)? else {
+            continue;
+        };
+        let transaction_hash = transaction.compute_hash();
+        for log in receipt.logs {
+            if filter.matches(&log) {
+                logs.push(RpcLog {
+                    log,
+                    block_hash,
+                    block_number: header.number,
+                    transaction_hash,
+                    transaction_index,
+                    log_index,
+                    removed,
+                });
+            }
+            log_index += 1;
+        }
+    }
+    Ok(())
+}
@@ -1,3 +1,7 @@
 pub(crate) mod account;
 pub(crate) mod block;
 pub(crate) mod client;
+pub(crate) mod filter;
+pub(crate) mod logs;
+pub(crate) mod subscription;
+pub(crate) mod transaction;
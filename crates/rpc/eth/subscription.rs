@@ -0,0 +1,162 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use ethereum_rust_blockchain::notifier::ChainEvent;
+use ethereum_rust_core::types::{BlockHash, BlockHeader};
+use ethereum_rust_storage::Store;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tracing::warn;
+
+use crate::{
+    eth::logs::{collect_block_logs, LogsFilter},
+    utils::{expect_params, parse_hex_param, parse_param, RpcErr},
+};
+
+/// Id of the next subscription, unique among all connections
+static NEXT_SUBSCRIPTION_ID: AtomicU64 = AtomicU64::new(0);
+
+/// What a subscription is notified of
+#[derive(Debug)]
+pub(crate) enum SubscriptionKind {
+    NewHeads,
+    Logs(LogsFilter),
+    NewPendingTransactions,
+}
+
+pub struct SubscribeRequest {
+    pub(crate) kind: SubscriptionKind,
+}
+
+pub struct UnsubscribeRequest {
+    pub id: u64,
+}
+
+/// A header as returned to `newHeads` subscriptions
+#[derive(Serialize)]
+struct NewHead<'a> {
+    hash: BlockHash,
+    #[serde(flatten)]
+    header: &'a BlockHeader,
+}
+
+impl SubscribeRequest {
+    pub fn parse(params: &Option<Vec<Value>>) -> Result<SubscribeRequest, RpcErr> {
+        let params = params.as_deref().unwrap_or_default();
+        let kind: String = parse_param(params, 0)?;
+        // Only logs subscriptions take a second param, their filter
+        let expected = if kind == "logs" { 1..=2 } else { 1..=1 };
+        if !expected.contains(&params.len()) {
+            return Err(RpcErr::BadParams(format!(
+                "expected {} params for {kind} subscriptions, got {}",
+                expected.end(),
+                params.len()
+            )));
+        }
+        let kind = match kind.as_str() {
+            "newHeads" => SubscriptionKind::NewHeads,
+            "newPendingTransactions" => SubscriptionKind::NewPendingTransactions,
+            "logs" => {
+                let filter: LogsFilter = match params.get(1) {
+                    Some(_) => parse_param(params, 1)?,
+                    None => LogsFilter::default(),
+                };
+                // Only the logs of new blocks are pushed, so the blocks can't be selected
+                if filter.block_hash.is_some()
+                    || filter.from_block.is_some()
+                    || filter.to_block.is_some()
+                {
+                    return Err(RpcErr::BadParams(
+                        "invalid param 1: logs subscriptions can't select blocks".to_string(),
+                    ));
+                }
+                SubscriptionKind::Logs(filter)
+            }
+            _ => {
+                return Err(RpcErr::BadParams(format!(
+                    "invalid param 0: unknown subscription kind {kind}"
+                )))
+            }
+        };
+        Ok(SubscribeRequest { kind })
+    }
+}
+
+impl UnsubscribeRequest {
+    pub fn parse(params: &Option<Vec<Value>>) -> Result<UnsubscribeRequest, RpcErr> {
+        let params = expect_params(params, 1)?;
+        Ok(UnsubscribeRequest {
+            id: parse_hex_param(params, 0)?,
+        })
+    }
+}
+
+/// Returns an id for a new subscription
+pub(crate) fn next_subscription_id() -> u64 {
+    NEXT_SUBSCRIPTION_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Builds the notifications of the event for the subscriptions that are interested in it
+pub(crate) fn notifications(
+    event: &ChainEvent,
+    subscriptions: &HashMap<u64, SubscriptionKind>,
+    storage: &Store,
+) -> Vec<Value> {
+    let mut notifications = Vec::new();
+    for (id, kind) in subscriptions {
+        match (event, kind) {
+            (ChainEvent::NewHead(block_hash, header), SubscriptionKind::NewHeads) => {
+                let new_head = NewHead {
+                    hash: *block_hash,
+                    header,
+                };
+                if let Ok(result) = serde_json::to_value(new_head) {
+                    notifications.push(notification(*id, result));
+                }
+            }
+            (
+                ChainEvent::NewHead(block_hash, header)
+                | ChainEvent::RemovedBlock(block_hash, header),
+                SubscriptionKind::Logs(filter),
+            ) => {
+                // The logs of the blocks removed by a reorg are notified again as removed
+                let removed = matches!(event, ChainEvent::RemovedBlock(..));
+                let mut logs = Vec::new();
+                if let Err(error) =
+                    collect_block_logs(filter, storage, *block_hash, header, removed, &mut logs)
+                {
+                    warn!("Failed to read the logs of block {block_hash:#x}: {error}");
+                }
+                // Each log is notified on its own
+                for log in logs {
+                    if let Ok(result) = serde_json::to_value(log) {
+                        notifications.push(notification(*id, result));
+                    }
+                }
+            }
+            (
+                ChainEvent::NewPendingTransaction(transaction_hash),
+                SubscriptionKind::NewPendingTransactions,
+            ) => notifications.push(notification(*id, json!(transaction_hash))),
+            _ => {}
+        }
+    }
+    notifications
+}
+
+fn notification(id: u64, result: Value) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "eth_subscription",
+        "params": {
+            "subscription": subscription_id_value(id),
+            "result": result,
+        },
+    })
+}
+
+pub(crate) fn subscription_id_value(id: u64) -> Value {
+    Value::String(format!("{id:#x}"))
+}
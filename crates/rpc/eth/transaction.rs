@@ -0,0 +1,235 @@
+use ethereum_rust_blockchain::{error::MempoolError, mempool::Mempool};
+use ethereum_rust_core::{
+    types::{
+        BlockHeader, BlockNumber, GenericTransaction, StateOverrides, Transaction, TxType,
+        WrappedEIP4844Transaction,
+    },
+    U256,
+};
+use ethereum_rust_evm::{
+    apply_state_overrides, evm_state, simulate_tx_from_generic, spec_id, EvmError, EvmState,
+    ExecutionResult, SpecId,
+};
+use ethereum_rust_storage::Store;
+use serde_json::Value;
+use tracing::info;
+
+use crate::{
+    eth::block::BlockIdentifier,
+    utils::{expect_params, parse_param, RpcErr},
+};
+
+pub enum SendRawTransactionRequest {
+    Transaction(Transaction),
+    /// Blob transactions are received in their network form, along with their blobs
+    BlobTransaction(WrappedEIP4844Transaction),
+}
+
+pub struct CallRequest {
+    pub transaction: GenericTransaction,
+    pub block: Option<BlockIdentifier>,
+    pub state_overrides: Option<StateOverrides>,
+}
+
+pub struct EstimateGasRequest {
+    pub transaction: GenericTransaction,
+    pub block: Option<BlockIdentifier>,
+    pub state_overrides: Option<StateOverrides>,
+}
+
+impl SendRawTransactionRequest {
+    pub fn parse(params: &Option<Vec<Value>>) -> Result<SendRawTransactionRequest, RpcErr> {
+        let params = expect_params(params, 1)?;
+        let encoded: String = parse_param(params, 0)?;
+        let invalid = |reason: String| RpcErr::BadParams(format!("invalid param 0: {reason}"));
+        let bytes = encoded
+            .strip_prefix("0x")
+            .and_then(|encoded| hex::decode(encoded).ok())
+            .ok_or_else(|| invalid("expected hex encoded bytes".to_string()))?;
+        let request = match bytes.first() {
+            Some(tx_type) if *tx_type == TxType::EIP4844 as u8 => {
+                SendRawTransactionRequest::BlobTransaction(
+                    WrappedEIP4844Transaction::decode_canonical(&bytes)
+                        .map_err(|error| invalid(error.to_string()))?,
+                )
+            }
+            Some(_) => SendRawTransactionRequest::Transaction(
+                Transaction::decode_canonical(&bytes)
+                    .map_err(|error| invalid(error.to_string()))?,
+            ),
+            None => return Err(invalid("empty transaction".to_string())),
+        };
+        Ok(request)
+    }
+}
+
+pub fn send_raw_transaction(
+    request: SendRawTransactionRequest,
+    storage: Store,
+    mempool: Mempool,
+) -> Result<Value, RpcErr> {
+    let result = match request {
+        SendRawTransactionRequest::Transaction(tx) => mempool.add_transaction(tx, &storage),
+        SendRawTransactionRequest::BlobTransaction(wrapped_tx) => {
+            mempool.add_blob_transaction(wrapped_tx, &storage)
+        }
+    };
+    let hash = match result {
+        Ok(hash) => hash,
+        Err(MempoolError::StoreError(error)) => return Err(error.into()),
+        Err(error) => return Err(RpcErr::InvalidTransaction(error.to_string())),
+    };
+    info!("Added transaction {:#x} to the mempool", hash);
+    serde_json::to_value(hash).map_err(|error| RpcErr::Internal(error.to_string()))
+}
+
+impl CallRequest {
+    pub fn parse(params: &Option<Vec<Value>>) -> Result<CallRequest, RpcErr> {
+        let (transaction, block, state_overrides) = parse_simulation_params(params)?;
+        Ok(CallRequest {
+            transaction,
+            block,
+            state_overrides,
+        })
+    }
+}
+
+impl EstimateGasRequest {
+    pub fn parse(params: &Option<Vec<Value>>) -> Result<EstimateGasRequest, RpcErr> {
+        let (transaction, block, state_overrides) = parse_simulation_params(params)?;
+        Ok(EstimateGasRequest {
+            transaction,
+            block,
+            state_overrides,
+        })
+    }
+}
+
+/// Parses the params shared by `eth_call` and `eth_estimateGas`: the transaction, and optionally
+/// the block to run it on and the state overrides to apply before running it
+fn parse_simulation_params(
+    params: &Option<Vec<Value>>,
+) -> Result<
+    (
+        GenericTransaction,
+        Option<BlockIdentifier>,
+        Option<StateOverrides>,
+    ),
+    RpcErr,
+> {
+    let params = params.as_deref().unwrap_or_default();
+    if params.is_empty() || params.len() > 3 {
+        return Err(RpcErr::BadParams(format!(
+            "expected 1 to 3 params, got {}",
+            params.len()
+        )));
+    };
+    // Differentiate between missing and bad optional params
+    let block = match params.get(1) {
+        Some(_) => Some(parse_param(params, 1)?),
+        None => None,
+    };
+    let state_overrides = match params.get(2) {
+        Some(_) => Some(parse_param(params, 2)?),
+        None => None,
+    };
+    Ok((parse_param(params, 0)?, block, state_overrides))
+}
+
+pub fn call(request: &CallRequest, storage: Store) -> Result<Value, RpcErr> {
+    let block = request.block.clone().unwrap_or_default();
+    info!("Requested call on block: {}", block);
+    let header = block.resolve_state_block(&storage)?;
+    let spec_id = simulation_spec_id(&header, &storage)?;
+    let mut state = simulation_state(storage, header.number, &request.state_overrides)?;
+    let mut transaction = request.transaction.clone();
+    // Calls can use up to the block's gas limit if no gas limit is given
+    transaction.gas.get_or_insert(header.gas_limit);
+    let result = simulate_tx_from_generic(&transaction, &header, &mut state, spec_id)?;
+    match result {
+        ExecutionResult::Success { .. } => serde_json::to_value(format!("0x{:x}", result.output()))
+            .map_err(|error| RpcErr::Internal(error.to_string())),
+        ExecutionResult::Revert { output, .. } => Err(RpcErr::Revert { data: output }),
+        ExecutionResult::Halt { reason, .. } => Err(RpcErr::Halt { reason }),
+    }
+}
+
+/// Estimates the gas needed by the transaction by searching for the lowest gas limit that it
+/// can be run with successfully
+/// The search starts from the transaction's gas limit, or the block's if none is given, capped
+/// by the gas that the sender can afford
+pub fn estimate_gas(request: &EstimateGasRequest, storage: Store) -> Result<Value, RpcErr> {
+    let block = request.block.clone().unwrap_or_default();
+    info!("Requested gas estimation on block: {}", block);
+    let header = block.resolve_state_block(&storage)?;
+    let spec_id = simulation_spec_id(&header, &storage)?;
+    let mut transaction = request.transaction.clone();
+    let mut highest_gas_limit = transaction.gas.unwrap_or(header.gas_limit);
+    if transaction.gas_price != 0 {
+        let balance = match request
+            .state_overrides
+            .as_ref()
+            .and_then(|overrides| overrides.get(&transaction.from))
+            .and_then(|account_override| account_override.balance)
+        {
+            Some(balance) => balance,
+            None => storage
+                .get_account_info_at_block(header.number, transaction.from)?
+                .map(|info| info.balance)
+                .unwrap_or_default(),
+        };
+        let allowance =
+            balance.saturating_sub(transaction.value) / U256::from(transaction.gas_price);
+        if allowance < U256::from(highest_gas_limit) {
+            highest_gas_limit = allowance.as_u64();
+        }
+    }
+    let mut state = simulation_state(storage, header.number, &request.state_overrides)?;
+
+    // If the transaction doesn't succeed with the highest gas limit it won't succeed with any other
+    transaction.gas = Some(highest_gas_limit);
+    let result = simulate_tx_from_generic(&transaction, &header, &mut state, spec_id)?;
+    let gas_used = match result {
+        ExecutionResult::Success { gas_used, .. } => gas_used,
+        ExecutionResult::Revert { output, .. } => return Err(RpcErr::Revert { data: output }),
+        ExecutionResult::Halt { reason, .. } if reason.starts_with("OutOfGas") => {
+            return Err(RpcErr::Halt {
+                reason: format!("gas required exceeds allowance ({highest_gas_limit})"),
+            })
+        }
+        ExecutionResult::Halt { reason, .. } => return Err(RpcErr::Halt { reason }),
+    };
+
+    // The gas used doesn't include the refunded gas, so a gas limit lower than it can't be enough
+    let mut lowest_gas_limit = gas_used.saturating_sub(1);
+    while lowest_gas_limit + 1 < highest_gas_limit {
+        let gas_limit = lowest_gas_limit + (highest_gas_limit - lowest_gas_limit) / 2;
+        transaction.gas = Some(gas_limit);
+        match simulate_tx_from_generic(&transaction, &header, &mut state, spec_id) {
+            Ok(result) if result.is_success() => highest_gas_limit = gas_limit,
+            // Either the transaction ran out of gas or the gas limit is below its intrinsic gas
+            Ok(_) | Err(EvmError::Transaction(_)) => lowest_gas_limit = gas_limit,
+            Err(error) => return Err(error.into()),
+        }
+    }
+    serde_json::to_value(format!("{:#x}", highest_gas_limit))
+        .map_err(|error| RpcErr::Internal(error.to_string()))
+}
+
+fn simulation_spec_id(header: &BlockHeader, storage: &Store) -> Result<SpecId, RpcErr> {
+    let chain_config = storage.get_chain_config()?;
+    Ok(spec_id(&chain_config, header.number, header.timestamp))
+}
+
+/// Creates the state transactions are simulated on, with the state overrides already applied
+fn simulation_state(
+    storage: Store,
+    block_number: BlockNumber,
+    state_overrides: &Option<StateOverrides>,
+) -> Result<EvmState, RpcErr> {
+    let mut state = evm_state(storage, block_number);
+    if let Some(state_overrides) = state_overrides {
+        apply_state_overrides(&mut state, state_overrides)?;
+    }
+    Ok(state)
+}
@@ -1,9 +1,18 @@
-use std::{future::IntoFuture, net::SocketAddr};
+use std::{future::IntoFuture, net::SocketAddr, sync::Arc};
 
-use axum::{routing::post, Json, Router};
-use engine::{ExchangeCapabilitiesRequest, NewPayloadV3Request};
+use axum::{
+    extract::DefaultBodyLimit,
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use engine::{
+    ExchangeCapabilitiesRequest, ForkChoiceUpdatedV3, GetBlobsV1Request, GetPayloadV3Request,
+    NewPayloadV3Request,
+};
 use eth::{
-    account::{self, GetBalanceRequest, GetCodeRequest, GetStorageAtRequest},
+    account::{self, GetBalanceRequest, GetCodeRequest, GetProofRequest, GetStorageAtRequest},
     block::{
         self, CreateAccessListRequest, GetBlockByHashRequest, GetBlockByNumberRequest,
         GetBlockReceiptsRequest, GetBlockTransactionCountByNumberRequest,
@@ -11,29 +20,94 @@ use eth::{
         GetTransactionByHashRequest, GetTransactionReceiptRequest,
     },
     client,
+    filter::{self, FilterIdRequest, NewFilterRequest},
+    logs::{self, GetLogsRequest},
+    transaction::{self, CallRequest, EstimateGasRequest, SendRawTransactionRequest},
 };
+use serde::Deserialize;
 use serde_json::Value;
 use tokio::net::TcpListener;
 use tracing::info;
-use utils::{RpcErr, RpcErrorMetadata, RpcErrorResponse, RpcRequest, RpcSuccessResponse};
+use utils::{
+    expect_params, parse_param, RpcErr, RpcErrorMetadata, RpcErrorResponse, RpcRequest,
+    RpcRequestId, RpcSuccessResponse, JSONRPC_VERSION,
+};
 
 mod admin;
+mod authentication;
 mod engine;
 mod eth;
 mod utils;
+mod websocket;
+
+pub use eth::{filter::FilterManager, logs::LogsLimits};
+pub use utils::{RequestLimits, RpcNamespace};
 
 use axum::extract::State;
+use ethereum_rust_blockchain::{mempool::Mempool, notifier::ChainNotifier};
+use ethereum_rust_core::Bytes;
 use ethereum_rust_storage::Store;
 
-pub async fn start_api(http_addr: SocketAddr, authrpc_addr: SocketAddr, storage: Store) {
+/// State shared by the rpc handlers
+#[derive(Clone)]
+pub struct RpcApiContext {
+    pub storage: Store,
+    pub mempool: Mempool,
+    pub logs_limits: LogsLimits,
+    pub filters: FilterManager,
+    pub notifier: ChainNotifier,
+    pub request_limits: RequestLimits,
+}
+
+/// Namespaces served by each listener
+#[derive(Debug, Clone)]
+pub struct ApiNamespaces {
+    pub http: Vec<RpcNamespace>,
+    pub ws: Vec<RpcNamespace>,
+    pub authrpc: Vec<RpcNamespace>,
+}
+
+/// Namespaces enabled for the listener that received a request
+pub(crate) type EnabledNamespaces = Arc<[RpcNamespace]>;
+
+pub async fn start_api(
+    http_addr: SocketAddr,
+    authrpc_addr: SocketAddr,
+    ws_addr: SocketAddr,
+    context: RpcApiContext,
+    jwt_secret: Bytes,
+    namespaces: ApiNamespaces,
+) {
+    // Filters read the blocks of the new chain again after a reorg
+    tokio::spawn(eth::filter::track_removed_blocks(
+        context.filters.clone(),
+        context.notifier.subscribe(),
+    ));
+    // Larger bodies are rejected before being read
+    let body_limit = DefaultBodyLimit::max(context.request_limits.max_body_size);
     let http_router = Router::new()
         .route("/", post(handle_http_request))
-        .with_state(storage.clone());
+        .layer(body_limit)
+        .layer(Extension(EnabledNamespaces::from(namespaces.http)))
+        .with_state(context.clone());
     let http_listener = TcpListener::bind(http_addr).await.unwrap();
 
+    let ws_router = Router::new()
+        .route("/", get(websocket::handle_websocket))
+        .layer(Extension(EnabledNamespaces::from(namespaces.ws)))
+        .with_state(context.clone());
+    let ws_listener = TcpListener::bind(ws_addr).await.unwrap();
+
     let authrpc_router = Router::new()
         .route("/", post(handle_authrpc_request))
-        .with_state(storage);
+        .layer(body_limit)
+        .layer(Extension(EnabledNamespaces::from(namespaces.authrpc)))
+        // Every request must carry a token signed with the secret shared with the consensus client
+        .layer(middleware::from_fn_with_state(
+            jwt_secret,
+            authentication::authenticate,
+        ))
+        .with_state(context);
     let authrpc_listener = TcpListener::bind(authrpc_addr).await.unwrap();
 
     let authrpc_server = axum::serve(authrpc_listener, authrpc_router)
@@ -42,11 +116,15 @@ pub async fn start_api(http_addr: SocketAddr, authrpc_addr: SocketAddr, storage:
     let http_server = axum::serve(http_listener, http_router)
         .with_graceful_shutdown(shutdown_signal())
         .into_future();
+    let ws_server = axum::serve(ws_listener, ws_router)
+        .with_graceful_shutdown(shutdown_signal())
+        .into_future();
 
     info!("Starting HTTP server at {http_addr}");
     info!("Starting Auth-RPC server at {}", authrpc_addr);
+    info!("Starting WebSocket server at {ws_addr}");
 
-    let _ = tokio::try_join!(authrpc_server, http_server)
+    let _ = tokio::try_join!(authrpc_server, http_server, ws_server)
         .inspect_err(|e| info!("Error shutting down servers: {:?}", e));
 }
 
@@ -56,95 +134,273 @@ async fn shutdown_signal() {
         .expect("failed to install Ctrl+C handler");
 }
 
-pub async fn handle_authrpc_request(State(storage): State<Store>, body: String) -> Json<Value> {
-    let req: RpcRequest = serde_json::from_str(&body).unwrap();
-    let res = match map_requests(&req, storage.clone()) {
-        res @ Ok(_) => res,
-        _ => map_internal_requests(&req, storage),
+pub async fn handle_authrpc_request(
+    State(context): State<RpcApiContext>,
+    Extension(namespaces): Extension<EnabledNamespaces>,
+    body: String,
+) -> Response {
+    let response =
+        handle_request_body(
+            &body,
+            context.request_limits,
+            |req| match map_enabled_requests(req, context.clone(), &namespaces) {
+                Err(RpcErr::MethodNotFound) => map_internal_requests(req, context.storage.clone()),
+                res => res,
+            },
+        );
+    http_response(response)
+}
+
+pub async fn handle_http_request(
+    State(context): State<RpcApiContext>,
+    Extension(namespaces): Extension<EnabledNamespaces>,
+    body: String,
+) -> Response {
+    let response = handle_request_body(&body, context.request_limits, |req| {
+        map_enabled_requests(req, context.clone(), &namespaces)
+    });
+    http_response(response)
+}
+
+/// Bodies without anything to answer get an empty response
+fn http_response(response: Option<Value>) -> Response {
+    match response {
+        Some(response) => Json(response).into_response(),
+        None => ().into_response(),
+    }
+}
+
+/// Answers a body holding either a single request or a batch of requests, handling each request
+/// with the given function
+/// Returns `None` if there is nothing to answer, which happens when all requests are notifications
+pub(crate) fn handle_request_body(
+    body: &str,
+    limits: RequestLimits,
+    mut handle: impl FnMut(&RpcRequest) -> Result<Value, RpcErr>,
+) -> Option<Value> {
+    let Ok(body) = serde_json::from_str::<Value>(body) else {
+        return Some(rpc_response(RpcRequestId::Null, Err(RpcErr::ParseError)).0);
     };
-    rpc_response(req.id, res)
+    match body {
+        Value::Array(requests) => {
+            let error = if requests.is_empty() {
+                Some("empty batch".to_string())
+            } else if requests.len() > limits.max_batch_size {
+                Some(format!(
+                    "batch of more than {} requests",
+                    limits.max_batch_size
+                ))
+            } else {
+                None
+            };
+            if let Some(error) = error {
+                return Some(
+                    rpc_response(RpcRequestId::Null, Err(RpcErr::InvalidRequest(error))).0,
+                );
+            }
+            let responses: Vec<Value> = requests
+                .iter()
+                .filter_map(|request| handle_request(request, &mut handle))
+                .collect();
+            // Batches of notifications are not answered
+            (!responses.is_empty()).then_some(Value::Array(responses))
+        }
+        request => handle_request(&request, &mut handle),
+    }
 }
 
-pub async fn handle_http_request(State(storage): State<Store>, body: String) -> Json<Value> {
-    let req: RpcRequest = serde_json::from_str(&body).unwrap();
-    let res = map_requests(&req, storage);
-    rpc_response(req.id, res)
+/// Handles a single request of a body, returning its response unless it is a notification
+fn handle_request(
+    request: &Value,
+    handle: &mut impl FnMut(&RpcRequest) -> Result<Value, RpcErr>,
+) -> Option<Value> {
+    let req = match RpcRequest::deserialize(request) {
+        Ok(req) => req,
+        Err(_) => {
+            // The id is still echoed if it can be read
+            let id = request
+                .get("id")
+                .and_then(|id| RpcRequestId::deserialize(id).ok())
+                .unwrap_or(RpcRequestId::Null);
+            let error = RpcErr::InvalidRequest("invalid request".to_string());
+            return Some(rpc_response(id, Err(error)).0);
+        }
+    };
+    let res = if req.jsonrpc == JSONRPC_VERSION {
+        handle(&req)
+    } else {
+        Err(RpcErr::InvalidRequest(format!(
+            "unsupported jsonrpc version {}",
+            req.jsonrpc
+        )))
+    };
+    // Notifications are handled but not answered
+    Some(rpc_response(req.id?, res).0)
 }
 
 /// Handle requests that can come from either clients or other users
-pub fn map_requests(req: &RpcRequest, storage: Store) -> Result<Value, RpcErr> {
-    match req.method.as_str() {
-        "engine_exchangeCapabilities" => {
-            let capabilities: ExchangeCapabilitiesRequest = req
-                .params
-                .as_ref()
-                .ok_or(RpcErr::BadParams)?
-                .first()
-                .ok_or(RpcErr::BadParams)
-                .and_then(|v| serde_json::from_value(v.clone()).map_err(|_| RpcErr::BadParams))?;
-            engine::exchange_capabilities(&capabilities)
+pub fn map_requests(req: &RpcRequest, context: RpcApiContext) -> Result<Value, RpcErr> {
+    match req.namespace()? {
+        RpcNamespace::Eth => map_eth_requests(req, context),
+        RpcNamespace::Engine => map_engine_requests(req, context),
+        RpcNamespace::Admin => map_admin_requests(req),
+        // No methods are served in these namespaces yet
+        RpcNamespace::Debug | RpcNamespace::Net | RpcNamespace::Web3 | RpcNamespace::Txpool => {
+            Err(RpcErr::MethodNotFound)
         }
+    }
+}
+
+/// Handles the request if its namespace is enabled for the listener it was received by
+pub(crate) fn map_enabled_requests(
+    req: &RpcRequest,
+    context: RpcApiContext,
+    namespaces: &[RpcNamespace],
+) -> Result<Value, RpcErr> {
+    if !namespaces.contains(&req.namespace()?) {
+        return Err(RpcErr::MethodNotFound);
+    }
+    map_requests(req, context)
+}
+
+fn map_eth_requests(req: &RpcRequest, context: RpcApiContext) -> Result<Value, RpcErr> {
+    let RpcApiContext {
+        storage,
+        mempool,
+        logs_limits,
+        filters,
+        ..
+    } = context;
+    match req.method.as_str() {
         "eth_chainId" => client::chain_id(storage),
         "eth_syncing" => client::syncing(),
         "eth_getBlockByNumber" => {
-            let request = GetBlockByNumberRequest::parse(&req.params).ok_or(RpcErr::BadParams)?;
+            let request = GetBlockByNumberRequest::parse(&req.params)?;
             block::get_block_by_number(&request, storage)
         }
         "eth_getBlockByHash" => {
-            let request = GetBlockByHashRequest::parse(&req.params).ok_or(RpcErr::BadParams)?;
+            let request = GetBlockByHashRequest::parse(&req.params)?;
             block::get_block_by_hash(&request, storage)
         }
         "eth_getBalance" => {
-            let request = GetBalanceRequest::parse(&req.params).ok_or(RpcErr::BadParams)?;
+            let request = GetBalanceRequest::parse(&req.params)?;
             account::get_balance(&request, storage)
         }
         "eth_getCode" => {
-            let request = GetCodeRequest::parse(&req.params).ok_or(RpcErr::BadParams)?;
+            let request = GetCodeRequest::parse(&req.params)?;
             account::get_code(&request, storage)
         }
         "eth_getStorageAt" => {
-            let request = GetStorageAtRequest::parse(&req.params).ok_or(RpcErr::BadParams)?;
+            let request = GetStorageAtRequest::parse(&req.params)?;
             account::get_storage_at(&request, storage)
         }
+        "eth_getProof" => {
+            let request = GetProofRequest::parse(&req.params)?;
+            account::get_proof(&request, storage)
+        }
         "eth_getBlockTransactionCountByNumber" => {
-            let request = GetBlockTransactionCountByNumberRequest::parse(&req.params)
-                .ok_or(RpcErr::BadParams)?;
+            let request = GetBlockTransactionCountByNumberRequest::parse(&req.params)?;
             block::get_block_transaction_count_by_number(&request, storage)
         }
         "eth_getTransactionByBlockNumberAndIndex" => {
-            let request = GetTransactionByBlockNumberAndIndexRequest::parse(&req.params)
-                .ok_or(RpcErr::BadParams)?;
+            let request = GetTransactionByBlockNumberAndIndexRequest::parse(&req.params)?;
             block::get_transaction_by_block_number_and_index(&request, storage)
         }
         "eth_getTransactionByBlockHashAndIndex" => {
-            let request = GetTransactionByBlockHashAndIndexRequest::parse(&req.params)
-                .ok_or(RpcErr::BadParams)?;
+            let request = GetTransactionByBlockHashAndIndexRequest::parse(&req.params)?;
             block::get_transaction_by_block_hash_and_index(&request, storage)
         }
         "eth_getBlockReceipts" => {
-            let request = GetBlockReceiptsRequest::parse(&req.params).ok_or(RpcErr::BadParams)?;
+            let request = GetBlockReceiptsRequest::parse(&req.params)?;
             block::get_block_receipts(&request, storage)
         }
         "eth_getTransactionByHash" => {
-            let request =
-                GetTransactionByHashRequest::parse(&req.params).ok_or(RpcErr::BadParams)?;
+            let request = GetTransactionByHashRequest::parse(&req.params)?;
             block::get_transaction_by_hash(&request, storage)
         }
         "eth_getTransactionReceipt" => {
-            let request =
-                GetTransactionReceiptRequest::parse(&req.params).ok_or(RpcErr::BadParams)?;
+            let request = GetTransactionReceiptRequest::parse(&req.params)?;
             block::get_transaction_receipt(&request, storage)
         }
+        "eth_getLogs" => {
+            let request = GetLogsRequest::parse(&req.params)?;
+            logs::get_logs(&request, storage, logs_limits)
+        }
+        "eth_newFilter" => {
+            let request = NewFilterRequest::parse(&req.params)?;
+            filter::new_filter(&request, storage, filters)
+        }
+        "eth_newBlockFilter" => filter::new_block_filter(storage, filters),
+        "eth_newPendingTransactionFilter" => {
+            filter::new_pending_transaction_filter(mempool, filters)
+        }
+        "eth_getFilterChanges" => {
+            let request = FilterIdRequest::parse(&req.params)?;
+            filter::get_filter_changes(&request, storage, mempool, filters, logs_limits)
+        }
+        "eth_getFilterLogs" => {
+            let request = FilterIdRequest::parse(&req.params)?;
+            filter::get_filter_logs(&request, storage, filters, logs_limits)
+        }
+        "eth_uninstallFilter" => {
+            let request = FilterIdRequest::parse(&req.params)?;
+            filter::uninstall_filter(&request, filters)
+        }
         "eth_createAccessList" => {
-            let request = CreateAccessListRequest::parse(&req.params).ok_or(RpcErr::BadParams)?;
+            let request = CreateAccessListRequest::parse(&req.params)?;
             block::create_access_list(&request, storage)
         }
-        "engine_forkchoiceUpdatedV3" => engine::forkchoice_updated_v3(),
+        "eth_call" => {
+            let request = CallRequest::parse(&req.params)?;
+            transaction::call(&request, storage)
+        }
+        "eth_estimateGas" => {
+            let request = EstimateGasRequest::parse(&req.params)?;
+            transaction::estimate_gas(&request, storage)
+        }
+        "eth_sendRawTransaction" => {
+            let request = SendRawTransactionRequest::parse(&req.params)?;
+            transaction::send_raw_transaction(request, storage, mempool)
+        }
+        _ => Err(RpcErr::MethodNotFound),
+    }
+}
+
+fn map_engine_requests(req: &RpcRequest, context: RpcApiContext) -> Result<Value, RpcErr> {
+    let RpcApiContext {
+        storage,
+        mempool,
+        notifier,
+        ..
+    } = context;
+    match req.method.as_str() {
+        "engine_exchangeCapabilities" => {
+            let params = expect_params(&req.params, 1)?;
+            let capabilities: ExchangeCapabilitiesRequest = parse_param(params, 0)?;
+            engine::exchange_capabilities(&capabilities)
+        }
+        "engine_forkchoiceUpdatedV3" => {
+            let request = ForkChoiceUpdatedV3::parse(&req.params)?;
+            engine::forkchoice_updated_v3(request, storage, mempool, notifier)
+        }
+        "engine_getPayloadV3" => {
+            let request = GetPayloadV3Request::parse(&req.params)?;
+            engine::get_payload_v3(request, storage)
+        }
         "engine_newPayloadV3" => {
-            let request =
-                parse_new_payload_v3_request(req.params.as_ref().ok_or(RpcErr::BadParams)?)?;
-            Ok(serde_json::to_value(engine::new_payload_v3(request)?).unwrap())
+            let request = parse_new_payload_v3_request(&req.params)?;
+            engine::new_payload_v3(request, storage, mempool, notifier)
         }
+        "engine_getBlobsV1" => {
+            let request = GetBlobsV1Request::parse(&req.params)?;
+            engine::get_blobs_v1(request, mempool)
+        }
+        _ => Err(RpcErr::MethodNotFound),
+    }
+}
+
+fn map_admin_requests(req: &RpcRequest) -> Result<Value, RpcErr> {
+    match req.method.as_str() {
         "admin_nodeInfo" => admin::node_info(),
         _ => Err(RpcErr::MethodNotFound),
     }
@@ -155,7 +411,7 @@ pub fn map_internal_requests(_req: &RpcRequest, _storage: Store) -> Result<Value
     Err(RpcErr::MethodNotFound)
 }
 
-fn rpc_response<E>(id: i32, res: Result<Value, E>) -> Json<Value>
+fn rpc_response<E>(id: RpcRequestId, res: Result<Value, E>) -> Json<Value>
 where
     E: Into<RpcErrorMetadata>,
 {
@@ -163,7 +419,7 @@ where
         Ok(result) => Json(
             serde_json::to_value(RpcSuccessResponse {
                 id,
-                jsonrpc: "2.0".to_string(),
+                jsonrpc: JSONRPC_VERSION.to_string(),
                 result,
             })
             .unwrap(),
@@ -171,7 +427,7 @@ where
         Err(error) => Json(
             serde_json::to_value(RpcErrorResponse {
                 id,
-                jsonrpc: "2.0".to_string(),
+                jsonrpc: JSONRPC_VERSION.to_string(),
                 error: error.into(),
             })
             .unwrap(),
@@ -179,30 +435,31 @@ where
     }
 }
 
-fn parse_new_payload_v3_request(params: &[Value]) -> Result<NewPayloadV3Request, RpcErr> {
-    if params.len() != 3 {
-        return Err(RpcErr::BadParams);
-    }
-    let payload = serde_json::from_value(params[0].clone()).map_err(|_| RpcErr::BadParams)?;
-    let expected_blob_versioned_hashes =
-        serde_json::from_value(params[1].clone()).map_err(|_| RpcErr::BadParams)?;
-    let parent_beacon_block_root =
-        serde_json::from_value(params[2].clone()).map_err(|_| RpcErr::BadParams)?;
+fn parse_new_payload_v3_request(
+    params: &Option<Vec<Value>>,
+) -> Result<NewPayloadV3Request, RpcErr> {
+    let params = expect_params(params, 3)?;
     Ok(NewPayloadV3Request {
-        payload,
-        expected_blob_versioned_hashes,
-        parent_beacon_block_root,
+        payload: parse_param(params, 0)?,
+        expected_blob_versioned_hashes: parse_param(params, 1)?,
+        parent_beacon_block_root: parse_param(params, 2)?,
     })
 }
 
 #[cfg(test)]
 mod tests {
+    use eth::filter::{DEFAULT_FILTER_TIMEOUT, DEFAULT_MAX_FILTERS};
+    use ethereum_rust_blockchain::mempool::DEFAULT_MEMPOOL_MAX_SIZE;
     use ethereum_rust_core::{
-        types::{code_hash, AccountInfo, BlockHeader},
-        Address, Bytes, U256,
+        rlp::decode::RLPDecode,
+        types::{
+            bloom_from_logs, code_hash, Account, AccountInfo, AccountProof, Block, BlockBody,
+            BlockHeader, ChainConfig, Log, Receipt, Transaction, TxType,
+        },
+        Address, Bytes, H256, U256,
     };
     use ethereum_rust_storage::EngineType;
-    use std::str::FromStr;
+    use std::{str::FromStr, time::Duration};
 
     use super::*;
 
@@ -212,6 +469,42 @@ mod tests {
         serde_json::to_value(serde_json::from_str::<RpcSuccessResponse>(str).unwrap()).unwrap()
     }
 
+    fn example_chain_config() -> ChainConfig {
+        ChainConfig {
+            chain_id: U256::from(3151908),
+            homestead_block: Some(0),
+            eip150_block: Some(0),
+            eip155_block: Some(0),
+            eip158_block: Some(0),
+            byzantium_block: Some(0),
+            constantinople_block: Some(0),
+            petersburg_block: Some(0),
+            istanbul_block: Some(0),
+            berlin_block: Some(0),
+            london_block: Some(0),
+            merge_netsplit_block: Some(0),
+            shanghai_time: Some(0),
+            cancun_time: Some(0),
+            terminal_total_difficulty: Some(U256::zero()),
+            terminal_total_difficulty_passed: true,
+            ..Default::default()
+        }
+    }
+
+    /// Stores an empty block with the given header as the canonical block number 0
+    fn store_genesis_header(storage: &Store, header: BlockHeader) {
+        let block_hash = header.compute_block_hash();
+        storage
+            .add_block(Block {
+                header,
+                body: BlockBody::empty(),
+            })
+            .expect("Failed to write to test DB");
+        storage
+            .set_canonical_block(0, block_hash)
+            .expect("Failed to write to test DB");
+    }
+
     #[test]
     fn create_access_list_simple_transfer() {
         // Create Request
@@ -223,8 +516,9 @@ mod tests {
             Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
         // Values taken from https://github.com/ethereum/execution-apis/blob/main/tests/genesis.json
         // TODO: Replace this initialization with reading and storing genesis block
+        store_genesis_header(&storage, BlockHeader::default());
         storage
-            .add_block_header(0, BlockHeader::default())
+            .set_chain_config(&example_chain_config())
             .expect("Failed to write to test DB");
         let address = Address::from_str("0c2c51a0990aee1d73c1228de158688341557508").unwrap();
         let account_info = AccountInfo {
@@ -235,8 +529,16 @@ mod tests {
             .add_account_info(address, account_info)
             .expect("Failed to write to test DB");
         // Process request
-        let result = map_requests(&request, storage);
-        let response = rpc_response(request.id, result);
+        let context = RpcApiContext {
+            storage,
+            mempool: Mempool::new(DEFAULT_MEMPOOL_MAX_SIZE, ChainNotifier::default()),
+            logs_limits: LogsLimits::default(),
+            filters: FilterManager::default(),
+            notifier: ChainNotifier::default(),
+            request_limits: RequestLimits::default(),
+        };
+        let result = map_requests(&request, context);
+        let response = rpc_response(request.id.unwrap(), result);
         let expected_response = to_rpc_response_success_value(
             r#"{"jsonrpc":"2.0","id":1,"result":{"accessList":[],"gasUsed":"0x5208"}}"#,
         );
@@ -254,8 +556,9 @@ mod tests {
             Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
         // Values taken from https://github.com/ethereum/execution-apis/blob/main/tests/genesis.json
         // TODO: Replace this initialization with reading and storing genesis block
+        store_genesis_header(&storage, BlockHeader::default());
         storage
-            .add_block_header(0, BlockHeader::default())
+            .set_chain_config(&example_chain_config())
             .expect("Failed to write to test DB");
         let address = Address::from_str("0c2c51a0990aee1d73c1228de158688341557508").unwrap();
         let account_info = AccountInfo {
@@ -282,10 +585,19 @@ mod tests {
             .add_account_code(code_hash, code)
             .expect("Failed to write to test DB");
         // Process request
-        let result = map_requests(&request, storage);
-        let response =
-            serde_json::from_value::<RpcSuccessResponse>(rpc_response(request.id, result).0)
-                .expect("Request failed");
+        let context = RpcApiContext {
+            storage,
+            mempool: Mempool::new(DEFAULT_MEMPOOL_MAX_SIZE, ChainNotifier::default()),
+            logs_limits: LogsLimits::default(),
+            filters: FilterManager::default(),
+            notifier: ChainNotifier::default(),
+            request_limits: RequestLimits::default(),
+        };
+        let result = map_requests(&request, context);
+        let response = serde_json::from_value::<RpcSuccessResponse>(
+            rpc_response(request.id.unwrap(), result).0,
+        )
+        .expect("Request failed");
         let expected_response_string = r#"{"jsonrpc":"2.0","id":1,"result":{"accessList":[{"address":"0x7dcd17433742f4c0ca53122ab541d0ba67fc27df","storageKeys":["0x0000000000000000000000000000000000000000000000000000000000000000","0x13a08e3cd39a1bc7bf9103f63f83273cced2beada9f723945176d6b983c65bd2"]}],"gasUsed":"0xca3c"}}"#;
         let expected_response =
             serde_json::from_str::<RpcSuccessResponse>(expected_response_string).unwrap();
@@ -297,4 +609,656 @@ mod tests {
             expected_response.result["accessList"]
         )
     }
+
+    /// Creates a store with a funded sender account on top of a block with a 30M gas limit
+    fn simulation_storage() -> Store {
+        let storage =
+            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        let header = BlockHeader {
+            gas_limit: 30_000_000,
+            ..Default::default()
+        };
+        store_genesis_header(&storage, header);
+        storage
+            .update_latest_block_number(0)
+            .expect("Failed to write to test DB");
+        storage
+            .set_chain_config(&example_chain_config())
+            .expect("Failed to write to test DB");
+        let address = Address::from_str("0c2c51a0990aee1d73c1228de158688341557508").unwrap();
+        let account_info = AccountInfo {
+            balance: U256::from_str_radix("c097ce7bc90715b34b9f1000000000", 16).unwrap(),
+            ..Default::default()
+        };
+        storage
+            .add_account_info(address, account_info)
+            .expect("Failed to write to test DB");
+        storage
+    }
+
+    fn process_simulation_request(body: &str) -> Json<Value> {
+        let request: RpcRequest = serde_json::from_str(body).unwrap();
+        let context = RpcApiContext {
+            storage: simulation_storage(),
+            mempool: Mempool::new(DEFAULT_MEMPOOL_MAX_SIZE, ChainNotifier::default()),
+            logs_limits: LogsLimits::default(),
+            filters: FilterManager::default(),
+            notifier: ChainNotifier::default(),
+            request_limits: RequestLimits::default(),
+        };
+        let result = map_requests(&request, context);
+        rpc_response(request.id.unwrap(), result)
+    }
+
+    #[test]
+    fn estimate_gas_simple_transfer() {
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_estimateGas","params":[{"from":"0x0c2c51a0990aee1d73c1228de158688341557508","to":"0x0100000000000000000000000000000000000000","value":"0xa"},"latest"]}"#;
+        let response = process_simulation_request(body);
+        let expected_response =
+            to_rpc_response_success_value(r#"{"jsonrpc":"2.0","id":1,"result":"0x5208"}"#);
+        assert_eq!(response.to_string(), expected_response.to_string());
+    }
+
+    #[test]
+    fn call_with_code_override() {
+        // The overriden code stores 0x2a in memory and returns it
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_call","params":[{"from":"0x0c2c51a0990aee1d73c1228de158688341557508","to":"0x0100000000000000000000000000000000000000"},"latest",{"0x0100000000000000000000000000000000000000":{"code":"0x602a60005260206000f3"}}]}"#;
+        let response = process_simulation_request(body);
+        let expected_response = to_rpc_response_success_value(
+            r#"{"jsonrpc":"2.0","id":1,"result":"0x000000000000000000000000000000000000000000000000000000000000002a"}"#,
+        );
+        assert_eq!(response.to_string(), expected_response.to_string());
+    }
+
+    #[test]
+    fn call_reverted() {
+        // The overriden code reverts with `Error("boom")`
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_call","params":[{"from":"0x0c2c51a0990aee1d73c1228de158688341557508","to":"0x0100000000000000000000000000000000000000"},"latest",{"0x0100000000000000000000000000000000000000":{"code":"0x7f08c379a0000000000000000000000000000000000000000000000000000000006000527f00000020000000000000000000000000000000000000000000000000000000006020527f00000004626f6f6d00000000000000000000000000000000000000000000000060405260646000fd"}}]}"#;
+        let response = process_simulation_request(body);
+        let error = &response.0["error"];
+        assert_eq!(error["code"], 3);
+        assert_eq!(error["message"], "execution reverted: boom");
+        assert_eq!(error["data"], "0x08c379a000000000000000000000000000000000000000000000000000000000000000200000000000000000000000000000000000000000000000000000000000000004626f6f6d00000000000000000000000000000000000000000000000000000000");
+    }
+
+    #[test]
+    fn errors_detail_bad_params_and_missing_blocks() {
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_getBalance","params":["0x0c2c51a0990aee1d73c1228de158688341557508"]}"#;
+        let error = &process_simulation_request(body).0["error"];
+        assert_eq!(error["code"], -32602);
+        assert_eq!(error["message"], "Invalid params: expected 2 params, got 1");
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_getBalance","params":["0x0c2c51a0990aee1d73c1228de158688341557508","newest"]}"#;
+        let error = &process_simulation_request(body).0["error"];
+        assert_eq!(error["code"], -32602);
+        assert!(error["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid params: invalid param 1"));
+        // Only the genesis block is stored, and no block has been finalized yet
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_getBalance","params":["0x0c2c51a0990aee1d73c1228de158688341557508","0x5"]}"#;
+        let error = &process_simulation_request(body).0["error"];
+        assert_eq!(error["code"], -32000);
+        assert_eq!(error["message"], "header not found");
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_getBalance","params":["0x0c2c51a0990aee1d73c1228de158688341557508","finalized"]}"#;
+        let error = &process_simulation_request(body).0["error"];
+        assert_eq!(error["code"], -39001);
+        assert_eq!(error["message"], "Unknown block");
+        // Simulations fail the same way
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_call","params":[{"from":"0x0c2c51a0990aee1d73c1228de158688341557508","to":"0x0100000000000000000000000000000000000000"},"0x5"]}"#;
+        let error = &process_simulation_request(body).0["error"];
+        assert_eq!(error["code"], -32000);
+        assert_eq!(error["message"], "header not found");
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_estimateGas","params":[{"from":"0x0c2c51a0990aee1d73c1228de158688341557508","to":"0x0100000000000000000000000000000000000000"},"finalized"]}"#;
+        let error = &process_simulation_request(body).0["error"];
+        assert_eq!(error["code"], -39001);
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_call","params":[{"from":"0x0c2c51a0990aee1d73c1228de158688341557508"},"newest"]}"#;
+        let error = &process_simulation_request(body).0["error"];
+        assert!(error["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid params: invalid param 1"));
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_getFilterChanges","params":["1"]}"#;
+        let error = &process_simulation_request(body).0["error"];
+        assert_eq!(
+            error["message"],
+            "Invalid params: invalid param 0: expected a hex quantity, got 1"
+        );
+    }
+
+    #[test]
+    fn get_proof_verifies_against_state_root() {
+        let mut storage =
+            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        let address = Address::from_str("0c2c51a0990aee1d73c1228de158688341557508").unwrap();
+        let account = Account {
+            info: AccountInfo {
+                balance: U256::from(10),
+                nonce: 1,
+                ..Default::default()
+            },
+            code: Bytes::new(),
+            storage: [(H256::from_low_u64_be(1), H256::from_low_u64_be(42))].into(),
+        };
+        storage
+            .add_account(address, account)
+            .expect("Failed to write to test DB");
+        let header = BlockHeader {
+            state_root: storage.get_state_root().unwrap(),
+            ..Default::default()
+        };
+        store_genesis_header(&storage, header.clone());
+        storage
+            .update_latest_block_number(0)
+            .expect("Failed to write to test DB");
+
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_getProof","params":["0x0c2c51a0990aee1d73c1228de158688341557508",["0x0000000000000000000000000000000000000000000000000000000000000001"],"latest"]}"#;
+        let request: RpcRequest = serde_json::from_str(body).unwrap();
+        let context = RpcApiContext {
+            storage,
+            mempool: Mempool::new(DEFAULT_MEMPOOL_MAX_SIZE, ChainNotifier::default()),
+            logs_limits: LogsLimits::default(),
+            filters: FilterManager::default(),
+            notifier: ChainNotifier::default(),
+            request_limits: RequestLimits::default(),
+        };
+        let proof: AccountProof = serde_json::from_value(map_requests(&request, context).unwrap())
+            .expect("Invalid proof");
+        assert_eq!(proof.balance, U256::from(10));
+        assert_eq!(proof.storage_proof[0].value, U256::from(42));
+        proof.verify(header.state_root).unwrap();
+    }
+
+    #[test]
+    fn get_logs_filters_by_address_and_topics() {
+        let storage =
+            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        store_genesis_header(&storage, BlockHeader::default());
+        let (address_a, address_b) = (Address::from_low_u64_be(1), Address::from_low_u64_be(2));
+        let (topic_a, topic_b) = (H256::from_low_u64_be(3), H256::from_low_u64_be(4));
+        let new_log = |address, topic| Log {
+            address,
+            topics: vec![topic],
+            data: Bytes::new(),
+        };
+        let transaction = Transaction::decode(&hex::decode("f86d80843baa0c4082f618946177843db3138ae69679a54b95cf345ed759450d870aa87bee538000808360306ba0151ccc02146b9b11adf516e6787b59acae3e76544fdcd75e77e67c6b598ce65da064c5dd5aae2fbb535830ebbdad0234975cd7ece3562013b63ea18cc0df6c97d4").unwrap()).unwrap();
+        // Block 1 has logs from both addresses, block 2 from the first one with another topic and
+        // block 3 has no logs
+        let block_logs = [
+            vec![new_log(address_a, topic_a), new_log(address_b, topic_a)],
+            vec![new_log(address_a, topic_b)],
+            vec![],
+        ];
+        let mut block_hashes = Vec::new();
+        for (number, logs) in (1..).zip(block_logs) {
+            let block = Block {
+                header: BlockHeader {
+                    number,
+                    logs_bloom: bloom_from_logs(&logs),
+                    ..Default::default()
+                },
+                body: BlockBody {
+                    transactions: vec![transaction.clone()],
+                    ..BlockBody::empty()
+                },
+            };
+            block_hashes.push(block.header.compute_block_hash());
+            let receipt = Receipt::new(TxType::Legacy, true, 21000, bloom_from_logs(&logs), logs);
+            storage
+                .add_executed_block(block, vec![receipt], &[])
+                .expect("Failed to write to test DB");
+        }
+        let get_logs = |params: &str, logs_limits: LogsLimits| {
+            let body =
+                format!(r#"{{"jsonrpc":"2.0","id":1,"method":"eth_getLogs","params":[{params}]}}"#);
+            let context = RpcApiContext {
+                storage: storage.clone(),
+                mempool: Mempool::new(DEFAULT_MEMPOOL_MAX_SIZE, ChainNotifier::default()),
+                logs_limits,
+                filters: FilterManager::default(),
+                notifier: ChainNotifier::default(),
+                request_limits: RequestLimits::default(),
+            };
+            handle_request_body(&body, context.request_limits, |req| {
+                map_requests(req, context.clone())
+            })
+            .unwrap()
+        };
+
+        let response = get_logs(
+            r#"{"fromBlock":"0x0","address":"0x0000000000000000000000000000000000000001"}"#,
+            LogsLimits::default(),
+        );
+        let logs = response["result"].as_array().unwrap();
+        assert_eq!(logs.len(), 2);
+        assert_eq!(logs[0]["blockNumber"], "0x1");
+        assert_eq!(logs[0]["blockHash"], format!("{:#x}", block_hashes[0]));
+        assert_eq!(
+            logs[0]["transactionHash"],
+            format!("{:#x}", transaction.compute_hash())
+        );
+        assert_eq!(logs[0]["transactionIndex"], "0x0");
+        assert_eq!(logs[0]["logIndex"], "0x0");
+        assert_eq!(logs[0]["removed"], false);
+        assert_eq!(logs[1]["blockNumber"], "0x2");
+
+        let response = get_logs(
+            &format!(r#"{{"fromBlock":"0x1","toBlock":"0x3","topics":[["{topic_b:#x}"]]}}"#),
+            LogsLimits::default(),
+        );
+        let logs = response["result"].as_array().unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0]["blockNumber"], "0x2");
+
+        // The log index counts all the logs of the block
+        let response = get_logs(
+            &format!(
+                r#"{{"blockHash":"{:#x}","address":["0x0000000000000000000000000000000000000002"]}}"#,
+                block_hashes[0]
+            ),
+            LogsLimits::default(),
+        );
+        let logs = response["result"].as_array().unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0]["logIndex"], "0x1");
+
+        let response = get_logs(
+            r#"{"fromBlock":"0x0","toBlock":"latest"}"#,
+            LogsLimits {
+                max_block_range: 2,
+                ..Default::default()
+            },
+        );
+        assert_eq!(response["error"]["code"], -32005);
+        let response = get_logs(
+            r#"{"fromBlock":"0x0","toBlock":"latest"}"#,
+            LogsLimits {
+                max_results: 2,
+                ..Default::default()
+            },
+        );
+        assert_eq!(response["error"]["code"], -32005);
+    }
+
+    #[test]
+    fn filters_return_changes_since_last_poll() {
+        let storage =
+            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        store_genesis_header(&storage, BlockHeader::default());
+        storage
+            .update_latest_block_number(0)
+            .expect("Failed to write to test DB");
+        let context = RpcApiContext {
+            storage: storage.clone(),
+            mempool: Mempool::new(DEFAULT_MEMPOOL_MAX_SIZE, ChainNotifier::default()),
+            logs_limits: LogsLimits::default(),
+            filters: FilterManager::default(),
+            notifier: ChainNotifier::default(),
+            request_limits: RequestLimits::default(),
+        };
+        let call = |context: &RpcApiContext, method: &str, params: &str| {
+            let body =
+                format!(r#"{{"jsonrpc":"2.0","id":1,"method":"{method}","params":[{params}]}}"#);
+            handle_request_body(&body, context.request_limits, |req| {
+                map_requests(req, context.clone())
+            })
+            .unwrap()
+        };
+        let block_filter = call(&context, "eth_newBlockFilter", "")["result"].clone();
+        let logs_filter = call(
+            &context,
+            "eth_newFilter",
+            r#"{"address":"0x0000000000000000000000000000000000000001"}"#,
+        )["result"]
+            .clone();
+        assert_ne!(block_filter, logs_filter);
+
+        // Adds a block with a log emitted by the filtered address
+        let add_block_with_log = |number| {
+            let logs = vec![Log {
+                address: Address::from_low_u64_be(1),
+                topics: vec![],
+                data: Bytes::new(),
+            }];
+            let transaction = Transaction::decode(&hex::decode("f86d80843baa0c4082f618946177843db3138ae69679a54b95cf345ed759450d870aa87bee538000808360306ba0151ccc02146b9b11adf516e6787b59acae3e76544fdcd75e77e67c6b598ce65da064c5dd5aae2fbb535830ebbdad0234975cd7ece3562013b63ea18cc0df6c97d4").unwrap()).unwrap();
+            let block = Block {
+                header: BlockHeader {
+                    number,
+                    logs_bloom: bloom_from_logs(&logs),
+                    ..Default::default()
+                },
+                body: BlockBody {
+                    transactions: vec![transaction],
+                    ..BlockBody::empty()
+                },
+            };
+            let block_hash = block.header.compute_block_hash();
+            let receipt = Receipt::new(TxType::Legacy, true, 21000, bloom_from_logs(&logs), logs);
+            storage
+                .add_executed_block(block, vec![receipt], &[])
+                .expect("Failed to write to test DB");
+            block_hash
+        };
+        let block_hash = add_block_with_log(1);
+
+        let changes = call(&context, "eth_getFilterChanges", &block_filter.to_string());
+        assert_eq!(
+            changes["result"],
+            serde_json::json!([format!("{block_hash:#x}")])
+        );
+        let changes = call(&context, "eth_getFilterChanges", &logs_filter.to_string());
+        assert_eq!(changes["result"].as_array().unwrap().len(), 1);
+        assert_eq!(changes["result"][0]["blockNumber"], "0x1");
+        // Changes are only returned once, while the filter's logs can be queried again
+        let changes = call(&context, "eth_getFilterChanges", &block_filter.to_string());
+        assert_eq!(changes["result"], serde_json::json!([]));
+        let changes = call(&context, "eth_getFilterChanges", &logs_filter.to_string());
+        assert_eq!(changes["result"], serde_json::json!([]));
+        let filter_logs = call(&context, "eth_getFilterLogs", &logs_filter.to_string());
+        assert_eq!(filter_logs["result"].as_array().unwrap().len(), 1);
+
+        // Polls with more logs than the results limit return them over several polls
+        add_block_with_log(2);
+        add_block_with_log(3);
+        let limited_context = RpcApiContext {
+            logs_limits: LogsLimits {
+                max_results: 1,
+                ..Default::default()
+            },
+            ..context.clone()
+        };
+        for block_number in ["0x2", "0x3"] {
+            let changes = call(
+                &limited_context,
+                "eth_getFilterChanges",
+                &logs_filter.to_string(),
+            );
+            assert_eq!(changes["result"].as_array().unwrap().len(), 1);
+            assert_eq!(changes["result"][0]["blockNumber"], block_number);
+        }
+        let changes = call(&context, "eth_getFilterChanges", &logs_filter.to_string());
+        assert_eq!(changes["result"], serde_json::json!([]));
+
+        // Blocks removed by a reorg are read again, as the new chain replaces them
+        context.filters.remove_block(3);
+        let changes = call(&context, "eth_getFilterChanges", &logs_filter.to_string());
+        assert_eq!(changes["result"].as_array().unwrap().len(), 1);
+        assert_eq!(changes["result"][0]["blockNumber"], "0x3");
+
+        let uninstalled = call(&context, "eth_uninstallFilter", &block_filter.to_string());
+        assert_eq!(uninstalled["result"], true);
+        let uninstalled = call(&context, "eth_uninstallFilter", &block_filter.to_string());
+        assert_eq!(uninstalled["result"], false);
+        let changes = call(&context, "eth_getFilterChanges", &block_filter.to_string());
+        assert_eq!(changes["error"]["code"], -32000);
+
+        // Filters that are not polled in time expire
+        let context = RpcApiContext {
+            filters: FilterManager::new(Duration::from_millis(1), DEFAULT_MAX_FILTERS),
+            ..context
+        };
+        let block_filter = call(&context, "eth_newBlockFilter", "")["result"].clone();
+        std::thread::sleep(Duration::from_millis(10));
+        let changes = call(&context, "eth_getFilterChanges", &block_filter.to_string());
+        assert_eq!(changes["error"]["code"], -32000);
+
+        // New filters are rejected while the maximum number of them is installed
+        let context = RpcApiContext {
+            filters: FilterManager::new(DEFAULT_FILTER_TIMEOUT, 1),
+            ..context
+        };
+        let block_filter = call(&context, "eth_newBlockFilter", "")["result"].clone();
+        let rejected = call(&context, "eth_newPendingTransactionFilter", "");
+        assert_eq!(rejected["error"]["code"], -32005);
+        call(&context, "eth_uninstallFilter", &block_filter.to_string());
+        let accepted = call(&context, "eth_newPendingTransactionFilter", "");
+        assert!(accepted["result"].is_string());
+    }
+
+    #[test]
+    fn chain_events_are_notified_to_subscriptions() {
+        use eth::subscription::{notifications, SubscribeRequest};
+        use ethereum_rust_blockchain::notifier::ChainEvent;
+        use std::collections::HashMap;
+
+        let storage =
+            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        store_genesis_header(&storage, BlockHeader::default());
+        storage
+            .update_latest_block_number(0)
+            .expect("Failed to write to test DB");
+        let subscribe = |params: &str| {
+            let params = Some(serde_json::from_str(params).unwrap());
+            SubscribeRequest::parse(&params).unwrap().kind
+        };
+        let mut subscriptions = HashMap::new();
+        subscriptions.insert(1, subscribe(r#"["newHeads"]"#));
+        subscriptions.insert(
+            2,
+            subscribe(r#"["logs",{"address":"0x0000000000000000000000000000000000000001"}]"#),
+        );
+        subscriptions.insert(
+            3,
+            subscribe(r#"["logs",{"address":"0x0000000000000000000000000000000000000002"}]"#),
+        );
+        subscriptions.insert(4, subscribe(r#"["newPendingTransactions"]"#));
+        // Logs subscriptions can't select the blocks
+        let params = Some(serde_json::from_str(r#"["logs",{"fromBlock":"0x0"}]"#).unwrap());
+        assert!(SubscribeRequest::parse(&params).is_err());
+
+        // Two blocks are imported, the first one with a log emitted by the first address
+        let logs = vec![Log {
+            address: Address::from_low_u64_be(1),
+            topics: vec![],
+            data: Bytes::new(),
+        }];
+        let transaction = Transaction::decode(&hex::decode("f86d80843baa0c4082f618946177843db3138ae69679a54b95cf345ed759450d870aa87bee538000808360306ba0151ccc02146b9b11adf516e6787b59acae3e76544fdcd75e77e67c6b598ce65da064c5dd5aae2fbb535830ebbdad0234975cd7ece3562013b63ea18cc0df6c97d4").unwrap()).unwrap();
+        let mut blocks = Vec::new();
+        for (number, logs) in (1..).zip([logs, vec![]]) {
+            let block = Block {
+                header: BlockHeader {
+                    number,
+                    logs_bloom: bloom_from_logs(&logs),
+                    ..Default::default()
+                },
+                body: BlockBody {
+                    transactions: vec![transaction.clone()],
+                    ..BlockBody::empty()
+                },
+            };
+            blocks.push((block.header.compute_block_hash(), block.header.clone()));
+            let receipt = Receipt::new(TxType::Legacy, true, 21000, bloom_from_logs(&logs), logs);
+            storage
+                .add_executed_block(block, vec![receipt], &[])
+                .expect("Failed to write to test DB");
+        }
+
+        let (block_hash, header) = blocks[0].clone();
+        let event = ChainEvent::NewHead(block_hash, header.clone());
+        let mut messages = notifications(&event, &subscriptions, &storage);
+        messages.sort_by_key(|message| message["params"]["subscription"].to_string());
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0]["method"], "eth_subscription");
+        assert_eq!(messages[0]["params"]["subscription"], "0x1");
+        assert_eq!(
+            messages[0]["params"]["result"]["hash"],
+            format!("{:#x}", block_hash)
+        );
+        assert_eq!(messages[0]["params"]["result"]["number"], "0x1");
+        assert_eq!(messages[1]["params"]["subscription"], "0x2");
+        assert_eq!(messages[1]["params"]["result"]["blockNumber"], "0x1");
+        assert_eq!(messages[1]["params"]["result"]["removed"], false);
+
+        // The logs of a block removed by a reorg are notified as removed, but not its header
+        let event = ChainEvent::RemovedBlock(block_hash, header);
+        let messages = notifications(&event, &subscriptions, &storage);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0]["params"]["subscription"], "0x2");
+        assert_eq!(messages[0]["params"]["result"]["removed"], true);
+
+        let (block_hash, header) = blocks[1].clone();
+        let event = ChainEvent::NewHead(block_hash, header);
+        let messages = notifications(&event, &subscriptions, &storage);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0]["params"]["subscription"], "0x1");
+
+        let transaction_hash = transaction.compute_hash();
+        let event = ChainEvent::NewPendingTransaction(transaction_hash);
+        let messages = notifications(&event, &subscriptions, &storage);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0]["params"]["subscription"], "0x4");
+        assert_eq!(
+            messages[0]["params"]["result"],
+            format!("{:#x}", transaction_hash)
+        );
+    }
+
+    #[test]
+    fn handle_batches_notifications_and_invalid_requests() {
+        let storage =
+            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        storage
+            .set_chain_config(&example_chain_config())
+            .expect("Failed to write to test DB");
+        let context = RpcApiContext {
+            storage,
+            mempool: Mempool::new(DEFAULT_MEMPOOL_MAX_SIZE, ChainNotifier::default()),
+            logs_limits: LogsLimits::default(),
+            filters: FilterManager::default(),
+            notifier: ChainNotifier::default(),
+            request_limits: RequestLimits {
+                max_batch_size: 3,
+                ..Default::default()
+            },
+        };
+        let handle = |body: &str| {
+            handle_request_body(body, context.request_limits, |req| {
+                map_requests(req, context.clone())
+            })
+        };
+
+        // String ids are echoed, notifications and invalid requests are answered in order
+        let response = handle(
+            r#"[{"jsonrpc":"2.0","id":"a","method":"eth_chainId"},{"jsonrpc":"2.0","method":"eth_chainId"},{"jsonrpc":"2.0","id":7,"method":1}]"#,
+        )
+        .unwrap();
+        let responses = response.as_array().unwrap();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["id"], "a");
+        assert_eq!(responses[0]["result"], "0x301824");
+        assert_eq!(responses[1]["id"], 7);
+        assert_eq!(responses[1]["error"]["code"], -32600);
+
+        // Numeric ids are echoed unchanged, whatever their range
+        let response =
+            handle(r#"{"jsonrpc":"2.0","id":18446744073709551615,"method":"eth_chainId"}"#)
+                .unwrap();
+        assert_eq!(response["id"], u64::MAX);
+        let response = handle(r#"{"jsonrpc":"2.0","id":1.5,"method":"eth_chainId"}"#).unwrap();
+        assert_eq!(response["id"], 1.5);
+        assert_eq!(response["result"], "0x301824");
+
+        let response = handle(r#"{"jsonrpc":"2.0","id":null,"method":"eth_chainId"}"#).unwrap();
+        assert_eq!(response["id"], Value::Null);
+        assert_eq!(response["result"], "0x301824");
+        let response = handle(r#"{"jsonrpc":"1.0","id":1,"method":"eth_chainId"}"#).unwrap();
+        assert_eq!(response["error"]["code"], -32600);
+        // Notifications alone get no response
+        assert!(handle(r#"{"jsonrpc":"2.0","method":"eth_chainId"}"#).is_none());
+        assert!(handle(r#"[{"jsonrpc":"2.0","method":"eth_chainId"}]"#).is_none());
+
+        let response = handle(r#"{"jsonrpc":"2.0","id":1,"#).unwrap();
+        assert_eq!(response["id"], Value::Null);
+        assert_eq!(response["error"]["code"], -32700);
+        let response = handle("[]").unwrap();
+        assert_eq!(response["error"]["code"], -32600);
+        let request = r#"{"jsonrpc":"2.0","id":1,"method":"eth_chainId"}"#;
+        let response = handle(&format!("[{request},{request},{request},{request}]")).unwrap();
+        assert_eq!(response["id"], Value::Null);
+        assert_eq!(response["error"]["code"], -32600);
+    }
+
+    #[test]
+    fn authentication_requires_recent_tokens_signed_with_the_secret() {
+        use authentication::{validate_jwt, AuthenticationError};
+        use jsonwebtoken::{encode, EncodingKey, Header};
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        let secret = [7; 32];
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let token = |secret: &[u8], iat: u64| {
+            encode(
+                &Header::default(),
+                &serde_json::json!({ "iat": iat }),
+                &EncodingKey::from_secret(secret),
+            )
+            .unwrap()
+        };
+
+        assert!(validate_jwt(&token(&secret, now), &secret).is_ok());
+        assert!(validate_jwt(&token(&secret, now - 30), &secret).is_ok());
+        assert!(matches!(
+            validate_jwt(&token(&[8; 32], now), &secret),
+            Err(AuthenticationError::InvalidToken)
+        ));
+        assert!(matches!(
+            validate_jwt(&token(&secret, now - 120), &secret),
+            Err(AuthenticationError::InvalidIssuedAtClaim)
+        ));
+        assert!(matches!(
+            validate_jwt(&token(&secret, now + 120), &secret),
+            Err(AuthenticationError::InvalidIssuedAtClaim)
+        ));
+        assert!(matches!(
+            validate_jwt("not a token", &secret),
+            Err(AuthenticationError::InvalidToken)
+        ));
+    }
+
+    #[test]
+    fn listeners_only_serve_their_enabled_namespaces() {
+        let storage =
+            Store::new("temp.db", EngineType::InMemory).expect("Failed to create test DB");
+        storage
+            .set_chain_config(&example_chain_config())
+            .expect("Failed to write to test DB");
+        let context = RpcApiContext {
+            storage,
+            mempool: Mempool::new(DEFAULT_MEMPOOL_MAX_SIZE, ChainNotifier::default()),
+            logs_limits: LogsLimits::default(),
+            filters: FilterManager::default(),
+            notifier: ChainNotifier::default(),
+            request_limits: RequestLimits::default(),
+        };
+        let call = |method: &str, namespaces: &[RpcNamespace]| {
+            let body = format!(r#"{{"jsonrpc":"2.0","id":1,"method":"{method}","params":[[]]}}"#);
+            let request: RpcRequest = serde_json::from_str(&body).unwrap();
+            map_enabled_requests(&request, context.clone(), namespaces)
+        };
+        let http_namespaces = [RpcNamespace::Eth, RpcNamespace::Net, RpcNamespace::Web3];
+        let authrpc_namespaces = [RpcNamespace::Eth, RpcNamespace::Engine];
+
+        assert!(call("eth_chainId", &http_namespaces).is_ok());
+        assert!(call("eth_chainId", &authrpc_namespaces).is_ok());
+        assert!(matches!(
+            call("engine_exchangeCapabilities", &http_namespaces),
+            Err(RpcErr::MethodNotFound)
+        ));
+        assert!(call("engine_exchangeCapabilities", &authrpc_namespaces).is_ok());
+        assert!(matches!(
+            call("admin_nodeInfo", &http_namespaces),
+            Err(RpcErr::MethodNotFound)
+        ));
+        assert!(matches!(
+            call("unknown_method", &http_namespaces),
+            Err(RpcErr::MethodNotFound)
+        ));
+        assert!("eth,engine"
+            .split(',')
+            .map(str::parse::<RpcNamespace>)
+            .eq([Ok(RpcNamespace::Eth), Ok(RpcNamespace::Engine)]));
+        assert!("personal".parse::<RpcNamespace>().is_err());
+    }
 }
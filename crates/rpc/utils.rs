@@ -1,65 +1,351 @@
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use std::str::FromStr;
+
+use ethereum_rust_core::{Bytes, U256};
+use ethereum_rust_evm::EvmError;
+use ethereum_rust_storage::error::StoreError;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+use serde_json::{Number, Value};
+
+/// Version of the protocol, which every request and response must carry
+pub const JSONRPC_VERSION: &str = "2.0";
+/// Maximum number of requests a batch can hold by default
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 1000;
+/// Maximum size in bytes of a request body by default
+pub const DEFAULT_MAX_BODY_SIZE: usize = 5 * 1024 * 1024;
+
+/// Limits applied to the requests received, so that a single body can't make the node process
+/// an unbounded amount of requests
+#[derive(Debug, Clone, Copy)]
+pub struct RequestLimits {
+    pub max_batch_size: usize,
+    pub max_body_size: usize,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+        }
+    }
+}
 
 pub enum RpcErr {
+    /// The request body is not valid JSON
+    ParseError,
+    /// The request is not a valid request object, or the batch holding it is not valid
+    InvalidRequest(String),
     MethodNotFound,
-    BadParams,
+    /// The params couldn't be read, the reason tells which one is wrong
+    BadParams(String),
     UnsuportedFork,
-    Internal,
-    Vm,
+    /// An unexpected failure, such as a database error
+    Internal(String),
+    /// The evm failed for a reason unrelated to the transaction being executed
+    Vm(String),
+    InvalidForkChoiceState,
+    InvalidPayloadAttributes,
+    UnknownPayload,
+    TooLargeRequest,
+    /// A logs query exceeded the configured limits
+    LogsLimitExceeded(String),
+    /// The filter was never installed, was uninstalled or expired
+    FilterNotFound,
+    /// The maximum number of filters is installed
+    TooManyFilters,
+    /// The block identified by a tag or hash is not known
+    UnknownBlock,
+    /// The block whose state is queried is not part of the chain
+    HeaderNotFound,
+    /// The state of the block was pruned or is incomplete
+    MissingState(String),
+    InvalidTransaction(String),
+    /// The execution of a call reverted, the revert data is returned along with the error
+    Revert {
+        data: Bytes,
+    },
+    /// The execution of a call halted, spending all of its gas
+    Halt {
+        reason: String,
+    },
 }
 
 impl From<RpcErr> for RpcErrorMetadata {
     fn from(value: RpcErr) -> Self {
         match value {
+            RpcErr::ParseError => RpcErrorMetadata {
+                code: -32700,
+                message: "Parse error".to_string(),
+                data: None,
+            },
+            RpcErr::InvalidRequest(reason) => RpcErrorMetadata {
+                code: -32600,
+                message: reason,
+                data: None,
+            },
             RpcErr::MethodNotFound => RpcErrorMetadata {
                 code: -32601,
                 message: "Method not found".to_string(),
+                data: None,
             },
-            RpcErr::BadParams => RpcErrorMetadata {
+            RpcErr::BadParams(reason) => RpcErrorMetadata {
                 code: -32602,
-                message: "Invalid params".to_string(),
+                message: format!("Invalid params: {reason}"),
+                data: None,
             },
             RpcErr::UnsuportedFork => RpcErrorMetadata {
                 code: -38005,
                 message: "Unsupported fork".to_string(),
+                data: None,
             },
-            RpcErr::Internal => RpcErrorMetadata {
+            RpcErr::Internal(reason) => RpcErrorMetadata {
                 code: -32603,
-                message: "Internal Error".to_string(),
+                message: format!("Internal Error: {reason}"),
+                data: None,
             },
-            RpcErr::Vm => RpcErrorMetadata {
+            RpcErr::Vm(reason) => RpcErrorMetadata {
                 code: -32015,
-                message: "Vm execution error".to_string(),
+                message: format!("Vm execution error: {reason}"),
+                data: None,
+            },
+            RpcErr::InvalidForkChoiceState => RpcErrorMetadata {
+                code: -38002,
+                message: "Invalid forkchoice state".to_string(),
+                data: None,
+            },
+            RpcErr::InvalidPayloadAttributes => RpcErrorMetadata {
+                code: -38003,
+                message: "Invalid payload attributes".to_string(),
+                data: None,
+            },
+            RpcErr::UnknownPayload => RpcErrorMetadata {
+                code: -38001,
+                message: "Unknown payload".to_string(),
+                data: None,
+            },
+            RpcErr::TooLargeRequest => RpcErrorMetadata {
+                code: -38004,
+                message: "Too large request".to_string(),
+                data: None,
+            },
+            RpcErr::LogsLimitExceeded(reason) => RpcErrorMetadata {
+                code: -32005,
+                message: reason,
+                data: None,
             },
+            RpcErr::FilterNotFound => RpcErrorMetadata {
+                code: -32000,
+                message: "filter not found".to_string(),
+                data: None,
+            },
+            RpcErr::TooManyFilters => RpcErrorMetadata {
+                code: -32005,
+                message: "too many filters installed".to_string(),
+                data: None,
+            },
+            RpcErr::UnknownBlock => RpcErrorMetadata {
+                code: -39001,
+                message: "Unknown block".to_string(),
+                data: None,
+            },
+            RpcErr::HeaderNotFound => RpcErrorMetadata {
+                code: -32000,
+                message: "header not found".to_string(),
+                data: None,
+            },
+            RpcErr::MissingState(reason) => RpcErrorMetadata {
+                code: -32000,
+                message: reason,
+                data: None,
+            },
+            RpcErr::InvalidTransaction(reason) => RpcErrorMetadata {
+                code: -32000,
+                message: reason,
+                data: None,
+            },
+            RpcErr::Revert { data } => RpcErrorMetadata {
+                code: 3,
+                message: revert_message(&data),
+                data: Some(format!("0x{:x}", data)),
+            },
+            RpcErr::Halt { reason } => RpcErrorMetadata {
+                code: -32000,
+                message: reason,
+                data: None,
+            },
+        }
+    }
+}
+
+impl From<StoreError> for RpcErr {
+    fn from(error: StoreError) -> Self {
+        match error {
+            StoreError::StateNotAvailable(_)
+            | StoreError::MissingTrieNode(_)
+            | StoreError::PrunedBlock(_) => RpcErr::MissingState(error.to_string()),
+            StoreError::MissingBlock(_) => RpcErr::UnknownBlock,
+            _ => RpcErr::Internal(error.to_string()),
+        }
+    }
+}
+
+impl From<EvmError> for RpcErr {
+    fn from(error: EvmError) -> Self {
+        match error {
+            EvmError::DB(error) => error.into(),
+            EvmError::Transaction(reason) | EvmError::Custom(reason) => {
+                RpcErr::InvalidTransaction(reason)
+            }
+            _ => RpcErr::Vm(error.to_string()),
         }
     }
 }
 
+/// Checks that the request has the expected number of params and returns them
+pub fn expect_params(params: &Option<Vec<Value>>, expected: usize) -> Result<&[Value], RpcErr> {
+    let params = params.as_deref().unwrap_or_default();
+    if params.len() != expected {
+        return Err(RpcErr::BadParams(format!(
+            "expected {expected} params, got {}",
+            params.len()
+        )));
+    }
+    Ok(params)
+}
+
+/// Reads the param at the given position, telling which one is wrong if it can't be read
+pub fn parse_param<T: DeserializeOwned>(params: &[Value], position: usize) -> Result<T, RpcErr> {
+    let param = params
+        .get(position)
+        .ok_or_else(|| RpcErr::BadParams(format!("missing param {position}")))?;
+    serde_json::from_value(param.clone())
+        .map_err(|error| RpcErr::BadParams(format!("invalid param {position}: {error}")))
+}
+
+/// Reads a param holding a `0x`-prefixed hex quantity, such as a filter or payload id
+pub fn parse_hex_param(params: &[Value], position: usize) -> Result<u64, RpcErr> {
+    let param: String = parse_param(params, position)?;
+    param
+        .strip_prefix("0x")
+        .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+        .ok_or_else(|| {
+            RpcErr::BadParams(format!(
+                "invalid param {position}: expected a hex quantity, got {param}"
+            ))
+        })
+}
+
+/// Describes a revert, including its reason if the revert data holds one
+pub fn revert_message(data: &[u8]) -> String {
+    match decode_revert_reason(data) {
+        Some(reason) => format!("execution reverted: {reason}"),
+        None => "execution reverted".to_string(),
+    }
+}
+
+/// Selector of `Error(string)`, used by solidity to encode revert reasons
+const ERROR_STRING_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// Decodes the reason of a revert if its data is an ABI-encoded `Error(string)`
+fn decode_revert_reason(data: &[u8]) -> Option<String> {
+    let payload = data.strip_prefix(&ERROR_STRING_SELECTOR)?;
+    // The payload consists of the offset of the string, its length and its contents
+    let offset = usize::try_from(U256::from_big_endian(payload.get(..32)?)).ok()?;
+    let length_end = offset.checked_add(32)?;
+    let length = usize::try_from(U256::from_big_endian(payload.get(offset..length_end)?)).ok()?;
+    let reason = payload.get(length_end..length_end.checked_add(length)?)?;
+    String::from_utf8(reason.to_vec()).ok()
+}
+
+/// Id of a request, which is echoed by its response
+/// Numbers are kept as they were received, so that any number, such as a fractional one or one
+/// that doesn't fit an i64, is echoed unchanged
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum RpcRequestId {
+    Number(Number),
+    String(String),
+    /// Also used to answer requests whose id couldn't be read
+    Null,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RpcRequest {
-    pub id: i32,
+    /// Requests without an id are notifications, which are not answered
+    #[serde(
+        default,
+        deserialize_with = "deserialize_request_id",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub id: Option<RpcRequestId>,
     pub jsonrpc: String,
     pub method: String,
     pub params: Option<Vec<Value>>,
 }
 
+impl RpcRequest {
+    /// Returns the namespace of the method, which prefixes its name
+    pub fn namespace(&self) -> Result<RpcNamespace, RpcErr> {
+        let (namespace, _) = self.method.split_once('_').ok_or(RpcErr::MethodNotFound)?;
+        namespace.parse().map_err(|_| RpcErr::MethodNotFound)
+    }
+}
+
+/// Groups of methods, which are enabled separately for each listener
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RpcNamespace {
+    Eth,
+    Engine,
+    Admin,
+    Debug,
+    Net,
+    Web3,
+    Txpool,
+}
+
+impl FromStr for RpcNamespace {
+    type Err = String;
+
+    fn from_str(namespace: &str) -> Result<RpcNamespace, String> {
+        match namespace {
+            "eth" => Ok(RpcNamespace::Eth),
+            "engine" => Ok(RpcNamespace::Engine),
+            "admin" => Ok(RpcNamespace::Admin),
+            "debug" => Ok(RpcNamespace::Debug),
+            "net" => Ok(RpcNamespace::Net),
+            "web3" => Ok(RpcNamespace::Web3),
+            "txpool" => Ok(RpcNamespace::Txpool),
+            _ => Err(format!("Unknown rpc namespace {namespace}")),
+        }
+    }
+}
+
+/// Reads a present id, telling a `null` id apart from a missing one
+fn deserialize_request_id<'de, D>(deserializer: D) -> Result<Option<RpcRequestId>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    RpcRequestId::deserialize(deserializer).map(Some)
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RpcErrorMetadata {
     pub code: i32,
     pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RpcSuccessResponse {
-    pub id: i32,
+    pub id: RpcRequestId,
     pub jsonrpc: String,
     pub result: Value,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RpcErrorResponse {
-    pub id: i32,
+    pub id: RpcRequestId,
     pub jsonrpc: String,
     pub error: RpcErrorMetadata,
 }
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket},
+        State, WebSocketUpgrade,
+    },
+    response::Response,
+    Extension,
+};
+use serde_json::Value;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::info;
+
+use crate::{
+    eth::subscription::{
+        next_subscription_id, notifications, subscription_id_value, SubscribeRequest,
+        SubscriptionKind, UnsubscribeRequest,
+    },
+    handle_request_body, map_requests,
+    utils::{RpcErr, RpcRequest},
+    EnabledNamespaces, RpcApiContext,
+};
+
+pub async fn handle_websocket(
+    State(context): State<RpcApiContext>,
+    Extension(namespaces): Extension<EnabledNamespaces>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    upgrade
+        .max_message_size(context.request_limits.max_body_size)
+        .on_upgrade(|socket| serve_websocket(socket, context, namespaces))
+}
+
+/// Answers the requests sent through the socket and pushes the notifications of its
+/// subscriptions until it is closed
+/// Subscriptions only live as long as the connection that created them
+async fn serve_websocket(
+    mut socket: WebSocket,
+    context: RpcApiContext,
+    namespaces: EnabledNamespaces,
+) {
+    let mut events = context.notifier.subscribe();
+    let mut subscriptions = HashMap::new();
+    loop {
+        let messages = tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(body))) => {
+                    handle_websocket_request(&body, &context, &namespaces, &mut subscriptions)
+                        .into_iter()
+                        .collect()
+                }
+                // Pings are answered by axum, and binary messages are not supported
+                Some(Ok(Message::Binary(_) | Message::Ping(_) | Message::Pong(_))) => continue,
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+            },
+            event = events.recv() => match event {
+                Ok(event) => notifications(&event, &subscriptions, &context.storage),
+                // The events the connection fell behind on are not notified
+                Err(RecvError::Lagged(skipped)) => {
+                    info!("Websocket connection skipped {skipped} events");
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            },
+        };
+        for message in messages {
+            if socket
+                .send(Message::Text(message.to_string()))
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+}
+
+/// Handles a message received through a websocket connection, returning the response to send
+/// back if there is one
+fn handle_websocket_request(
+    body: &str,
+    context: &RpcApiContext,
+    namespaces: &EnabledNamespaces,
+    subscriptions: &mut HashMap<u64, SubscriptionKind>,
+) -> Option<Value> {
+    handle_request_body(body, context.request_limits, |req| {
+        // Subscriptions belong to the eth namespace
+        if !namespaces.contains(&req.namespace()?) {
+            return Err(RpcErr::MethodNotFound);
+        }
+        match req.method.as_str() {
+            "eth_subscribe" => subscribe(req, context, subscriptions),
+            "eth_unsubscribe" => unsubscribe(req, subscriptions),
+            _ => map_requests(req, context.clone()),
+        }
+    })
+}
+
+fn subscribe(
+    req: &RpcRequest,
+    context: &RpcApiContext,
+    subscriptions: &mut HashMap<u64, SubscriptionKind>,
+) -> Result<Value, RpcErr> {
+    let request = SubscribeRequest::parse(&req.params)?;
+    info!("Requested new subscription: {:?}", request.kind);
+    let id = next_subscription_id();
+    subscriptions.insert(id, request.kind);
+    Ok(subscription_id_value(id))
+}
+
+fn unsubscribe(
+    req: &RpcRequest,
+    subscriptions: &mut HashMap<u64, SubscriptionKind>,
+) -> Result<Value, RpcErr> {
+    let request = UnsubscribeRequest::parse(&req.params)?;
+    info!("Requested removal of subscription {:#x}", request.id);
+    let removed = subscriptions.remove(&request.id).is_some();
+    Ok(Value::Bool(removed))
+}
@@ -1,4 +1,5 @@
 pub mod api;
+pub mod batch;
 #[cfg(feature = "in_memory")]
 pub mod in_memory;
 #[cfg(feature = "libmdbx")]
@@ -1,19 +1,25 @@
-use std::fmt::Debug;
+use std::{collections::HashMap, fmt::Debug};
 
 use bytes::Bytes;
 use ethereum_types::{Address, H256, U256};
 
 use ethereum_rust_core::types::{
-    Account, AccountInfo, BlockBody, BlockHash, BlockHeader, BlockNumber, Index, Receipt,
-    Transaction,
+    Account, AccountInfo, BlockBody, BlockHash, BlockHeader, BlockNumber, BuiltPayload,
+    ChainConfig, Index, Receipt, Transaction,
 };
 
 use crate::error::StoreError;
 
-pub trait StoreEngine: Debug + Send {
+use super::batch::WriteBatch;
+
+pub trait StoreEngine: Debug + Send + Sync {
+    /// Applies the writes of the batch as a single atomic operation, so that either all of them
+    /// are committed or none of them are
+    fn write_batch(&self, batch: WriteBatch) -> Result<(), StoreError>;
+
     /// Add account info
     fn add_account_info(
-        &mut self,
+        &self,
         address: Address,
         account_info: AccountInfo,
     ) -> Result<(), StoreError>;
@@ -22,34 +28,59 @@ pub trait StoreEngine: Debug + Send {
     fn get_account_info(&self, address: Address) -> Result<Option<AccountInfo>, StoreError>;
 
     /// Remove account info
-    fn remove_account_info(&mut self, address: Address) -> Result<(), StoreError>;
+    fn remove_account_info(&self, address: Address) -> Result<(), StoreError>;
 
-    /// Add block header
+    /// Add block header under the block's hash
     fn add_block_header(
-        &mut self,
-        block_number: BlockNumber,
+        &self,
+        block_hash: BlockHash,
         block_header: BlockHeader,
     ) -> Result<(), StoreError>;
 
-    /// Obtain block header
+    /// Obtain the header of a stored block given its hash, whether it is canonical or not
+    fn get_block_header_by_hash(
+        &self,
+        block_hash: BlockHash,
+    ) -> Result<Option<BlockHeader>, StoreError>;
+
+    /// Obtain the header of the canonical block with the given number
     fn get_block_header(
         &self,
         block_number: BlockNumber,
-    ) -> Result<Option<BlockHeader>, StoreError>;
+    ) -> Result<Option<BlockHeader>, StoreError> {
+        match self.get_canonical_block_hash(block_number)? {
+            Some(block_hash) => self.get_block_header_by_hash(block_hash),
+            None => Ok(None),
+        }
+    }
 
-    /// Add block body
+    /// Add block body under the block's hash
     fn add_block_body(
-        &mut self,
-        block_number: BlockNumber,
+        &self,
+        block_hash: BlockHash,
         block_body: BlockBody,
     ) -> Result<(), StoreError>;
 
-    /// Obtain block body
-    fn get_block_body(&self, block_number: BlockNumber) -> Result<Option<BlockBody>, StoreError>;
+    /// Obtain the body of a stored block given its hash, whether it is canonical or not
+    fn get_block_body_by_hash(
+        &self,
+        block_hash: BlockHash,
+    ) -> Result<Option<BlockBody>, StoreError>;
 
-    /// Add block body
+    /// Remove the body of a stored block given its hash
+    fn remove_block_body(&self, block_hash: BlockHash) -> Result<(), StoreError>;
+
+    /// Obtain the body of the canonical block with the given number
+    fn get_block_body(&self, block_number: BlockNumber) -> Result<Option<BlockBody>, StoreError> {
+        match self.get_canonical_block_hash(block_number)? {
+            Some(block_hash) => self.get_block_body_by_hash(block_hash),
+            None => Ok(None),
+        }
+    }
+
+    /// Add block number
     fn add_block_number(
-        &mut self,
+        &self,
         block_hash: BlockHash,
         block_number: BlockNumber,
     ) -> Result<(), StoreError>;
@@ -57,9 +88,25 @@ pub trait StoreEngine: Debug + Send {
     /// Obtain block number
     fn get_block_number(&self, block_hash: BlockHash) -> Result<Option<BlockNumber>, StoreError>;
 
+    /// Set the hash of the canonical block with the given number
+    fn add_canonical_block_hash(
+        &self,
+        block_number: BlockNumber,
+        block_hash: BlockHash,
+    ) -> Result<(), StoreError>;
+
+    /// Obtain the hash of the canonical block with the given number
+    fn get_canonical_block_hash(
+        &self,
+        block_number: BlockNumber,
+    ) -> Result<Option<BlockHash>, StoreError>;
+
+    /// Remove the canonical block with the given number from the canonical chain
+    fn remove_canonical_block_hash(&self, block_number: BlockNumber) -> Result<(), StoreError>;
+
     /// Store transaction location (block number and index of the transaction within the block)
     fn add_transaction_location(
-        &mut self,
+        &self,
         transaction_hash: H256,
         block_number: BlockNumber,
         index: Index,
@@ -71,23 +118,41 @@ pub trait StoreEngine: Debug + Send {
         transaction_hash: H256,
     ) -> Result<Option<(BlockNumber, Index)>, StoreError>;
 
-    /// Add receipt
+    /// Remove transaction location
+    fn remove_transaction_location(&self, transaction_hash: H256) -> Result<(), StoreError>;
+
+    /// Add receipt under the hash of its block
     fn add_receipt(
-        &mut self,
-        block_number: BlockNumber,
+        &self,
+        block_hash: BlockHash,
         index: Index,
         receipt: Receipt,
     ) -> Result<(), StoreError>;
 
-    /// Obtain receipt
+    /// Remove all receipts of a stored block given the block's hash
+    fn remove_receipts(&self, block_hash: BlockHash) -> Result<(), StoreError>;
+
+    /// Obtain receipt of a stored block given the block's hash
+    fn get_receipt_by_hash(
+        &self,
+        block_hash: BlockHash,
+        index: Index,
+    ) -> Result<Option<Receipt>, StoreError>;
+
+    /// Obtain receipt of the canonical block with the given number
     fn get_receipt(
         &self,
         block_number: BlockNumber,
         index: Index,
-    ) -> Result<Option<Receipt>, StoreError>;
+    ) -> Result<Option<Receipt>, StoreError> {
+        match self.get_canonical_block_hash(block_number)? {
+            Some(block_hash) => self.get_receipt_by_hash(block_hash, index),
+            None => Ok(None),
+        }
+    }
 
     /// Add account code
-    fn add_account_code(&mut self, code_hash: H256, code: Bytes) -> Result<(), StoreError>;
+    fn add_account_code(&self, code_hash: H256, code: Bytes) -> Result<(), StoreError>;
 
     /// Obtain account code via code hash
     fn get_account_code(&self, code_hash: H256) -> Result<Option<Bytes>, StoreError>;
@@ -121,7 +186,7 @@ pub trait StoreEngine: Debug + Send {
 
     // Add storage value
     fn add_storage_at(
-        &mut self,
+        &self,
         address: Address,
         storage_key: H256,
         storage_value: H256,
@@ -134,11 +199,97 @@ pub trait StoreEngine: Debug + Send {
         storage_key: H256,
     ) -> Result<Option<H256>, StoreError>;
 
+    /// Remove a storage value
+    fn remove_storage_at(&self, address: Address, storage_key: H256) -> Result<(), StoreError>;
+
     // Add storage value
-    fn remove_account_storage(&mut self, address: Address) -> Result<(), StoreError>;
+    fn remove_account_storage(&self, address: Address) -> Result<(), StoreError>;
+
+    /// Obtain the info of every account stored
+    fn get_account_infos(&self) -> Result<Vec<(Address, AccountInfo)>, StoreError>;
+
+    /// Obtain every storage key and value stored for an account
+    fn get_account_storage(&self, address: Address) -> Result<HashMap<H256, H256>, StoreError>;
+
+    /// Add the info an account had before being modified by the given block, `None` if the
+    /// account didn't exist before it
+    fn add_account_info_history(
+        &self,
+        block_number: BlockNumber,
+        address: Address,
+        account_info: Option<AccountInfo>,
+    ) -> Result<(), StoreError>;
+
+    /// Obtain the info an account had before being modified by the given block
+    /// Returns `None` if the block didn't modify the account and `Some(None)` if the account
+    /// didn't exist before it
+    fn get_account_info_history(
+        &self,
+        block_number: BlockNumber,
+        address: Address,
+    ) -> Result<Option<Option<AccountInfo>>, StoreError>;
+
+    /// Add the value a storage slot had before being modified by the given block
+    fn add_storage_history(
+        &self,
+        block_number: BlockNumber,
+        address: Address,
+        storage_key: H256,
+        storage_value: H256,
+    ) -> Result<(), StoreError>;
+
+    /// Obtain the value a storage slot had before being modified by the given block, `None` if
+    /// the block didn't modify it
+    fn get_storage_history(
+        &self,
+        block_number: BlockNumber,
+        address: Address,
+        storage_key: H256,
+    ) -> Result<Option<H256>, StoreError>;
+
+    /// Obtain the infos every account modified by the given block had before it
+    fn get_block_account_info_history(
+        &self,
+        block_number: BlockNumber,
+    ) -> Result<Vec<(Address, Option<AccountInfo>)>, StoreError>;
+
+    /// Obtain the values every storage slot modified by the given block had before it
+    fn get_block_storage_history(
+        &self,
+        block_number: BlockNumber,
+    ) -> Result<Vec<(Address, H256, H256)>, StoreError>;
+
+    /// Remove the account and storage history recorded for the given block
+    fn remove_state_history(&self, block_number: BlockNumber) -> Result<(), StoreError>;
+
+    /// Add encoded trie nodes, each one under the hash of its encoding
+    /// Nodes are shared between the state trie and the storage tries of every account
+    fn add_trie_nodes(&self, nodes: Vec<(H256, Vec<u8>)>) -> Result<(), StoreError>;
+
+    /// Obtain an encoded trie node given its hash
+    fn get_trie_node(&self, node_hash: H256) -> Result<Option<Vec<u8>>, StoreError>;
+
+    /// Obtain the info an account had at the end of the given block, that is, the one recorded by
+    /// the first change made to it by the blocks applied after it, or its current info if there
+    /// is none
+    fn get_account_info_at_block(
+        &self,
+        block_number: BlockNumber,
+        address: Address,
+    ) -> Result<Option<AccountInfo>, StoreError>;
+
+    /// Obtain the value a storage slot had at the end of the given block, that is, the one
+    /// recorded by the first change made to it by the blocks applied after it, or its current
+    /// value if there is none
+    fn get_storage_at_block(
+        &self,
+        block_number: BlockNumber,
+        address: Address,
+        storage_key: H256,
+    ) -> Result<Option<H256>, StoreError>;
 
     /// Stores account in db (including info, code & storage)
-    fn add_account(&mut self, address: Address, account: Account) -> Result<(), StoreError> {
+    fn add_account(&self, address: Address, account: Account) -> Result<(), StoreError> {
         self.add_account_info(address, account.info.clone())?;
         self.add_account_code(account.info.code_hash, account.code)?;
         for (storage_key, storage_value) in account.storage {
@@ -148,13 +299,13 @@ pub trait StoreEngine: Debug + Send {
     }
 
     /// Removes account info and storage
-    fn remove_account(&mut self, address: Address) -> Result<(), StoreError> {
+    fn remove_account(&self, address: Address) -> Result<(), StoreError> {
         self.remove_account_info(address)?;
         self.remove_account_storage(address)
     }
 
     /// Increments the balance of an account by a given ammount (if it exists)
-    fn increment_balance(&mut self, address: Address, amount: U256) -> Result<(), StoreError> {
+    fn increment_balance(&self, address: Address, amount: U256) -> Result<(), StoreError> {
         if let Some(mut account_info) = self.get_account_info(address)? {
             account_info.balance = account_info.balance.saturating_add(amount);
             self.add_account_info(address, account_info)?;
@@ -162,8 +313,51 @@ pub trait StoreEngine: Debug + Send {
         Ok(())
     }
     /// Updates the value of the chain id
-    fn update_chain_id(&mut self, chain_id: U256) -> Result<(), StoreError>;
+    fn update_chain_id(&self, chain_id: U256) -> Result<(), StoreError>;
 
     /// Obtain the current chain id
     fn get_chain_id(&self) -> Result<Option<U256>, StoreError>;
+
+    /// Updates the chain configuration
+    fn update_chain_config(&self, chain_config: &ChainConfig) -> Result<(), StoreError>;
+
+    /// Obtain the current chain configuration
+    fn get_chain_config(&self) -> Result<Option<ChainConfig>, StoreError>;
+
+    /// Updates the number of the latest block whose state has been applied to the store
+    fn update_latest_block_number(&self, block_number: BlockNumber) -> Result<(), StoreError>;
+
+    /// Obtain the number of the latest block whose state has been applied to the store
+    fn get_latest_block_number(&self) -> Result<Option<BlockNumber>, StoreError>;
+
+    /// Updates the number of the latest canonical block whose body, receipts and transaction
+    /// index entries were pruned, all blocks before it are pruned too
+    fn update_pruned_block_number(&self, block_number: BlockNumber) -> Result<(), StoreError>;
+
+    /// Obtain the number of the latest pruned canonical block, `None` if no block was pruned yet
+    fn get_pruned_block_number(&self) -> Result<Option<BlockNumber>, StoreError>;
+
+    /// Updates the number of the latest block marked as finalized by the consensus layer
+    fn update_finalized_block_number(&self, block_number: BlockNumber) -> Result<(), StoreError>;
+
+    /// Obtain the number of the latest block marked as finalized by the consensus layer
+    fn get_finalized_block_number(&self) -> Result<Option<BlockNumber>, StoreError>;
+
+    /// Updates the number of the latest block marked as safe by the consensus layer
+    fn update_safe_block_number(&self, block_number: BlockNumber) -> Result<(), StoreError>;
+
+    /// Obtain the number of the latest block marked as safe by the consensus layer
+    fn get_safe_block_number(&self) -> Result<Option<BlockNumber>, StoreError>;
+
+    /// Updates the root of the state trie of the current state
+    fn update_state_root(&self, state_root: H256) -> Result<(), StoreError>;
+
+    /// Obtain the root of the state trie of the current state
+    fn get_state_root(&self) -> Result<Option<H256>, StoreError>;
+
+    /// Add a locally built payload under its payload id
+    fn add_payload(&self, payload_id: u64, payload: BuiltPayload) -> Result<(), StoreError>;
+
+    /// Obtain a locally built payload given its payload id
+    fn get_payload(&self, payload_id: u64) -> Result<Option<BuiltPayload>, StoreError>;
 }
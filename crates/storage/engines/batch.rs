@@ -0,0 +1,206 @@
+use bytes::Bytes;
+use ethereum_rust_core::types::{
+    AccountInfo, BlockBody, BlockHash, BlockHeader, BlockNumber, BuiltPayload, ChainConfig, Index,
+    Receipt,
+};
+use ethereum_types::{Address, H256, U256};
+
+/// A set of writes that are applied to the store as a single atomic operation via
+/// `StoreEngine::write_batch`, either all of them are committed or none of them are
+/// Writes are applied in the order they were added to the batch
+#[derive(Debug, Default)]
+pub struct WriteBatch {
+    pub(crate) ops: Vec<WriteOp>,
+}
+
+/// A single write of a `WriteBatch`, each one matches a write method of `StoreEngine`
+#[derive(Debug)]
+pub(crate) enum WriteOp {
+    AddAccountInfo(Address, AccountInfo),
+    RemoveAccountInfo(Address),
+    AddAccountCode(H256, Bytes),
+    AddStorageAt(Address, H256, H256),
+    RemoveStorageAt(Address, H256),
+    RemoveAccountStorage(Address),
+    AddBlockHeader(BlockHash, BlockHeader),
+    AddBlockBody(BlockHash, BlockBody),
+    RemoveBlockBody(BlockHash),
+    AddBlockNumber(BlockHash, BlockNumber),
+    AddCanonicalBlockHash(BlockNumber, BlockHash),
+    RemoveCanonicalBlockHash(BlockNumber),
+    AddTransactionLocation(H256, BlockNumber, Index),
+    RemoveTransactionLocation(H256),
+    AddReceipt(BlockHash, Index, Receipt),
+    RemoveReceipts(BlockHash),
+    AddAccountInfoHistory(BlockNumber, Address, Option<AccountInfo>),
+    AddStorageHistory(BlockNumber, Address, H256, H256),
+    RemoveStateHistory(BlockNumber),
+    AddTrieNodes(Vec<(H256, Vec<u8>)>),
+    UpdateChainId(U256),
+    UpdateChainConfig(ChainConfig),
+    UpdateLatestBlockNumber(BlockNumber),
+    UpdatePrunedBlockNumber(BlockNumber),
+    UpdateFinalizedBlockNumber(BlockNumber),
+    UpdateSafeBlockNumber(BlockNumber),
+    UpdateStateRoot(H256),
+    AddPayload(u64, BuiltPayload),
+}
+
+impl WriteBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_account_info(&mut self, address: Address, account_info: AccountInfo) {
+        self.ops
+            .push(WriteOp::AddAccountInfo(address, account_info));
+    }
+
+    pub fn remove_account_info(&mut self, address: Address) {
+        self.ops.push(WriteOp::RemoveAccountInfo(address));
+    }
+
+    pub fn add_account_code(&mut self, code_hash: H256, code: Bytes) {
+        self.ops.push(WriteOp::AddAccountCode(code_hash, code));
+    }
+
+    pub fn add_storage_at(&mut self, address: Address, storage_key: H256, storage_value: H256) {
+        self.ops
+            .push(WriteOp::AddStorageAt(address, storage_key, storage_value));
+    }
+
+    pub fn remove_storage_at(&mut self, address: Address, storage_key: H256) {
+        self.ops
+            .push(WriteOp::RemoveStorageAt(address, storage_key));
+    }
+
+    pub fn remove_account_storage(&mut self, address: Address) {
+        self.ops.push(WriteOp::RemoveAccountStorage(address));
+    }
+
+    pub fn add_block_header(&mut self, block_hash: BlockHash, block_header: BlockHeader) {
+        self.ops
+            .push(WriteOp::AddBlockHeader(block_hash, block_header));
+    }
+
+    pub fn add_block_body(&mut self, block_hash: BlockHash, block_body: BlockBody) {
+        self.ops.push(WriteOp::AddBlockBody(block_hash, block_body));
+    }
+
+    pub fn remove_block_body(&mut self, block_hash: BlockHash) {
+        self.ops.push(WriteOp::RemoveBlockBody(block_hash));
+    }
+
+    pub fn add_block_number(&mut self, block_hash: BlockHash, block_number: BlockNumber) {
+        self.ops
+            .push(WriteOp::AddBlockNumber(block_hash, block_number));
+    }
+
+    pub fn add_canonical_block_hash(&mut self, block_number: BlockNumber, block_hash: BlockHash) {
+        self.ops
+            .push(WriteOp::AddCanonicalBlockHash(block_number, block_hash));
+    }
+
+    pub fn remove_canonical_block_hash(&mut self, block_number: BlockNumber) {
+        self.ops
+            .push(WriteOp::RemoveCanonicalBlockHash(block_number));
+    }
+
+    pub fn add_transaction_location(
+        &mut self,
+        transaction_hash: H256,
+        block_number: BlockNumber,
+        index: Index,
+    ) {
+        self.ops.push(WriteOp::AddTransactionLocation(
+            transaction_hash,
+            block_number,
+            index,
+        ));
+    }
+
+    pub fn remove_transaction_location(&mut self, transaction_hash: H256) {
+        self.ops
+            .push(WriteOp::RemoveTransactionLocation(transaction_hash));
+    }
+
+    pub fn add_receipt(&mut self, block_hash: BlockHash, index: Index, receipt: Receipt) {
+        self.ops
+            .push(WriteOp::AddReceipt(block_hash, index, receipt));
+    }
+
+    pub fn remove_receipts(&mut self, block_hash: BlockHash) {
+        self.ops.push(WriteOp::RemoveReceipts(block_hash));
+    }
+
+    pub fn add_account_info_history(
+        &mut self,
+        block_number: BlockNumber,
+        address: Address,
+        account_info: Option<AccountInfo>,
+    ) {
+        self.ops.push(WriteOp::AddAccountInfoHistory(
+            block_number,
+            address,
+            account_info,
+        ));
+    }
+
+    pub fn add_storage_history(
+        &mut self,
+        block_number: BlockNumber,
+        address: Address,
+        storage_key: H256,
+        storage_value: H256,
+    ) {
+        self.ops.push(WriteOp::AddStorageHistory(
+            block_number,
+            address,
+            storage_key,
+            storage_value,
+        ));
+    }
+
+    pub fn remove_state_history(&mut self, block_number: BlockNumber) {
+        self.ops.push(WriteOp::RemoveStateHistory(block_number));
+    }
+
+    pub fn add_trie_nodes(&mut self, nodes: Vec<(H256, Vec<u8>)>) {
+        self.ops.push(WriteOp::AddTrieNodes(nodes));
+    }
+
+    pub fn update_chain_id(&mut self, chain_id: U256) {
+        self.ops.push(WriteOp::UpdateChainId(chain_id));
+    }
+
+    pub fn update_chain_config(&mut self, chain_config: ChainConfig) {
+        self.ops.push(WriteOp::UpdateChainConfig(chain_config));
+    }
+
+    pub fn update_latest_block_number(&mut self, block_number: BlockNumber) {
+        self.ops
+            .push(WriteOp::UpdateLatestBlockNumber(block_number));
+    }
+
+    pub fn update_pruned_block_number(&mut self, block_number: BlockNumber) {
+        self.ops
+            .push(WriteOp::UpdatePrunedBlockNumber(block_number));
+    }
+
+    pub fn update_finalized_block_number(&mut self, block_number: BlockNumber) {
+        self.ops
+            .push(WriteOp::UpdateFinalizedBlockNumber(block_number));
+    }
+
+    pub fn update_safe_block_number(&mut self, block_number: BlockNumber) {
+        self.ops.push(WriteOp::UpdateSafeBlockNumber(block_number));
+    }
+
+    pub fn update_state_root(&mut self, state_root: H256) {
+        self.ops.push(WriteOp::UpdateStateRoot(state_root));
+    }
+
+    pub fn add_payload(&mut self, payload_id: u64, payload: BuiltPayload) {
+        self.ops.push(WriteOp::AddPayload(payload_id, payload));
+    }
+}
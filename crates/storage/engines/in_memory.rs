@@ -1,156 +1,377 @@
 use crate::error::StoreError;
 use bytes::Bytes;
 use ethereum_rust_core::types::{
-    AccountInfo, BlockBody, BlockHash, BlockHeader, BlockNumber, Index, Receipt,
+    AccountInfo, BlockBody, BlockHash, BlockHeader, BlockNumber, BuiltPayload, ChainConfig, Index,
+    Receipt,
 };
 use ethereum_types::{Address, H256, U256};
-use std::{collections::HashMap, fmt::Debug};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Debug,
+    sync::{RwLock, RwLockReadGuard},
+};
+
+use super::{
+    api::StoreEngine,
+    batch::{WriteBatch, WriteOp},
+};
 
-use super::api::StoreEngine;
+/// Keeps the whole store in memory behind a lock, which is held for the whole of each write
+/// batch, so that readers see either none or all of its writes
+#[derive(Default)]
+pub struct Store(RwLock<StoreInner>);
 
 #[derive(Default)]
-pub struct Store {
+struct StoreInner {
     chain_data: ChainData,
     account_infos: HashMap<Address, AccountInfo>,
     block_numbers: HashMap<BlockHash, BlockNumber>,
-    bodies: HashMap<BlockNumber, BlockBody>,
-    headers: HashMap<BlockNumber, BlockHeader>,
+    // Maps block numbers to the hash of the canonical block at that height
+    canonical_hashes: HashMap<BlockNumber, BlockHash>,
+    bodies: HashMap<BlockHash, BlockBody>,
+    headers: HashMap<BlockHash, BlockHeader>,
     // Maps code hashes to code
     account_codes: HashMap<H256, Bytes>,
     account_storages: HashMap<Address, HashMap<H256, H256>>,
     // Maps transaction hashes to their block number and index within the block
     transaction_locations: HashMap<H256, (BlockNumber, Index)>,
-    receipts: HashMap<BlockNumber, HashMap<Index, Receipt>>,
+    receipts: HashMap<BlockHash, HashMap<Index, Receipt>>,
+    // Maps payload ids to the payloads built for them
+    payloads: HashMap<u64, BuiltPayload>,
+    // Maps block numbers to the account infos and storage values before the block modified them
+    account_info_history: HashMap<BlockNumber, HashMap<Address, Option<AccountInfo>>>,
+    storage_history: HashMap<BlockNumber, HashMap<Address, HashMap<H256, H256>>>,
+    // Maps accounts and storage slots to the blocks whose history holds a change to them
+    account_history_blocks: HashMap<Address, BTreeSet<BlockNumber>>,
+    storage_history_blocks: HashMap<(Address, H256), BTreeSet<BlockNumber>>,
+    // Maps trie node hashes to their encoding
+    trie_nodes: HashMap<H256, Vec<u8>>,
 }
 
 #[derive(Default)]
 struct ChainData {
     chain_id: Option<U256>,
+    chain_config: Option<ChainConfig>,
+    latest_block_number: Option<BlockNumber>,
+    finalized_block_number: Option<BlockNumber>,
+    pruned_block_number: Option<BlockNumber>,
+    safe_block_number: Option<BlockNumber>,
+    state_root: Option<H256>,
 }
 
 impl Store {
     pub fn new() -> Result<Self, StoreError> {
         Ok(Self::default())
     }
+
+    fn inner(&self) -> RwLockReadGuard<'_, StoreInner> {
+        self.0.read().unwrap()
+    }
+
+    // Helper method to perform a single write
+    fn apply(&self, op: WriteOp) -> Result<(), StoreError> {
+        self.0.write().unwrap().apply(op);
+        Ok(())
+    }
+}
+
+impl StoreInner {
+    /// Applies a single write, writes to memory can't fail
+    fn apply(&mut self, op: WriteOp) {
+        match op {
+            WriteOp::AddAccountInfo(address, info) => {
+                self.account_infos.insert(address, info);
+            }
+            WriteOp::RemoveAccountInfo(address) => {
+                self.account_infos.remove(&address);
+            }
+            WriteOp::AddAccountCode(code_hash, code) => {
+                self.account_codes.insert(code_hash, code);
+            }
+            WriteOp::AddStorageAt(address, key, value) => {
+                self.account_storages
+                    .entry(address)
+                    .or_default()
+                    .insert(key, value);
+            }
+            WriteOp::RemoveStorageAt(address, key) => {
+                if let Some(entry) = self.account_storages.get_mut(&address) {
+                    entry.remove(&key);
+                }
+            }
+            WriteOp::RemoveAccountStorage(address) => {
+                self.account_storages.remove(&address);
+            }
+            WriteOp::AddBlockHeader(hash, header) => {
+                self.headers.insert(hash, header);
+            }
+            WriteOp::AddBlockBody(hash, body) => {
+                self.bodies.insert(hash, body);
+            }
+            WriteOp::RemoveBlockBody(hash) => {
+                self.bodies.remove(&hash);
+            }
+            WriteOp::AddBlockNumber(hash, number) => {
+                self.block_numbers.insert(hash, number);
+            }
+            WriteOp::AddCanonicalBlockHash(number, hash) => {
+                self.canonical_hashes.insert(number, hash);
+            }
+            WriteOp::RemoveCanonicalBlockHash(number) => {
+                self.canonical_hashes.remove(&number);
+            }
+            WriteOp::AddTransactionLocation(tx_hash, number, index) => {
+                self.transaction_locations.insert(tx_hash, (number, index));
+            }
+            WriteOp::RemoveTransactionLocation(tx_hash) => {
+                self.transaction_locations.remove(&tx_hash);
+            }
+            WriteOp::AddReceipt(hash, index, receipt) => {
+                self.receipts
+                    .entry(hash)
+                    .or_default()
+                    .insert(index, receipt);
+            }
+            WriteOp::RemoveReceipts(hash) => {
+                self.receipts.remove(&hash);
+            }
+            WriteOp::AddAccountInfoHistory(number, address, info) => {
+                self.account_info_history
+                    .entry(number)
+                    .or_default()
+                    .insert(address, info);
+                self.account_history_blocks
+                    .entry(address)
+                    .or_default()
+                    .insert(number);
+            }
+            WriteOp::AddStorageHistory(number, address, key, value) => {
+                self.storage_history
+                    .entry(number)
+                    .or_default()
+                    .entry(address)
+                    .or_default()
+                    .insert(key, value);
+                self.storage_history_blocks
+                    .entry((address, key))
+                    .or_default()
+                    .insert(number);
+            }
+            WriteOp::RemoveStateHistory(number) => self.remove_state_history(number),
+            WriteOp::AddTrieNodes(nodes) => self.trie_nodes.extend(nodes),
+            WriteOp::UpdateChainId(chain_id) => {
+                self.chain_data.chain_id.replace(chain_id);
+            }
+            WriteOp::UpdateChainConfig(config) => {
+                self.chain_data.chain_config.replace(config);
+            }
+            WriteOp::UpdateLatestBlockNumber(number) => {
+                self.chain_data.latest_block_number.replace(number);
+            }
+            WriteOp::UpdatePrunedBlockNumber(number) => {
+                self.chain_data.pruned_block_number.replace(number);
+            }
+            WriteOp::UpdateFinalizedBlockNumber(number) => {
+                self.chain_data.finalized_block_number.replace(number);
+            }
+            WriteOp::UpdateSafeBlockNumber(number) => {
+                self.chain_data.safe_block_number.replace(number);
+            }
+            WriteOp::UpdateStateRoot(state_root) => {
+                self.chain_data.state_root.replace(state_root);
+            }
+            WriteOp::AddPayload(payload_id, payload) => {
+                self.payloads.insert(payload_id, payload);
+            }
+        }
+    }
+
+    fn remove_state_history(&mut self, block_number: BlockNumber) {
+        for address in self
+            .account_info_history
+            .remove(&block_number)
+            .unwrap_or_default()
+            .into_keys()
+        {
+            remove_history_block(&mut self.account_history_blocks, address, block_number);
+        }
+        for (address, slots) in self
+            .storage_history
+            .remove(&block_number)
+            .unwrap_or_default()
+        {
+            for storage_key in slots.into_keys() {
+                remove_history_block(
+                    &mut self.storage_history_blocks,
+                    (address, storage_key),
+                    block_number,
+                );
+            }
+        }
+    }
 }
 
 impl StoreEngine for Store {
+    fn write_batch(&self, batch: WriteBatch) -> Result<(), StoreError> {
+        // Writes to memory can't fail and the store is locked until all of them are applied, so
+        // applying them one after the other is already atomic
+        let mut store = self.0.write().unwrap();
+        for op in batch.ops {
+            store.apply(op);
+        }
+        Ok(())
+    }
+
     fn add_account_info(
-        &mut self,
+        &self,
         address: Address,
         account_info: AccountInfo,
     ) -> Result<(), StoreError> {
-        self.account_infos.insert(address, account_info);
-        Ok(())
+        self.apply(WriteOp::AddAccountInfo(address, account_info))
     }
 
     fn get_account_info(&self, address: Address) -> Result<Option<AccountInfo>, StoreError> {
-        Ok(self.account_infos.get(&address).cloned())
+        Ok(self.inner().account_infos.get(&address).cloned())
     }
 
-    fn remove_account_info(&mut self, address: Address) -> Result<(), StoreError> {
-        self.account_infos.remove(&address);
-        Ok(())
+    fn remove_account_info(&self, address: Address) -> Result<(), StoreError> {
+        self.apply(WriteOp::RemoveAccountInfo(address))
     }
 
-    fn get_block_header(&self, block_number: u64) -> Result<Option<BlockHeader>, StoreError> {
-        Ok(self.headers.get(&block_number).cloned())
+    fn get_block_header_by_hash(
+        &self,
+        block_hash: BlockHash,
+    ) -> Result<Option<BlockHeader>, StoreError> {
+        Ok(self.inner().headers.get(&block_hash).cloned())
     }
 
-    fn get_block_body(&self, block_number: u64) -> Result<Option<BlockBody>, StoreError> {
-        Ok(self.bodies.get(&block_number).cloned())
+    fn get_block_body_by_hash(
+        &self,
+        block_hash: BlockHash,
+    ) -> Result<Option<BlockBody>, StoreError> {
+        Ok(self.inner().bodies.get(&block_hash).cloned())
     }
 
     fn add_block_header(
-        &mut self,
-        block_number: BlockNumber,
+        &self,
+        block_hash: BlockHash,
         block_header: BlockHeader,
     ) -> Result<(), StoreError> {
-        self.headers.insert(block_number, block_header);
-        Ok(())
+        self.apply(WriteOp::AddBlockHeader(block_hash, block_header))
     }
 
     fn add_block_body(
-        &mut self,
-        block_number: BlockNumber,
+        &self,
+        block_hash: BlockHash,
         block_body: BlockBody,
     ) -> Result<(), StoreError> {
-        self.bodies.insert(block_number, block_body);
-        Ok(())
+        self.apply(WriteOp::AddBlockBody(block_hash, block_body))
+    }
+
+    fn remove_block_body(&self, block_hash: BlockHash) -> Result<(), StoreError> {
+        self.apply(WriteOp::RemoveBlockBody(block_hash))
     }
 
     fn add_block_number(
-        &mut self,
+        &self,
         block_hash: BlockHash,
         block_number: BlockNumber,
     ) -> Result<(), StoreError> {
-        self.block_numbers.insert(block_hash, block_number);
-        Ok(())
+        self.apply(WriteOp::AddBlockNumber(block_hash, block_number))
     }
 
     fn get_block_number(&self, block_hash: BlockHash) -> Result<Option<BlockNumber>, StoreError> {
-        Ok(self.block_numbers.get(&block_hash).copied())
+        Ok(self.inner().block_numbers.get(&block_hash).copied())
+    }
+
+    fn add_canonical_block_hash(
+        &self,
+        block_number: BlockNumber,
+        block_hash: BlockHash,
+    ) -> Result<(), StoreError> {
+        self.apply(WriteOp::AddCanonicalBlockHash(block_number, block_hash))
+    }
+
+    fn get_canonical_block_hash(
+        &self,
+        block_number: BlockNumber,
+    ) -> Result<Option<BlockHash>, StoreError> {
+        Ok(self.inner().canonical_hashes.get(&block_number).copied())
+    }
+
+    fn remove_canonical_block_hash(&self, block_number: BlockNumber) -> Result<(), StoreError> {
+        self.apply(WriteOp::RemoveCanonicalBlockHash(block_number))
     }
 
     fn add_transaction_location(
-        &mut self,
+        &self,
         transaction_hash: H256,
         block_number: BlockNumber,
         index: Index,
     ) -> Result<(), StoreError> {
-        self.transaction_locations
-            .insert(transaction_hash, (block_number, index));
-        Ok(())
+        self.apply(WriteOp::AddTransactionLocation(
+            transaction_hash,
+            block_number,
+            index,
+        ))
     }
 
     fn get_transaction_location(
         &self,
         transaction_hash: H256,
     ) -> Result<Option<(BlockNumber, Index)>, StoreError> {
-        Ok(self.transaction_locations.get(&transaction_hash).copied())
+        Ok(self
+            .inner()
+            .transaction_locations
+            .get(&transaction_hash)
+            .copied())
+    }
+
+    fn remove_transaction_location(&self, transaction_hash: H256) -> Result<(), StoreError> {
+        self.apply(WriteOp::RemoveTransactionLocation(transaction_hash))
     }
 
     fn add_receipt(
-        &mut self,
-        block_number: BlockNumber,
+        &self,
+        block_hash: BlockHash,
         index: Index,
         receipt: Receipt,
     ) -> Result<(), StoreError> {
-        let entry = self.receipts.entry(block_number).or_default();
-        entry.insert(index, receipt);
-        Ok(())
+        self.apply(WriteOp::AddReceipt(block_hash, index, receipt))
+    }
+
+    fn remove_receipts(&self, block_hash: BlockHash) -> Result<(), StoreError> {
+        self.apply(WriteOp::RemoveReceipts(block_hash))
     }
 
-    fn get_receipt(
+    fn get_receipt_by_hash(
         &self,
-        block_number: BlockNumber,
+        block_hash: BlockHash,
         index: Index,
     ) -> Result<Option<Receipt>, StoreError> {
         Ok(self
+            .inner()
             .receipts
-            .get(&block_number)
+            .get(&block_hash)
             .and_then(|entry| entry.get(&index))
             .cloned())
     }
 
-    fn add_account_code(&mut self, code_hash: H256, code: Bytes) -> Result<(), StoreError> {
-        self.account_codes.insert(code_hash, code);
-        Ok(())
+    fn add_account_code(&self, code_hash: H256, code: Bytes) -> Result<(), StoreError> {
+        self.apply(WriteOp::AddAccountCode(code_hash, code))
     }
 
     fn get_account_code(&self, code_hash: H256) -> Result<Option<Bytes>, StoreError> {
-        Ok(self.account_codes.get(&code_hash).cloned())
+        Ok(self.inner().account_codes.get(&code_hash).cloned())
     }
 
     fn add_storage_at(
-        &mut self,
+        &self,
         address: Address,
         storage_key: H256,
         storage_value: H256,
     ) -> Result<(), StoreError> {
-        let entry = self.account_storages.entry(address).or_default();
-        entry.insert(storage_key, storage_value);
-        Ok(())
+        self.apply(WriteOp::AddStorageAt(address, storage_key, storage_value))
     }
 
     fn get_storage_at(
@@ -159,23 +380,261 @@ impl StoreEngine for Store {
         storage_key: H256,
     ) -> Result<Option<H256>, StoreError> {
         Ok(self
+            .inner()
             .account_storages
             .get(&address)
             .and_then(|entry| entry.get(&storage_key).cloned()))
     }
 
-    fn remove_account_storage(&mut self, address: Address) -> Result<(), StoreError> {
-        self.account_storages.remove(&address);
-        Ok(())
+    fn remove_storage_at(&self, address: Address, storage_key: H256) -> Result<(), StoreError> {
+        self.apply(WriteOp::RemoveStorageAt(address, storage_key))
     }
 
-    fn update_chain_id(&mut self, chain_id: U256) -> Result<(), StoreError> {
-        self.chain_data.chain_id.replace(chain_id);
-        Ok(())
+    fn remove_account_storage(&self, address: Address) -> Result<(), StoreError> {
+        self.apply(WriteOp::RemoveAccountStorage(address))
+    }
+
+    fn get_account_infos(&self) -> Result<Vec<(Address, AccountInfo)>, StoreError> {
+        Ok(self
+            .inner()
+            .account_infos
+            .iter()
+            .map(|(address, info)| (*address, info.clone()))
+            .collect())
+    }
+
+    fn get_account_storage(&self, address: Address) -> Result<HashMap<H256, H256>, StoreError> {
+        Ok(self
+            .inner()
+            .account_storages
+            .get(&address)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn add_account_info_history(
+        &self,
+        block_number: BlockNumber,
+        address: Address,
+        account_info: Option<AccountInfo>,
+    ) -> Result<(), StoreError> {
+        self.apply(WriteOp::AddAccountInfoHistory(
+            block_number,
+            address,
+            account_info,
+        ))
+    }
+
+    fn get_account_info_history(
+        &self,
+        block_number: BlockNumber,
+        address: Address,
+    ) -> Result<Option<Option<AccountInfo>>, StoreError> {
+        Ok(self
+            .inner()
+            .account_info_history
+            .get(&block_number)
+            .and_then(|entry| entry.get(&address).cloned()))
+    }
+
+    fn add_storage_history(
+        &self,
+        block_number: BlockNumber,
+        address: Address,
+        storage_key: H256,
+        storage_value: H256,
+    ) -> Result<(), StoreError> {
+        self.apply(WriteOp::AddStorageHistory(
+            block_number,
+            address,
+            storage_key,
+            storage_value,
+        ))
+    }
+
+    fn get_storage_history(
+        &self,
+        block_number: BlockNumber,
+        address: Address,
+        storage_key: H256,
+    ) -> Result<Option<H256>, StoreError> {
+        Ok(self
+            .inner()
+            .storage_history
+            .get(&block_number)
+            .and_then(|entry| entry.get(&address))
+            .and_then(|entry| entry.get(&storage_key).cloned()))
+    }
+
+    fn get_block_account_info_history(
+        &self,
+        block_number: BlockNumber,
+    ) -> Result<Vec<(Address, Option<AccountInfo>)>, StoreError> {
+        Ok(self
+            .inner()
+            .account_info_history
+            .get(&block_number)
+            .map(|entry| {
+                entry
+                    .iter()
+                    .map(|(address, info)| (*address, info.clone()))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    fn get_block_storage_history(
+        &self,
+        block_number: BlockNumber,
+    ) -> Result<Vec<(Address, H256, H256)>, StoreError> {
+        Ok(self
+            .inner()
+            .storage_history
+            .get(&block_number)
+            .map(|entry| {
+                entry
+                    .iter()
+                    .flat_map(|(address, slots)| {
+                        slots
+                            .iter()
+                            .map(move |(key, value)| (*address, *key, *value))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    fn remove_state_history(&self, block_number: BlockNumber) -> Result<(), StoreError> {
+        self.apply(WriteOp::RemoveStateHistory(block_number))
+    }
+
+    fn get_account_info_at_block(
+        &self,
+        block_number: BlockNumber,
+        address: Address,
+    ) -> Result<Option<AccountInfo>, StoreError> {
+        // The change and the current state are read under the same lock, so that no write can
+        // take place in between
+        let store = self.inner();
+        let change_block_number = store
+            .account_history_blocks
+            .get(&address)
+            .and_then(|blocks| blocks.range(block_number + 1..).next());
+        Ok(match change_block_number {
+            Some(change_block_number) => store
+                .account_info_history
+                .get(change_block_number)
+                .and_then(|entry| entry.get(&address).cloned())
+                .flatten(),
+            None => store.account_infos.get(&address).cloned(),
+        })
+    }
+
+    fn get_storage_at_block(
+        &self,
+        block_number: BlockNumber,
+        address: Address,
+        storage_key: H256,
+    ) -> Result<Option<H256>, StoreError> {
+        let store = self.inner();
+        let change_block_number = store
+            .storage_history_blocks
+            .get(&(address, storage_key))
+            .and_then(|blocks| blocks.range(block_number + 1..).next());
+        let slots = match change_block_number {
+            Some(change_block_number) => store
+                .storage_history
+                .get(change_block_number)
+                .and_then(|entry| entry.get(&address)),
+            None => store.account_storages.get(&address),
+        };
+        Ok(slots.and_then(|slots| slots.get(&storage_key).cloned()))
+    }
+
+    fn add_trie_nodes(&self, nodes: Vec<(H256, Vec<u8>)>) -> Result<(), StoreError> {
+        self.apply(WriteOp::AddTrieNodes(nodes))
+    }
+
+    fn get_trie_node(&self, node_hash: H256) -> Result<Option<Vec<u8>>, StoreError> {
+        Ok(self.inner().trie_nodes.get(&node_hash).cloned())
+    }
+
+    fn update_chain_id(&self, chain_id: U256) -> Result<(), StoreError> {
+        self.apply(WriteOp::UpdateChainId(chain_id))
     }
 
     fn get_chain_id(&self) -> Result<Option<U256>, StoreError> {
-        Ok(self.chain_data.chain_id)
+        Ok(self.inner().chain_data.chain_id)
+    }
+
+    fn update_chain_config(&self, chain_config: &ChainConfig) -> Result<(), StoreError> {
+        self.apply(WriteOp::UpdateChainConfig(chain_config.clone()))
+    }
+
+    fn get_chain_config(&self) -> Result<Option<ChainConfig>, StoreError> {
+        Ok(self.inner().chain_data.chain_config.clone())
+    }
+
+    fn update_latest_block_number(&self, block_number: BlockNumber) -> Result<(), StoreError> {
+        self.apply(WriteOp::UpdateLatestBlockNumber(block_number))
+    }
+
+    fn get_latest_block_number(&self) -> Result<Option<BlockNumber>, StoreError> {
+        Ok(self.inner().chain_data.latest_block_number)
+    }
+
+    fn update_pruned_block_number(&self, block_number: BlockNumber) -> Result<(), StoreError> {
+        self.apply(WriteOp::UpdatePrunedBlockNumber(block_number))
+    }
+
+    fn get_pruned_block_number(&self) -> Result<Option<BlockNumber>, StoreError> {
+        Ok(self.inner().chain_data.pruned_block_number)
+    }
+
+    fn update_finalized_block_number(&self, block_number: BlockNumber) -> Result<(), StoreError> {
+        self.apply(WriteOp::UpdateFinalizedBlockNumber(block_number))
+    }
+
+    fn get_finalized_block_number(&self) -> Result<Option<BlockNumber>, StoreError> {
+        Ok(self.inner().chain_data.finalized_block_number)
+    }
+
+    fn update_safe_block_number(&self, block_number: BlockNumber) -> Result<(), StoreError> {
+        self.apply(WriteOp::UpdateSafeBlockNumber(block_number))
+    }
+
+    fn get_safe_block_number(&self) -> Result<Option<BlockNumber>, StoreError> {
+        Ok(self.inner().chain_data.safe_block_number)
+    }
+
+    fn update_state_root(&self, state_root: H256) -> Result<(), StoreError> {
+        self.apply(WriteOp::UpdateStateRoot(state_root))
+    }
+
+    fn get_state_root(&self) -> Result<Option<H256>, StoreError> {
+        Ok(self.inner().chain_data.state_root)
+    }
+
+    fn add_payload(&self, payload_id: u64, payload: BuiltPayload) -> Result<(), StoreError> {
+        self.apply(WriteOp::AddPayload(payload_id, payload))
+    }
+
+    fn get_payload(&self, payload_id: u64) -> Result<Option<BuiltPayload>, StoreError> {
+        Ok(self.inner().payloads.get(&payload_id).cloned())
+    }
+}
+
+/// Removes a block from the blocks that changed an account or storage slot
+fn remove_history_block<K: Eq + std::hash::Hash>(
+    history_blocks: &mut HashMap<K, BTreeSet<BlockNumber>>,
+    key: K,
+    block_number: BlockNumber,
+) {
+    if let Some(blocks) = history_blocks.get_mut(&key) {
+        blocks.remove(&block_number);
+        if blocks.is_empty() {
+            history_blocks.remove(&key);
+        }
     }
 }
 
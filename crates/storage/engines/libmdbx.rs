@@ -1,23 +1,26 @@
 use super::api::StoreEngine;
+use super::batch::{WriteBatch, WriteOp};
 use crate::error::StoreError;
 use crate::rlp::{
     AccountCodeHashRLP, AccountCodeRLP, AccountInfoRLP, AddressRLP, BlockBodyRLP, BlockHashRLP,
-    BlockHeaderRLP, ReceiptRLP, TransactionHashRLP,
+    BlockHeaderRLP, BuiltPayloadRLP, ReceiptRLP, TransactionHashRLP,
 };
 use anyhow::Result;
 use bytes::Bytes;
 use ethereum_rust_core::rlp::decode::RLPDecode;
 use ethereum_rust_core::rlp::encode::RLPEncode;
 use ethereum_rust_core::types::{
-    AccountInfo, BlockBody, BlockHash, BlockHeader, BlockNumber, Index, Receipt,
+    AccountInfo, BlockBody, BlockHash, BlockHeader, BlockNumber, BuiltPayload, ChainConfig, Index,
+    Receipt,
 };
 use ethereum_types::{Address, H256, U256};
 use libmdbx::orm::{Decodable, Encodable};
 use libmdbx::{
     dupsort,
-    orm::{table, Database},
-    table_info,
+    orm::{table, Database, Transaction},
+    table_info, RW,
 };
+use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
 use std::path::Path;
 
@@ -28,7 +31,7 @@ pub struct Store {
 impl Store {
     pub fn new(path: &str) -> Result<Self, StoreError> {
         Ok(Self {
-            db: init_db(Some(path)),
+            db: init_db(Some(path))?,
         })
     }
 
@@ -66,8 +69,20 @@ impl Store {
 }
 
 impl StoreEngine for Store {
+    fn write_batch(&self, batch: WriteBatch) -> Result<(), StoreError> {
+        let txn = self
+            .db
+            .begin_readwrite()
+            .map_err(StoreError::LibmdbxError)?;
+        for op in batch.ops {
+            apply_write(&txn, op)?;
+        }
+        // Nothing is written unless every write of the batch succeeds
+        txn.commit().map_err(StoreError::LibmdbxError)
+    }
+
     fn add_account_info(
-        &mut self,
+        &self,
         address: Address,
         account_info: AccountInfo,
     ) -> Result<(), StoreError> {
@@ -78,42 +93,46 @@ impl StoreEngine for Store {
         Ok(self.read::<AccountInfos>(address.into())?.map(|a| a.to()))
     }
 
-    fn remove_account_info(&mut self, address: Address) -> Result<(), StoreError> {
+    fn remove_account_info(&self, address: Address) -> Result<(), StoreError> {
         self.remove::<AccountInfos>(address.into())
     }
 
     fn add_block_header(
-        &mut self,
-        block_number: BlockNumber,
+        &self,
+        block_hash: BlockHash,
         block_header: BlockHeader,
     ) -> std::result::Result<(), StoreError> {
-        self.write::<Headers>(block_number, block_header.into())
+        self.write::<Headers>(block_hash.into(), block_header.into())
     }
 
-    fn get_block_header(
+    fn get_block_header_by_hash(
         &self,
-        block_number: BlockNumber,
+        block_hash: BlockHash,
     ) -> Result<Option<BlockHeader>, StoreError> {
-        Ok(self.read::<Headers>(block_number)?.map(|a| a.to()))
+        Ok(self.read::<Headers>(block_hash.into())?.map(|a| a.to()))
     }
 
     fn add_block_body(
-        &mut self,
-        block_number: BlockNumber,
+        &self,
+        block_hash: BlockHash,
         block_body: BlockBody,
     ) -> std::result::Result<(), StoreError> {
-        self.write::<Bodies>(block_number, block_body.into())
+        self.write::<Bodies>(block_hash.into(), block_body.into())
+    }
+
+    fn remove_block_body(&self, block_hash: BlockHash) -> Result<(), StoreError> {
+        self.remove::<Bodies>(block_hash.into())
     }
 
-    fn get_block_body(
+    fn get_block_body_by_hash(
         &self,
-        block_number: BlockNumber,
+        block_hash: BlockHash,
     ) -> std::result::Result<Option<BlockBody>, StoreError> {
-        Ok(self.read::<Bodies>(block_number)?.map(|b| b.to()))
+        Ok(self.read::<Bodies>(block_hash.into())?.map(|b| b.to()))
     }
 
     fn add_block_number(
-        &mut self,
+        &self,
         block_hash: BlockHash,
         block_number: BlockNumber,
     ) -> std::result::Result<(), StoreError> {
@@ -127,7 +146,28 @@ impl StoreEngine for Store {
         self.read::<BlockNumbers>(block_hash.into())
     }
 
-    fn add_account_code(&mut self, code_hash: H256, code: Bytes) -> Result<(), StoreError> {
+    fn add_canonical_block_hash(
+        &self,
+        block_number: BlockNumber,
+        block_hash: BlockHash,
+    ) -> Result<(), StoreError> {
+        self.write::<CanonicalBlockHashes>(block_number, block_hash.into())
+    }
+
+    fn get_canonical_block_hash(
+        &self,
+        block_number: BlockNumber,
+    ) -> Result<Option<BlockHash>, StoreError> {
+        Ok(self
+            .read::<CanonicalBlockHashes>(block_number)?
+            .map(|h| h.to()))
+    }
+
+    fn remove_canonical_block_hash(&self, block_number: BlockNumber) -> Result<(), StoreError> {
+        self.remove::<CanonicalBlockHashes>(block_number)
+    }
+
+    fn add_account_code(&self, code_hash: H256, code: Bytes) -> Result<(), StoreError> {
         self.write::<AccountCodes>(code_hash.into(), code.into())
     }
 
@@ -136,26 +176,35 @@ impl StoreEngine for Store {
     }
 
     fn add_receipt(
-        &mut self,
-        block_number: BlockNumber,
+        &self,
+        block_hash: BlockHash,
         index: Index,
         receipt: Receipt,
     ) -> Result<(), StoreError> {
-        self.write::<Receipts>((block_number, index), receipt.into())
+        self.write::<Receipts>((block_hash.0, index), receipt.into())
     }
 
-    fn get_receipt(
+    fn remove_receipts(&self, block_hash: BlockHash) -> Result<(), StoreError> {
+        let txn = self
+            .db
+            .begin_readwrite()
+            .map_err(StoreError::LibmdbxError)?;
+        delete_receipts(&txn, block_hash)?;
+        txn.commit().map_err(StoreError::LibmdbxError)
+    }
+
+    fn get_receipt_by_hash(
         &self,
-        block_number: BlockNumber,
+        block_hash: BlockHash,
         index: Index,
     ) -> Result<Option<Receipt>, StoreError> {
         Ok(self
-            .read::<Receipts>((block_number, index))?
+            .read::<Receipts>((block_hash.0, index))?
             .map(|r| r.to()))
     }
 
     fn add_transaction_location(
-        &mut self,
+        &self,
         transaction_hash: H256,
         block_number: BlockNumber,
         index: Index,
@@ -170,13 +219,22 @@ impl StoreEngine for Store {
         self.read::<TransactionLocations>(transaction_hash.into())
     }
 
+    fn remove_transaction_location(&self, transaction_hash: H256) -> Result<(), StoreError> {
+        self.remove::<TransactionLocations>(transaction_hash.into())
+    }
+
     fn add_storage_at(
-        &mut self,
+        &self,
         address: Address,
         storage_key: H256,
         storage_value: H256,
     ) -> Result<(), StoreError> {
-        self.write::<AccountStorages>(address.into(), (storage_key.into(), storage_value.into()))
+        let txn = self
+            .db
+            .begin_readwrite()
+            .map_err(StoreError::LibmdbxError)?;
+        write_storage_at(&txn, address, storage_key, storage_value)?;
+        txn.commit().map_err(StoreError::LibmdbxError)
     }
 
     fn get_storage_at(
@@ -189,17 +247,235 @@ impl StoreEngine for Store {
         let mut cursor = txn
             .cursor::<AccountStorages>()
             .map_err(StoreError::LibmdbxError)?;
+        // The cursor will be placed on the closest storage key if the requested one is not present
         Ok(cursor
             .seek_value(address.into(), storage_key.into())
             .map_err(StoreError::LibmdbxError)?
+            .filter(|(key, _)| key.0 == storage_key.0)
             .map(|s| s.1.into()))
     }
 
-    fn remove_account_storage(&mut self, address: Address) -> Result<(), StoreError> {
+    fn remove_storage_at(&self, address: Address, storage_key: H256) -> Result<(), StoreError> {
+        let txn = self
+            .db
+            .begin_readwrite()
+            .map_err(StoreError::LibmdbxError)?;
+        delete_storage_at(&txn, address, storage_key)?;
+        txn.commit().map_err(StoreError::LibmdbxError)
+    }
+
+    fn remove_account_storage(&self, address: Address) -> Result<(), StoreError> {
         self.remove::<AccountStorages>(address.into())
     }
 
-    fn update_chain_id(&mut self, chain_id: U256) -> Result<(), StoreError> {
+    fn get_account_infos(&self) -> Result<Vec<(Address, AccountInfo)>, StoreError> {
+        let txn = self.db.begin_read().map_err(StoreError::LibmdbxError)?;
+        let cursor = txn
+            .cursor::<AccountInfos>()
+            .map_err(StoreError::LibmdbxError)?;
+        cursor
+            .walk(None)
+            .map(|entry| {
+                entry
+                    .map(|(address, info)| (address.to(), info.to()))
+                    .map_err(StoreError::LibmdbxError)
+            })
+            .collect()
+    }
+
+    fn get_account_storage(&self, address: Address) -> Result<HashMap<H256, H256>, StoreError> {
+        let txn = self.db.begin_read().map_err(StoreError::LibmdbxError)?;
+        let cursor = txn
+            .cursor::<AccountStorages>()
+            .map_err(StoreError::LibmdbxError)?;
+        cursor
+            .walk_key(address.into(), None)
+            .map(|entry| {
+                entry
+                    .map(|(key, value)| (H256(key.0), value.into()))
+                    .map_err(StoreError::LibmdbxError)
+            })
+            .collect()
+    }
+
+    fn add_account_info_history(
+        &self,
+        block_number: BlockNumber,
+        address: Address,
+        account_info: Option<AccountInfo>,
+    ) -> Result<(), StoreError> {
+        let txn = self
+            .db
+            .begin_readwrite()
+            .map_err(StoreError::LibmdbxError)?;
+        apply_write(
+            &txn,
+            WriteOp::AddAccountInfoHistory(block_number, address, account_info),
+        )?;
+        txn.commit().map_err(StoreError::LibmdbxError)
+    }
+
+    fn get_account_info_history(
+        &self,
+        block_number: BlockNumber,
+        address: Address,
+    ) -> Result<Option<Option<AccountInfo>>, StoreError> {
+        self.read::<AccountInfoHistory>((block_number, address.into()))?
+            .map(|value| decode_account_info_history(&value))
+            .transpose()
+    }
+
+    fn add_storage_history(
+        &self,
+        block_number: BlockNumber,
+        address: Address,
+        storage_key: H256,
+        storage_value: H256,
+    ) -> Result<(), StoreError> {
+        let txn = self
+            .db
+            .begin_readwrite()
+            .map_err(StoreError::LibmdbxError)?;
+        apply_write(
+            &txn,
+            WriteOp::AddStorageHistory(block_number, address, storage_key, storage_value),
+        )?;
+        txn.commit().map_err(StoreError::LibmdbxError)
+    }
+
+    fn get_storage_history(
+        &self,
+        block_number: BlockNumber,
+        address: Address,
+        storage_key: H256,
+    ) -> Result<Option<H256>, StoreError> {
+        let txn = self.db.begin_read().map_err(StoreError::LibmdbxError)?;
+        let mut cursor = txn
+            .cursor::<StorageHistory>()
+            .map_err(StoreError::LibmdbxError)?;
+        Ok(cursor
+            .seek_value((block_number, address.into()), storage_key.into())
+            .map_err(StoreError::LibmdbxError)?
+            .filter(|(key, _)| key.0 == storage_key.0)
+            .map(|s| s.1.into()))
+    }
+
+    fn get_block_account_info_history(
+        &self,
+        block_number: BlockNumber,
+    ) -> Result<Vec<(Address, Option<AccountInfo>)>, StoreError> {
+        let txn = self.db.begin_read().map_err(StoreError::LibmdbxError)?;
+        let cursor = txn
+            .cursor::<AccountInfoHistory>()
+            .map_err(StoreError::LibmdbxError)?;
+        let mut history = Vec::new();
+        // Entries are keyed by block number first, so the ones for the block are contiguous
+        for entry in cursor.walk(Some((block_number, AddressBytes([0; 20])))) {
+            let ((entry_block_number, address), value) = entry.map_err(StoreError::LibmdbxError)?;
+            if entry_block_number != block_number {
+                break;
+            }
+            history.push((
+                Address::from(address.0),
+                decode_account_info_history(&value)?,
+            ));
+        }
+        Ok(history)
+    }
+
+    fn get_block_storage_history(
+        &self,
+        block_number: BlockNumber,
+    ) -> Result<Vec<(Address, H256, H256)>, StoreError> {
+        let txn = self.db.begin_read().map_err(StoreError::LibmdbxError)?;
+        let cursor = txn
+            .cursor::<StorageHistory>()
+            .map_err(StoreError::LibmdbxError)?;
+        let mut history = Vec::new();
+        for entry in cursor.walk(Some((block_number, AddressBytes([0; 20])))) {
+            let ((entry_block_number, address), (key, value)) =
+                entry.map_err(StoreError::LibmdbxError)?;
+            if entry_block_number != block_number {
+                break;
+            }
+            history.push((Address::from(address.0), H256(key.0), value.into()));
+        }
+        Ok(history)
+    }
+
+    fn remove_state_history(&self, block_number: BlockNumber) -> Result<(), StoreError> {
+        let txn = self
+            .db
+            .begin_readwrite()
+            .map_err(StoreError::LibmdbxError)?;
+        delete_state_history(&txn, block_number)?;
+        txn.commit().map_err(StoreError::LibmdbxError)
+    }
+
+    fn get_account_info_at_block(
+        &self,
+        block_number: BlockNumber,
+        address: Address,
+    ) -> Result<Option<AccountInfo>, StoreError> {
+        // The cursor will be placed on the first change made to the account after the block, if
+        // there is one
+        let change = {
+            let txn = self.db.begin_read().map_err(StoreError::LibmdbxError)?;
+            let mut cursor = txn
+                .cursor::<AccountInfoHistoryByAddress>()
+                .map_err(StoreError::LibmdbxError)?;
+            cursor
+                .seek_closest((address.into(), block_number + 1))
+                .map_err(StoreError::LibmdbxError)?
+        };
+        match change {
+            Some(((entry_address, _), value)) if entry_address.0 == address.0 => {
+                decode_account_info_history(&value)
+            }
+            _ => self.get_account_info(address),
+        }
+    }
+
+    fn get_storage_at_block(
+        &self,
+        block_number: BlockNumber,
+        address: Address,
+        storage_key: H256,
+    ) -> Result<Option<H256>, StoreError> {
+        // The cursor will be placed on the first change made to the slot after the block, if
+        // there is one
+        let change = {
+            let txn = self.db.begin_read().map_err(StoreError::LibmdbxError)?;
+            let mut cursor = txn
+                .cursor::<StorageHistoryBySlot>()
+                .map_err(StoreError::LibmdbxError)?;
+            cursor
+                .seek_value((address.into(), storage_key.into()), block_number + 1)
+                .map_err(StoreError::LibmdbxError)?
+        };
+        match change {
+            Some((_, value)) => Ok(Some(value.into())),
+            None => self.get_storage_at(address, storage_key),
+        }
+    }
+
+    fn add_trie_nodes(&self, nodes: Vec<(H256, Vec<u8>)>) -> Result<(), StoreError> {
+        let txn = self
+            .db
+            .begin_readwrite()
+            .map_err(StoreError::LibmdbxError)?;
+        for (node_hash, node) in nodes {
+            txn.upsert::<TrieNodes>(node_hash.0, node)
+                .map_err(StoreError::LibmdbxError)?;
+        }
+        txn.commit().map_err(StoreError::LibmdbxError)
+    }
+
+    fn get_trie_node(&self, node_hash: H256) -> Result<Option<Vec<u8>>, StoreError> {
+        self.read::<TrieNodes>(node_hash.0)
+    }
+
+    fn update_chain_id(&self, chain_id: U256) -> Result<(), StoreError> {
         self.write::<ChainData>(ChainDataIndex::ChainId, chain_id.encode_to_vec())
     }
 
@@ -211,6 +487,315 @@ impl StoreEngine for Store {
                 .map_err(|_| StoreError::DecodeError),
         }
     }
+
+    fn update_chain_config(&self, chain_config: &ChainConfig) -> Result<(), StoreError> {
+        // Store the chain config as json as it has too many optional fields to be rlp encoded
+        let bytes =
+            serde_json::to_vec(chain_config).map_err(|e| StoreError::Custom(e.to_string()))?;
+        self.write::<ChainData>(ChainDataIndex::ChainConfig, bytes)
+    }
+
+    fn get_chain_config(&self) -> Result<Option<ChainConfig>, StoreError> {
+        match self.read::<ChainData>(ChainDataIndex::ChainConfig)? {
+            None => Ok(None),
+            Some(ref bytes) => serde_json::from_slice(bytes)
+                .map(Some)
+                .map_err(|_| StoreError::DecodeError),
+        }
+    }
+
+    fn update_latest_block_number(&self, block_number: BlockNumber) -> Result<(), StoreError> {
+        self.write::<ChainData>(
+            ChainDataIndex::LatestBlockNumber,
+            block_number.encode_to_vec(),
+        )
+    }
+
+    fn get_latest_block_number(&self) -> Result<Option<BlockNumber>, StoreError> {
+        match self.read::<ChainData>(ChainDataIndex::LatestBlockNumber)? {
+            None => Ok(None),
+            Some(ref rlp) => RLPDecode::decode(rlp)
+                .map(Some)
+                .map_err(|_| StoreError::DecodeError),
+        }
+    }
+
+    fn update_pruned_block_number(&self, block_number: BlockNumber) -> Result<(), StoreError> {
+        self.write::<ChainData>(
+            ChainDataIndex::PrunedBlockNumber,
+            block_number.encode_to_vec(),
+        )
+    }
+
+    fn get_pruned_block_number(&self) -> Result<Option<BlockNumber>, StoreError> {
+        match self.read::<ChainData>(ChainDataIndex::PrunedBlockNumber)? {
+            None => Ok(None),
+            Some(ref rlp) => RLPDecode::decode(rlp)
+                .map(Some)
+                .map_err(|_| StoreError::DecodeError),
+        }
+    }
+
+    fn update_finalized_block_number(&self, block_number: BlockNumber) -> Result<(), StoreError> {
+        self.write::<ChainData>(
+            ChainDataIndex::FinalizedBlockNumber,
+            block_number.encode_to_vec(),
+        )
+    }
+
+    fn get_finalized_block_number(&self) -> Result<Option<BlockNumber>, StoreError> {
+        match self.read::<ChainData>(ChainDataIndex::FinalizedBlockNumber)? {
+            None => Ok(None),
+            Some(ref rlp) => RLPDecode::decode(rlp)
+                .map(Some)
+                .map_err(|_| StoreError::DecodeError),
+        }
+    }
+
+    fn update_safe_block_number(&self, block_number: BlockNumber) -> Result<(), StoreError> {
+        self.write::<ChainData>(
+            ChainDataIndex::SafeBlockNumber,
+            block_number.encode_to_vec(),
+        )
+    }
+
+    fn get_safe_block_number(&self) -> Result<Option<BlockNumber>, StoreError> {
+        match self.read::<ChainData>(ChainDataIndex::SafeBlockNumber)? {
+            None => Ok(None),
+            Some(ref rlp) => RLPDecode::decode(rlp)
+                .map(Some)
+                .map_err(|_| StoreError::DecodeError),
+        }
+    }
+
+    fn update_state_root(&self, state_root: H256) -> Result<(), StoreError> {
+        self.write::<ChainData>(ChainDataIndex::StateRoot, state_root.encode_to_vec())
+    }
+
+    fn get_state_root(&self) -> Result<Option<H256>, StoreError> {
+        match self.read::<ChainData>(ChainDataIndex::StateRoot)? {
+            None => Ok(None),
+            Some(ref rlp) => RLPDecode::decode(rlp)
+                .map(Some)
+                .map_err(|_| StoreError::DecodeError),
+        }
+    }
+
+    fn add_payload(&self, payload_id: u64, payload: BuiltPayload) -> Result<(), StoreError> {
+        self.write::<Payloads>(payload_id, payload.into())
+    }
+
+    fn get_payload(&self, payload_id: u64) -> Result<Option<BuiltPayload>, StoreError> {
+        Ok(self.read::<Payloads>(payload_id)?.map(|b| b.to()))
+    }
+}
+
+/// Applies a single write of a batch within the batch's transaction
+fn apply_write(txn: &Transaction<'_, RW>, op: WriteOp) -> Result<(), StoreError> {
+    match op {
+        WriteOp::AddAccountInfo(address, info) => {
+            txn.upsert::<AccountInfos>(address.into(), info.into())
+        }
+        WriteOp::RemoveAccountInfo(address) => {
+            txn.delete::<AccountInfos>(address.into(), None).map(|_| ())
+        }
+        WriteOp::AddAccountCode(code_hash, code) => {
+            txn.upsert::<AccountCodes>(code_hash.into(), code.into())
+        }
+        WriteOp::AddStorageAt(address, key, value) => {
+            return write_storage_at(txn, address, key, value)
+        }
+        WriteOp::RemoveStorageAt(address, key) => return delete_storage_at(txn, address, key),
+        WriteOp::RemoveAccountStorage(address) => txn
+            .delete::<AccountStorages>(address.into(), None)
+            .map(|_| ()),
+        WriteOp::AddBlockHeader(hash, header) => txn.upsert::<Headers>(hash.into(), header.into()),
+        WriteOp::AddBlockBody(hash, body) => txn.upsert::<Bodies>(hash.into(), body.into()),
+        WriteOp::RemoveBlockBody(hash) => txn.delete::<Bodies>(hash.into(), None).map(|_| ()),
+        WriteOp::AddBlockNumber(hash, number) => txn.upsert::<BlockNumbers>(hash.into(), number),
+        WriteOp::AddCanonicalBlockHash(number, hash) => {
+            txn.upsert::<CanonicalBlockHashes>(number, hash.into())
+        }
+        WriteOp::RemoveCanonicalBlockHash(number) => {
+            txn.delete::<CanonicalBlockHashes>(number, None).map(|_| ())
+        }
+        WriteOp::AddTransactionLocation(tx_hash, number, index) => {
+            txn.upsert::<TransactionLocations>(tx_hash.into(), (number, index))
+        }
+        WriteOp::RemoveTransactionLocation(tx_hash) => txn
+            .delete::<TransactionLocations>(tx_hash.into(), None)
+            .map(|_| ()),
+        WriteOp::AddReceipt(hash, index, receipt) => {
+            txn.upsert::<Receipts>((hash.0, index), receipt.into())
+        }
+        WriteOp::RemoveReceipts(hash) => return delete_receipts(txn, hash),
+        WriteOp::AddAccountInfoHistory(number, address, info) => {
+            // Accounts that didn't exist before the block are stored as an empty value
+            let value = info.map(|info| info.encode_to_vec()).unwrap_or_default();
+            txn.upsert::<AccountInfoHistory>((number, address.into()), value.clone())
+                .and_then(|_| {
+                    txn.upsert::<AccountInfoHistoryByAddress>((address.into(), number), value)
+                })
+        }
+        // Each slot is only recorded once per block, so there is no previous value to replace
+        WriteOp::AddStorageHistory(number, address, key, value) => txn
+            .upsert::<StorageHistory>((number, address.into()), (key.into(), value.into()))
+            .and_then(|_| {
+                txn.upsert::<StorageHistoryBySlot>(
+                    (address.into(), key.into()),
+                    (number, value.into()),
+                )
+            }),
+        WriteOp::RemoveStateHistory(number) => return delete_state_history(txn, number),
+        WriteOp::AddTrieNodes(nodes) => nodes
+            .into_iter()
+            .try_for_each(|(node_hash, node)| txn.upsert::<TrieNodes>(node_hash.0, node)),
+        WriteOp::UpdateChainId(chain_id) => {
+            txn.upsert::<ChainData>(ChainDataIndex::ChainId, chain_id.encode_to_vec())
+        }
+        WriteOp::UpdateChainConfig(config) => {
+            let bytes =
+                serde_json::to_vec(&config).map_err(|e| StoreError::Custom(e.to_string()))?;
+            txn.upsert::<ChainData>(ChainDataIndex::ChainConfig, bytes)
+        }
+        WriteOp::UpdateLatestBlockNumber(number) => {
+            txn.upsert::<ChainData>(ChainDataIndex::LatestBlockNumber, number.encode_to_vec())
+        }
+        WriteOp::UpdatePrunedBlockNumber(number) => {
+            txn.upsert::<ChainData>(ChainDataIndex::PrunedBlockNumber, number.encode_to_vec())
+        }
+        WriteOp::UpdateFinalizedBlockNumber(number) => {
+            txn.upsert::<ChainData>(ChainDataIndex::FinalizedBlockNumber, number.encode_to_vec())
+        }
+        WriteOp::UpdateSafeBlockNumber(number) => {
+            txn.upsert::<ChainData>(ChainDataIndex::SafeBlockNumber, number.encode_to_vec())
+        }
+        WriteOp::UpdateStateRoot(state_root) => {
+            txn.upsert::<ChainData>(ChainDataIndex::StateRoot, state_root.encode_to_vec())
+        }
+        WriteOp::AddPayload(payload_id, payload) => {
+            txn.upsert::<Payloads>(payload_id, payload.into())
+        }
+    }
+    .map_err(StoreError::LibmdbxError)
+}
+
+/// Writes a storage value within a read-write transaction
+fn write_storage_at(
+    txn: &Transaction<'_, RW>,
+    address: Address,
+    storage_key: H256,
+    storage_value: H256,
+) -> Result<(), StoreError> {
+    // Dupsort tables can hold many values for the same key, so we need to remove the previous
+    // value for this storage key (if any) before inserting the new one
+    delete_storage_at(txn, address, storage_key)?;
+    txn.upsert::<AccountStorages>(address.into(), (storage_key.into(), storage_value.into()))
+        .map_err(StoreError::LibmdbxError)
+}
+
+/// Removes a storage value (if present) within a read-write transaction
+fn delete_storage_at(
+    txn: &Transaction<'_, RW>,
+    address: Address,
+    storage_key: H256,
+) -> Result<(), StoreError> {
+    let mut cursor = txn
+        .cursor::<AccountStorages>()
+        .map_err(StoreError::LibmdbxError)?;
+    // The cursor will be placed on the closest storage key if the requested one is not present
+    if let Some((key, _)) = cursor
+        .seek_value(address.into(), storage_key.into())
+        .map_err(StoreError::LibmdbxError)?
+    {
+        if key.0 == storage_key.0 {
+            cursor.delete_current().map_err(StoreError::LibmdbxError)?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes all receipts of a block within a read-write transaction
+fn delete_receipts(txn: &Transaction<'_, RW>, block_hash: BlockHash) -> Result<(), StoreError> {
+    // Receipts are keyed by block hash first, so the ones for the block are contiguous
+    let first_key = (block_hash.0, 0);
+    let mut cursor = txn.cursor::<Receipts>().map_err(StoreError::LibmdbxError)?;
+    while let Some(((entry_block_hash, _), _)) = cursor
+        .seek_closest(first_key)
+        .map_err(StoreError::LibmdbxError)?
+    {
+        if entry_block_hash != block_hash.0 {
+            break;
+        }
+        cursor.delete_current().map_err(StoreError::LibmdbxError)?;
+    }
+    Ok(())
+}
+
+/// Removes the state history of a block within a read-write transaction
+fn delete_state_history(
+    txn: &Transaction<'_, RW>,
+    block_number: BlockNumber,
+) -> Result<(), StoreError> {
+    // Entries are keyed by block number first, so the ones for the block are contiguous
+    let first_key = (block_number, AddressBytes([0; 20]));
+    let mut cursor = txn
+        .cursor::<AccountInfoHistory>()
+        .map_err(StoreError::LibmdbxError)?;
+    while let Some(((entry_block_number, address), _)) = cursor
+        .seek_closest(first_key)
+        .map_err(StoreError::LibmdbxError)?
+    {
+        if entry_block_number != block_number {
+            break;
+        }
+        txn.delete::<AccountInfoHistoryByAddress>((address, block_number), None)
+            .map_err(StoreError::LibmdbxError)?;
+        cursor.delete_current().map_err(StoreError::LibmdbxError)?;
+    }
+    // The slots are read before removing them, as their index entries are found by value
+    let cursor = txn
+        .cursor::<StorageHistory>()
+        .map_err(StoreError::LibmdbxError)?;
+    let mut slots = Vec::new();
+    for entry in cursor.walk(Some(first_key)) {
+        let ((entry_block_number, address), (key, value)) =
+            entry.map_err(StoreError::LibmdbxError)?;
+        if entry_block_number != block_number {
+            break;
+        }
+        slots.push((address, key, value));
+    }
+    for (address, key, value) in slots {
+        txn.delete::<StorageHistoryBySlot>((address, key), Some((block_number, value)))
+            .map_err(StoreError::LibmdbxError)?;
+    }
+    let mut cursor = txn
+        .cursor::<StorageHistory>()
+        .map_err(StoreError::LibmdbxError)?;
+    while let Some(((entry_block_number, _), _)) = cursor
+        .seek_closest(first_key)
+        .map_err(StoreError::LibmdbxError)?
+    {
+        if entry_block_number != block_number {
+            break;
+        }
+        cursor
+            .delete_current_key()
+            .map_err(StoreError::LibmdbxError)?;
+    }
+    Ok(())
+}
+
+/// Decodes a value of the account info history, where accounts that didn't exist are stored as an
+/// empty value
+fn decode_account_info_history(value: &[u8]) -> Result<Option<AccountInfo>, StoreError> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    AccountInfo::decode(value)
+        .map(Some)
+        .map_err(|_| StoreError::DecodeError)
 }
 
 impl Debug for Store {
@@ -227,12 +812,17 @@ table!(
 );
 
 table!(
-    /// Block headers table.
-    ( Headers ) BlockNumber => BlockHeaderRLP
+    /// Canonical block number to hash table.
+    ( CanonicalBlockHashes ) BlockNumber => BlockHashRLP
+);
+
+table!(
+    /// Block headers table, holds the headers of every stored block, canonical or not.
+    ( Headers ) BlockHashRLP => BlockHeaderRLP
 );
 table!(
-    /// Block bodies table.
-    ( Bodies ) BlockNumber => BlockBodyRLP
+    /// Block bodies table, holds the bodies of every stored block, canonical or not.
+    ( Bodies ) BlockHashRLP => BlockBodyRLP
 );
 table!(
     /// Account infos table.
@@ -246,9 +836,9 @@ table!(
     /// Account codes table.
     ( AccountCodes ) AccountCodeHashRLP => AccountCodeRLP
 );
-dupsort!(
-    /// Receipts table.
-    ( Receipts ) (BlockNumber, Index)[Index] => ReceiptRLP
+table!(
+    /// Receipts table, keyed by the hash of the block and the index of the transaction.
+    ( Receipts ) ([u8; 32], Index) => ReceiptRLP
 );
 
 table!(
@@ -262,6 +852,41 @@ table!(
     ( ChainData ) ChainDataIndex => Vec<u8>
 );
 
+table!(
+    /// Locally built payloads table.
+    ( Payloads ) u64 => BuiltPayloadRLP
+);
+
+table!(
+    /// Account info history table, holds the rlp-encoded info each account had before being
+    /// modified by a block, or an empty value if the account didn't exist before it
+    ( AccountInfoHistory ) (BlockNumber, AddressBytes) => Vec<u8>
+);
+
+dupsort!(
+    /// Storage history table, holds the value each storage slot had before being modified by a block
+    ( StorageHistory ) (BlockNumber, AddressBytes) => (AccountStorageKeyBytes, AccountStorageValueBytes) [AccountStorageKeyBytes]
+);
+
+table!(
+    /// Account info history index, holds the entries of the account info history table keyed by
+    /// address first, so that the first change made to an account after a given block can be
+    /// found with a single seek
+    ( AccountInfoHistoryByAddress ) (AddressBytes, BlockNumber) => Vec<u8>
+);
+
+dupsort!(
+    /// Storage history index, holds the entries of the storage history table keyed by slot
+    /// first, so that the first change made to a slot after a given block can be found with a
+    /// single seek
+    ( StorageHistoryBySlot ) (AddressBytes, AccountStorageKeyBytes) => (BlockNumber, AccountStorageValueBytes) [BlockNumber]
+);
+
+table!(
+    /// Trie nodes table, holds the encoding of the nodes of the state and storage tries under their hash
+    ( TrieNodes ) [u8; 32] => Vec<u8>
+);
+
 // Storage values are stored as bytes instead of using their rlp encoding
 // As they are stored in a dupsort table, they need to have a fixed size, and encoding them doesn't preserve their size
 pub struct AccountStorageKeyBytes(pub [u8; 32]);
@@ -313,10 +938,40 @@ impl From<AccountStorageValueBytes> for H256 {
     }
 }
 
+// Addresses are stored as bytes when they are part of a composite key, as each part of the key needs to have a fixed size
+#[derive(Clone, Copy)]
+pub struct AddressBytes(pub [u8; 20]);
+
+impl Encodable for AddressBytes {
+    type Encoded = [u8; 20];
+
+    fn encode(self) -> Self::Encoded {
+        self.0
+    }
+}
+
+impl Decodable for AddressBytes {
+    fn decode(b: &[u8]) -> anyhow::Result<Self> {
+        Ok(AddressBytes(b.try_into()?))
+    }
+}
+
+impl From<Address> for AddressBytes {
+    fn from(value: Address) -> Self {
+        AddressBytes(value.0)
+    }
+}
+
 /// Represents the key for each unique value of the chain data stored in the db
-// (TODO: Remove this comment once full) Will store chain-specific data such as chain id and latest finalized/pending/safe block number
+// Stores chain-specific data such as chain id, chain config, the latest, finalized, safe and pruned block numbers and the current state root
 pub enum ChainDataIndex {
     ChainId = 0,
+    LatestBlockNumber = 1,
+    FinalizedBlockNumber = 2,
+    SafeBlockNumber = 3,
+    ChainConfig = 4,
+    StateRoot = 5,
+    PrunedBlockNumber = 6,
 }
 
 impl Encodable for ChainDataIndex {
@@ -327,11 +982,12 @@ impl Encodable for ChainDataIndex {
     }
 }
 
-/// Initializes a new database with the provided path. If the path is `None`, the database
-/// will be temporary.
-pub fn init_db(path: Option<impl AsRef<Path>>) -> Database {
+/// Initializes a new database with the provided path, or opens the existing one. If the path is
+/// `None`, the database will be temporary.
+pub fn init_db(path: Option<impl AsRef<Path>>) -> Result<Database, StoreError> {
     let tables = [
         table_info!(BlockNumbers),
+        table_info!(CanonicalBlockHashes),
         table_info!(Headers),
         table_info!(Bodies),
         table_info!(AccountInfos),
@@ -340,11 +996,17 @@ pub fn init_db(path: Option<impl AsRef<Path>>) -> Database {
         table_info!(Receipts),
         table_info!(TransactionLocations),
         table_info!(ChainData),
+        table_info!(Payloads),
+        table_info!(AccountInfoHistory),
+        table_info!(StorageHistory),
+        table_info!(AccountInfoHistoryByAddress),
+        table_info!(StorageHistoryBySlot),
+        table_info!(TrieNodes),
     ]
     .into_iter()
     .collect();
     let path = path.map(|p| p.as_ref().to_path_buf());
-    Database::create(path, &tables).unwrap()
+    Database::create(path, &tables).map_err(StoreError::LibmdbxError)
 }
 
 #[cfg(test)]
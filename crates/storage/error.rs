@@ -1,3 +1,5 @@
+use ethereum_rust_core::types::BlockNumber;
+use ethereum_types::H256;
 use thiserror::Error;
 
 // TODO improve errors
@@ -10,4 +12,16 @@ pub enum StoreError {
     LibmdbxError(anyhow::Error),
     #[error("{0}")]
     Custom(String),
+    #[error("Genesis mismatch: the database was initialized with genesis block {0:#x} but the supplied genesis block is {1:#x}")]
+    GenesisMismatch(H256, H256),
+    #[error("Chain config mismatch: the database was initialized with a different chain config")]
+    ChainConfigMismatch,
+    #[error("State for block {0} is not available")]
+    StateNotAvailable(BlockNumber),
+    #[error("Trie node {0:#x} not found")]
+    MissingTrieNode(H256),
+    #[error("Block {0:#x} not found")]
+    MissingBlock(H256),
+    #[error("Data of block {0} was pruned")]
+    PrunedBlock(BlockNumber),
 }
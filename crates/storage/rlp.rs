@@ -3,7 +3,7 @@ use std::marker::PhantomData;
 use bytes::Bytes;
 use ethereum_rust_core::{
     rlp::{decode::RLPDecode, encode::RLPEncode},
-    types::{AccountInfo, BlockBody, BlockHash, BlockHeader, Receipt},
+    types::{AccountInfo, BlockBody, BlockHash, BlockHeader, BuiltPayload, Receipt},
     Address, H256,
 };
 #[cfg(feature = "libmdbx")]
@@ -20,6 +20,9 @@ pub type BlockHashRLP = Rlp<BlockHash>;
 pub type BlockHeaderRLP = Rlp<BlockHeader>;
 pub type BlockBodyRLP = Rlp<BlockBody>;
 
+// Payload types
+pub type BuiltPayloadRLP = Rlp<BuiltPayload>;
+
 // Receipt types
 pub type ReceiptRLP = Rlp<Receipt>;
 
@@ -3,24 +3,93 @@ use self::engines::in_memory::Store as InMemoryStore;
 #[cfg(feature = "libmdbx")]
 use self::engines::libmdbx::Store as LibmdbxStore;
 use self::error::StoreError;
+use self::trie::{keccak, Trie, TrieNodes, EMPTY_TRIE_HASH};
 use bytes::Bytes;
-use engines::api::StoreEngine;
+use engines::{api::StoreEngine, batch::WriteBatch};
+use ethereum_rust_core::rlp::{decode::RLPDecode, encode::RLPEncode};
 use ethereum_rust_core::types::{
-    Account, AccountInfo, Block, BlockBody, BlockHash, BlockHeader, BlockNumber, Genesis, Index,
-    Receipt, Transaction,
+    Account, AccountInfo, AccountProof, AccountState, Block, BlockBody, BlockHash, BlockHeader,
+    BlockNumber, BuiltPayload, ChainConfig, Genesis, Index, Receipt, StorageProof, Transaction,
+    EMPTY_KECCACK_HASH,
 };
 use ethereum_types::{Address, H256, U256};
+use std::collections::{hash_map::Entry, HashMap};
 use std::fmt::Debug;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
 use tracing::info;
 
 mod engines;
 pub mod error;
 mod rlp;
+mod trie;
+
+/// Number of blocks before the latest one whose state can be queried by default
+pub const DEFAULT_STATE_HISTORY_WINDOW: u64 = 128;
+
+/// Maximum number of blocks pruned along with each new block, so that catching up after the
+/// pruning distance is shortened doesn't result in a huge write
+const MAX_PRUNED_BLOCKS_PER_BLOCK: u64 = 64;
+
+/// Determines which bodies, receipts and transaction index entries of past canonical blocks are
+/// kept in the store. Block headers and the state history are never pruned this way
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PruningMode {
+    /// Everything is kept
+    #[default]
+    Archive,
+    /// Only the given number of blocks before the latest one are kept, older ones are pruned as
+    /// new blocks are added. The genesis block is always kept
+    Distance(u64),
+}
 
+/// Handle to the store, which can be cloned and shared between threads
+/// Reads go straight to the engine and are never blocked by writes, each of them sees the store
+/// either before or after a write (the libmdbx engine runs every read in its own read-only
+/// transaction). Writers are serialized by a separate lock, so that a writer can prepare its
+/// writes from the current state without it changing in between
 #[derive(Debug, Clone)]
 pub struct Store {
-    engine: Arc<Mutex<dyn StoreEngine>>,
+    engine: Arc<dyn StoreEngine>,
+    writer: Arc<Mutex<()>>,
+    /// Serializes the updates to the chain made on top of the state they read, see `lock_chain`
+    chain_lock: Arc<Mutex<()>>,
+    /// Number of blocks before the latest one whose state can be queried, the state history of
+    /// older blocks is removed as new blocks are applied
+    state_history_window: u64,
+    pruning_mode: PruningMode,
+}
+
+/// Represents the changes an account undergoes after executing a block
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AccountUpdate {
+    pub address: Address,
+    /// The account was destroyed, so its info and storage must be removed before applying the rest of the update
+    pub removed: bool,
+    pub info: Option<AccountInfo>,
+    pub code: Option<Bytes>,
+    pub added_storage: HashMap<H256, H256>,
+}
+
+impl AccountUpdate {
+    /// Creates an AccountUpdate for an account that was removed
+    pub fn removed(address: Address) -> Self {
+        Self {
+            address,
+            removed: true,
+            ..Default::default()
+        }
+    }
+
+    /// Creates an AccountUpdate that sets the whole account
+    fn from_account(address: Address, account: Account) -> Self {
+        Self {
+            address,
+            removed: false,
+            info: Some(account.info),
+            code: Some(account.code),
+            added_storage: account.storage.into_iter().collect(),
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -38,89 +107,127 @@ impl Store {
         let store = match engine_type {
             #[cfg(feature = "libmdbx")]
             EngineType::Libmdbx => Self {
-                engine: Arc::new(Mutex::new(LibmdbxStore::new(path)?)),
+                engine: Arc::new(LibmdbxStore::new(path)?),
+                writer: Default::default(),
+                chain_lock: Default::default(),
+                state_history_window: DEFAULT_STATE_HISTORY_WINDOW,
+                pruning_mode: PruningMode::default(),
             },
             #[cfg(feature = "in_memory")]
             EngineType::InMemory => Self {
-                engine: Arc::new(Mutex::new(InMemoryStore::new()?)),
+                engine: Arc::new(InMemoryStore::new()?),
+                writer: Default::default(),
+                chain_lock: Default::default(),
+                state_history_window: DEFAULT_STATE_HISTORY_WINDOW,
+                pruning_mode: PruningMode::default(),
             },
         };
         info!("Started store engine");
         Ok(store)
     }
 
+    /// Sets the number of blocks before the latest one whose state can be queried
+    pub fn set_state_history_window(&mut self, state_history_window: u64) {
+        self.state_history_window = state_history_window;
+    }
+
+    /// Sets which data of past canonical blocks is kept, blocks are pruned as new ones are added
+    pub fn set_pruning_mode(&mut self, pruning_mode: PruningMode) {
+        self.pruning_mode = pruning_mode;
+    }
+
+    /// Locks the chain for an update that depends on its current state, such as importing a
+    /// block on top of the latest one or reorganizing the canonical chain
+    /// The lock must be held from the reads the update is based on until it is committed, so that
+    /// no other update can change the chain in between. Readers are not affected by it
+    pub fn lock_chain(&self) -> MutexGuard<'_, ()> {
+        self.chain_lock.lock().unwrap()
+    }
+
+    /// Performs a write on the engine, no other write can take place at the same time
+    fn write<T>(
+        &self,
+        write: impl FnOnce(&dyn StoreEngine) -> Result<T, StoreError>,
+    ) -> Result<T, StoreError> {
+        let _writer = self.writer.lock().unwrap();
+        write(&*self.engine)
+    }
+
+    /// Builds a write batch from the current state of the store and commits it atomically
+    /// No other write can take place in between, readers keep accessing the store meanwhile
+    fn commit<T>(
+        &self,
+        build: impl FnOnce(&dyn StoreEngine, &mut WriteBatch) -> Result<T, StoreError>,
+    ) -> Result<T, StoreError> {
+        let _writer = self.writer.lock().unwrap();
+        let mut batch = WriteBatch::new();
+        let result = build(&*self.engine, &mut batch)?;
+        self.engine.write_batch(batch)?;
+        Ok(result)
+    }
+
     pub fn add_account_info(
         &self,
         address: Address,
         account_info: AccountInfo,
     ) -> Result<(), StoreError> {
-        self.engine
-            .clone()
-            .lock()
-            .unwrap()
-            .add_account_info(address, account_info)
+        self.write(|engine| engine.add_account_info(address, account_info))
     }
 
     pub fn get_account_info(&self, address: Address) -> Result<Option<AccountInfo>, StoreError> {
-        self.engine
-            .clone()
-            .lock()
-            .unwrap()
-            .get_account_info(address)
+        self.engine.get_account_info(address)
     }
 
     pub fn remove_account_info(&self, address: Address) -> Result<(), StoreError> {
-        self.engine
-            .clone()
-            .lock()
-            .unwrap()
-            .remove_account_info(address)
+        self.write(|engine| engine.remove_account_info(address))
     }
 
     pub fn add_block_header(
         &self,
-        block_number: BlockNumber,
+        block_hash: BlockHash,
         block_header: BlockHeader,
     ) -> Result<(), StoreError> {
-        self.engine
-            .clone()
-            .lock()
-            .unwrap()
-            .add_block_header(block_number, block_header)
+        self.write(|engine| engine.add_block_header(block_hash, block_header))
     }
 
+    /// Obtain the header of the canonical block with the given number
     pub fn get_block_header(
         &self,
         block_number: BlockNumber,
     ) -> Result<Option<BlockHeader>, StoreError> {
-        self.engine
-            .clone()
-            .lock()
-            .unwrap()
-            .get_block_header(block_number)
+        self.engine.get_block_header(block_number)
     }
 
     pub fn add_block_body(
         &self,
-        block_number: BlockNumber,
+        block_hash: BlockHash,
         block_body: BlockBody,
     ) -> Result<(), StoreError> {
-        self.engine
-            .clone()
-            .lock()
-            .unwrap()
-            .add_block_body(block_number, block_body)
+        self.write(|engine| engine.add_block_body(block_hash, block_body))
     }
 
+    /// Obtain the body of the canonical block with the given number
     pub fn get_block_body(
         &self,
         block_number: BlockNumber,
     ) -> Result<Option<BlockBody>, StoreError> {
-        self.engine
-            .clone()
-            .lock()
-            .unwrap()
-            .get_block_body(block_number)
+        self.engine.get_block_body(block_number)
+    }
+
+    /// Obtain the header of a stored block given its hash, whether it is canonical or not
+    pub fn get_block_header_by_hash(
+        &self,
+        block_hash: BlockHash,
+    ) -> Result<Option<BlockHeader>, StoreError> {
+        self.engine.get_block_header_by_hash(block_hash)
+    }
+
+    /// Obtain the body of a stored block given its hash, whether it is canonical or not
+    pub fn get_block_body_by_hash(
+        &self,
+        block_hash: BlockHash,
+    ) -> Result<Option<BlockBody>, StoreError> {
+        self.engine.get_block_body_by_hash(block_hash)
     }
 
     pub fn add_block_number(
@@ -128,22 +235,39 @@ impl Store {
         block_hash: BlockHash,
         block_number: BlockNumber,
     ) -> Result<(), StoreError> {
-        self.engine
-            .clone()
-            .lock()
-            .unwrap()
-            .add_block_number(block_hash, block_number)
+        self.write(|engine| engine.add_block_number(block_hash, block_number))
     }
 
     pub fn get_block_number(
         &self,
         block_hash: BlockHash,
     ) -> Result<Option<BlockNumber>, StoreError> {
-        self.engine
-            .clone()
-            .lock()
-            .unwrap()
-            .get_block_number(block_hash)
+        self.engine.get_block_number(block_hash)
+    }
+
+    /// Obtain the hash of the canonical block with the given number
+    pub fn get_canonical_block_hash(
+        &self,
+        block_number: BlockNumber,
+    ) -> Result<Option<BlockHash>, StoreError> {
+        self.engine.get_canonical_block_hash(block_number)
+    }
+
+    /// Makes the stored block with the given hash the canonical block at its height, indexing
+    /// its transactions in place of those of the block it replaces
+    pub fn set_canonical_block(
+        &self,
+        block_number: BlockNumber,
+        block_hash: BlockHash,
+    ) -> Result<(), StoreError> {
+        self.commit(|engine, batch| {
+            let block_body = engine
+                .get_block_body_by_hash(block_hash)?
+                .ok_or(StoreError::MissingBlock(block_hash))?;
+            remove_canonical_block(engine, batch, block_number)?;
+            write_canonical_block(batch, block_number, block_hash, &block_body);
+            Ok(())
+        })
     }
 
     pub fn add_transaction_location(
@@ -152,112 +276,414 @@ impl Store {
         block_number: BlockNumber,
         index: Index,
     ) -> Result<(), StoreError> {
-        self.engine
-            .lock()
-            .unwrap()
-            .add_transaction_location(transaction_hash, block_number, index)
+        self.write(|engine| engine.add_transaction_location(transaction_hash, block_number, index))
     }
 
     pub fn get_transaction_location(
         &self,
         transaction_hash: H256,
     ) -> Result<Option<(BlockNumber, Index)>, StoreError> {
-        self.engine
-            .lock()
-            .unwrap()
-            .get_transaction_location(transaction_hash)
+        self.engine.get_transaction_location(transaction_hash)
     }
 
     pub fn add_account_code(&self, code_hash: H256, code: Bytes) -> Result<(), StoreError> {
-        self.engine
-            .clone()
-            .lock()
-            .unwrap()
-            .add_account_code(code_hash, code)
+        self.write(|engine| engine.add_account_code(code_hash, code))
     }
 
     pub fn get_account_code(&self, code_hash: H256) -> Result<Option<Bytes>, StoreError> {
-        self.engine
-            .clone()
-            .lock()
-            .unwrap()
-            .get_account_code(code_hash)
+        self.engine.get_account_code(code_hash)
     }
 
     pub fn get_code_by_account_address(
         &self,
         address: Address,
     ) -> Result<Option<Bytes>, StoreError> {
-        self.engine
-            .clone()
-            .lock()
-            .unwrap()
-            .get_code_by_account_address(address)
+        self.engine.get_code_by_account_address(address)
     }
 
+    /// Stores an account's info, code and storage, and adds it to the state trie
     pub fn add_account(&mut self, address: Address, account: Account) -> Result<(), StoreError> {
-        self.engine.lock().unwrap().add_account(address, account)
+        let account_update = AccountUpdate::from_account(address, account);
+        self.commit(|engine, batch| write_account_updates(engine, batch, &[account_update]))
     }
 
     pub fn add_receipt(
         &self,
-        block_number: BlockNumber,
+        block_hash: BlockHash,
         index: Index,
         receipt: Receipt,
     ) -> Result<(), StoreError> {
-        self.engine
-            .clone()
-            .lock()
-            .unwrap()
-            .add_receipt(block_number, index, receipt)
+        self.write(|engine| engine.add_receipt(block_hash, index, receipt))
     }
 
+    /// Obtain a receipt of the canonical block with the given number
     pub fn get_receipt(
         &self,
         block_number: BlockNumber,
         index: Index,
     ) -> Result<Option<Receipt>, StoreError> {
-        self.engine
-            .clone()
-            .lock()
-            .unwrap()
-            .get_receipt(block_number, index)
+        self.engine.get_receipt(block_number, index)
     }
 
+    /// Obtain a receipt of a stored block given the block's hash, whether it is canonical or not
+    pub fn get_receipt_by_hash(
+        &self,
+        block_hash: BlockHash,
+        index: Index,
+    ) -> Result<Option<Receipt>, StoreError> {
+        self.engine.get_receipt_by_hash(block_hash, index)
+    }
+
+    /// Stores the block's header and body under its hash, along with its number
+    /// The block is not made part of the canonical chain, see `set_canonical_block`
     pub fn add_block(&self, block: Block) -> Result<(), StoreError> {
-        // TODO Maybe add both in a single tx?
-        self.add_block_body(block.header.number, block.body)?;
-        self.add_block_header(block.header.number, block.header)?;
+        self.commit(|_, batch| {
+            write_block(batch, block);
+            Ok(())
+        })
+    }
+
+    /// Stores the receipts of a block's transactions, in the same order as the transactions
+    pub fn add_receipts(
+        &self,
+        block_hash: BlockHash,
+        receipts: Vec<Receipt>,
+    ) -> Result<(), StoreError> {
+        self.commit(|_, batch| {
+            write_receipts(batch, block_hash, receipts);
+            Ok(())
+        })
+    }
+
+    /// Applies the account updates resulting from executing a block to the current state and its
+    /// state trie
+    /// The previous values of the modified accounts and storage slots are recorded as the
+    /// block's state history, so that the state before the block can still be queried
+    pub fn apply_account_updates(
+        &self,
+        block_number: BlockNumber,
+        account_updates: &[AccountUpdate],
+    ) -> Result<(), StoreError> {
+        self.commit(|engine, batch| {
+            self.write_state_transition(engine, batch, block_number, account_updates)
+        })
+    }
+
+    /// Stores an executed block along with its receipts and the state resulting from executing
+    /// it, and makes it the latest canonical block
+    /// The canonical blocks that fall out of the pruning distance are pruned along with it
+    /// Everything is written at once, so the store never holds a partially imported block
+    pub fn add_executed_block(
+        &self,
+        block: Block,
+        receipts: Vec<Receipt>,
+        account_updates: &[AccountUpdate],
+    ) -> Result<(), StoreError> {
+        let block_number = block.header.number;
+        let block_hash = block.header.compute_block_hash();
+        self.commit(|engine, batch| {
+            self.write_state_transition(engine, batch, block_number, account_updates)?;
+            remove_canonical_block(engine, batch, block_number)?;
+            write_canonical_block(batch, block_number, block_hash, &block.body);
+            write_block(batch, block);
+            write_receipts(batch, block_hash, receipts);
+            batch.update_latest_block_number(block_number);
+            self.write_pruning(engine, batch, block_number)
+        })
+    }
+
+    /// Reorganizes the canonical chain so that the stored block with the given hash becomes its
+    /// head, replacing the canonical blocks after the common ancestor of both chains with the
+    /// ancestors of the new head, and unwinds the state to the end of the common ancestor
+    /// Returns the number of the common ancestor. The state of the blocks after it is not applied,
+    /// so they must be executed again on top of it (see `add_executed_block`)
+    /// Fails if the new chain is not fully stored or if the common ancestor is older than the
+    /// state history window
+    pub fn reorg_to(&self, new_head: BlockHash) -> Result<BlockNumber, StoreError> {
+        self.commit(|engine, batch| {
+            // Walk back from the new head until reaching a canonical block
+            let mut new_blocks = Vec::new();
+            let mut block_hash = new_head;
+            let ancestor_number = loop {
+                let header = engine
+                    .get_block_header_by_hash(block_hash)?
+                    .ok_or(StoreError::MissingBlock(block_hash))?;
+                if engine.get_canonical_block_hash(header.number)? == Some(block_hash) {
+                    break header.number;
+                }
+                let body = engine
+                    .get_block_body_by_hash(block_hash)?
+                    .ok_or(StoreError::MissingBlock(block_hash))?;
+                new_blocks.push((header.number, block_hash, body));
+                block_hash = header.parent_hash;
+            };
+            // The transactions of the replaced blocks are unindexed before indexing the new ones,
+            // as the same transaction may be included in both chains
+            self.write_unwind(engine, batch, ancestor_number)?;
+            for (block_number, block_hash, body) in new_blocks.iter().rev() {
+                write_canonical_block(batch, *block_number, *block_hash, body);
+            }
+            Ok(ancestor_number)
+        })
+    }
+
+    /// Reverts the state to the end of the given block, which must be within the state history
+    /// window, and removes the blocks after it from the canonical chain
+    /// The removed blocks are kept in the store so that they can be made canonical again
+    pub fn unwind_to(&self, block_number: BlockNumber) -> Result<(), StoreError> {
+        self.commit(|engine, batch| {
+            self.check_state_available(engine, block_number)?;
+            self.write_unwind(engine, batch, block_number)
+        })
+    }
+
+    /// Adds the writes needed to unwind the chain to the given block to the batch: the state is
+    /// reverted to the end of the block using the change sets of the blocks applied after it,
+    /// and the canonical blocks after it are removed from the canonical chain
+    fn write_unwind(
+        &self,
+        engine: &dyn StoreEngine,
+        batch: &mut WriteBatch,
+        block_number: BlockNumber,
+    ) -> Result<(), StoreError> {
+        let latest_block_number = engine.get_latest_block_number()?.unwrap_or_default();
+        if block_number < latest_block_number {
+            self.check_state_available(engine, block_number)?;
+            write_state_unwind(engine, batch, block_number, latest_block_number)?;
+        }
+        let mut unwound_block_number = block_number + 1;
+        while engine
+            .get_canonical_block_hash(unwound_block_number)?
+            .is_some()
+        {
+            remove_canonical_block(engine, batch, unwound_block_number)?;
+            unwound_block_number += 1;
+        }
+        // The blocks that replace the pruned ones will need to be pruned again
+        if engine
+            .get_pruned_block_number()?
+            .is_some_and(|pruned_block_number| pruned_block_number > block_number)
+        {
+            batch.update_pruned_block_number(block_number);
+        }
         Ok(())
     }
 
-    pub fn add_initial_state(&mut self, genesis: Genesis) -> Result<(), StoreError> {
-        // TODO: Check initial state is not already present in db
-        info!("Storing initial state from genesis");
+    /// Adds the writes needed to prune the canonical blocks that fall out of the pruning distance
+    /// once the given block is the latest one to the batch
+    fn write_pruning(
+        &self,
+        engine: &dyn StoreEngine,
+        batch: &mut WriteBatch,
+        latest_block_number: BlockNumber,
+    ) -> Result<(), StoreError> {
+        let PruningMode::Distance(distance) = self.pruning_mode else {
+            return Ok(());
+        };
+        let Some(last_block_number) = latest_block_number.checked_sub(distance.saturating_add(1))
+        else {
+            return Ok(());
+        };
+        // Blocks are pruned in order, resuming after the last pruned one
+        let first_block_number = engine
+            .get_pruned_block_number()?
+            .map_or(1, |block_number| block_number + 1);
+        if first_block_number > last_block_number {
+            return Ok(());
+        }
+        let last_block_number =
+            last_block_number.min(first_block_number + MAX_PRUNED_BLOCKS_PER_BLOCK - 1);
+        for block_number in first_block_number..=last_block_number {
+            prune_canonical_block(engine, batch, block_number)?;
+        }
+        batch.update_pruned_block_number(last_block_number);
+        Ok(())
+    }
 
-        // Obtain genesis block
-        let genesis_block = genesis.get_block();
+    /// Adds the writes needed to apply the account updates of a block to the batch, along with
+    /// the block's state history and the removal of the history that falls out of the window
+    fn write_state_transition(
+        &self,
+        engine: &dyn StoreEngine,
+        batch: &mut WriteBatch,
+        block_number: BlockNumber,
+        account_updates: &[AccountUpdate],
+    ) -> Result<(), StoreError> {
+        write_state_history(engine, batch, block_number, account_updates)?;
+        write_account_updates(engine, batch, account_updates)?;
+        // The history of this block's ancestor that falls out of the window is no longer needed
+        if let Some(block_number) = block_number.checked_sub(self.state_history_window) {
+            batch.remove_state_history(block_number);
+        }
+        Ok(())
+    }
+
+    /// Checks that the state at the end of the given block can be queried, that is, that it is
+    /// not after the latest block nor older than the state history window
+    fn check_state_available(
+        &self,
+        engine: &dyn StoreEngine,
+        block_number: BlockNumber,
+    ) -> Result<(), StoreError> {
+        let latest_block_number = engine.get_latest_block_number()?.unwrap_or_default();
+        if block_number > latest_block_number
+            || latest_block_number - block_number > self.state_history_window
+        {
+            return Err(StoreError::StateNotAvailable(block_number));
+        }
+        Ok(())
+    }
+
+    /// Obtain the info an account had at the end of the given block
+    pub fn get_account_info_at_block(
+        &self,
+        block_number: BlockNumber,
+        address: Address,
+    ) -> Result<Option<AccountInfo>, StoreError> {
+        let engine = &*self.engine;
+        self.check_state_available(engine, block_number)?;
+        engine.get_account_info_at_block(block_number, address)
+    }
 
-        // Store genesis block
-        self.add_block(genesis_block)?;
+    /// Obtain the value a storage slot had at the end of the given block
+    pub fn get_storage_at_block(
+        &self,
+        block_number: BlockNumber,
+        address: Address,
+        storage_key: H256,
+    ) -> Result<Option<H256>, StoreError> {
+        let engine = &*self.engine;
+        self.check_state_available(engine, block_number)?;
+        engine.get_storage_at_block(block_number, address, storage_key)
+    }
 
-        // Store each alloc account
-        for (address, account) in genesis.alloc.into_iter() {
-            self.add_account(address, account.into())?;
+    /// Obtain the code an account had at the end of the given block
+    pub fn get_code_by_account_address_at_block(
+        &self,
+        block_number: BlockNumber,
+        address: Address,
+    ) -> Result<Option<Bytes>, StoreError> {
+        let Some(account_info) = self.get_account_info_at_block(block_number, address)? else {
+            return Ok(None);
+        };
+        self.get_account_code(account_info.code_hash)
+    }
+
+    /// Computes the state root that would result from applying the given account updates to the
+    /// current state, without writing them to the store
+    pub fn compute_state_root(
+        &self,
+        account_updates: &[AccountUpdate],
+    ) -> Result<H256, StoreError> {
+        let (state_root, _) = update_tries(&*self.engine, account_updates)?;
+        Ok(state_root)
+    }
+
+    /// Obtain the root of the state trie of the current state
+    pub fn get_state_root(&self) -> Result<H256, StoreError> {
+        Ok(self.engine.get_state_root()?.unwrap_or(EMPTY_TRIE_HASH))
+    }
+
+    /// Obtain the proof of an account and of the given slots of its storage in the state with the
+    /// given root, which must be the root of a state stored as a result of applying blocks
+    pub fn get_account_proof(
+        &self,
+        state_root: H256,
+        address: Address,
+        storage_keys: &[H256],
+    ) -> Result<AccountProof, StoreError> {
+        let engine = &*self.engine;
+        let state_trie = Trie::open(engine, state_root);
+        let hashed_address = keccak(address.as_bytes());
+        let account_proof = state_trie.get_proof(hashed_address.as_bytes())?;
+        let account_state = match state_trie.get(hashed_address.as_bytes())? {
+            Some(encoded) => AccountState::decode(&encoded).map_err(|_| StoreError::DecodeError)?,
+            // Accounts that don't exist are reported as empty accounts
+            None => AccountState {
+                nonce: 0,
+                balance: U256::zero(),
+                storage_root: EMPTY_TRIE_HASH,
+                code_hash: *EMPTY_KECCACK_HASH,
+            },
+        };
+        let storage_trie = Trie::open(engine, account_state.storage_root);
+        let storage_proof = storage_keys
+            .iter()
+            .map(|storage_key| {
+                let hashed_key = keccak(storage_key.as_bytes());
+                let value = match storage_trie.get(hashed_key.as_bytes())? {
+                    Some(encoded) => U256::decode(&encoded).map_err(|_| StoreError::DecodeError)?,
+                    None => U256::zero(),
+                };
+                let proof = storage_trie.get_proof(hashed_key.as_bytes())?;
+                Ok(StorageProof {
+                    key: *storage_key,
+                    value,
+                    proof: proof.into_iter().map(Bytes::from).collect(),
+                })
+            })
+            .collect::<Result<_, StoreError>>()?;
+        Ok(AccountProof {
+            address,
+            account_proof: account_proof.into_iter().map(Bytes::from).collect(),
+            balance: account_state.balance,
+            code_hash: account_state.code_hash,
+            nonce: account_state.nonce,
+            storage_hash: account_state.storage_root,
+            storage_proof,
+        })
+    }
+
+    /// Stores the genesis block, its state and the chain config
+    /// If the database was already initialized, checks that the supplied genesis matches the stored one
+    pub fn add_initial_state(&mut self, genesis: Genesis) -> Result<(), StoreError> {
+        // Obtain genesis block
+        let genesis_block = genesis.get_block();
+        let genesis_hash = genesis_block.header.compute_block_hash();
+
+        // Check if the database was already initialized
+        if let Some(stored_genesis_header) = self.get_block_header(0)? {
+            let stored_genesis_hash = stored_genesis_header.compute_block_hash();
+            if stored_genesis_hash != genesis_hash {
+                return Err(StoreError::GenesisMismatch(
+                    stored_genesis_hash,
+                    genesis_hash,
+                ));
+            }
+            // The chain config is not part of the genesis block so it must be checked separately
+            if self.engine.get_chain_config()? != Some(genesis.config) {
+                return Err(StoreError::ChainConfigMismatch);
+            }
+            info!(
+                "Initial state already stored for genesis {:#x}",
+                genesis_hash
+            );
+            return Ok(());
         }
+        info!("Storing initial state from genesis");
 
-        // Store chain info
-        self.update_chain_id(genesis.config.chain_id)
+        // Store the genesis block, its state and the chain info at once
+        let account_updates: Vec<_> = genesis
+            .alloc
+            .into_iter()
+            .map(|(address, account)| AccountUpdate::from_account(address, account.into()))
+            .collect();
+        self.commit(|engine, batch| {
+            write_canonical_block(batch, 0, genesis_hash, &genesis_block.body);
+            write_block(batch, genesis_block);
+            write_account_updates(engine, batch, &account_updates)?;
+            batch.update_latest_block_number(0);
+            batch.update_chain_id(genesis.config.chain_id);
+            batch.update_chain_config(genesis.config);
+            Ok(())
+        })
     }
 
     pub fn get_transaction_by_hash(
         &self,
         transaction_hash: H256,
     ) -> Result<Option<Transaction>, StoreError> {
-        self.engine
-            .lock()
-            .unwrap()
-            .get_transaction_by_hash(transaction_hash)
+        self.engine.get_transaction_by_hash(transaction_hash)
     }
 
     pub fn add_storage_at(
@@ -266,10 +692,7 @@ impl Store {
         storage_key: H256,
         storage_value: H256,
     ) -> Result<(), StoreError> {
-        self.engine
-            .lock()
-            .unwrap()
-            .add_storage_at(address, storage_key, storage_value)
+        self.write(|engine| engine.add_storage_at(address, storage_key, storage_value))
     }
 
     pub fn get_storage_at(
@@ -277,44 +700,377 @@ impl Store {
         address: Address,
         storage_key: H256,
     ) -> Result<Option<H256>, StoreError> {
-        self.engine
-            .lock()
-            .unwrap()
-            .get_storage_at(address, storage_key)
+        self.engine.get_storage_at(address, storage_key)
     }
 
     pub fn remove_account_storage(&self, address: Address) -> Result<(), StoreError> {
-        self.engine.lock().unwrap().remove_account_storage(address)
+        self.write(|engine| engine.remove_account_storage(address))
     }
 
     pub fn remove_account(&self, address: Address) -> Result<(), StoreError> {
-        self.engine.lock().unwrap().remove_account(address)
+        self.write(|engine| engine.remove_account(address))
     }
 
     pub fn increment_balance(&self, address: Address, amount: U256) -> Result<(), StoreError> {
-        self.engine
-            .lock()
-            .unwrap()
-            .increment_balance(address, amount)
+        self.write(|engine| engine.increment_balance(address, amount))
     }
 
     pub fn update_chain_id(&self, chain_id: U256) -> Result<(), StoreError> {
-        self.engine.lock().unwrap().update_chain_id(chain_id)
+        self.write(|engine| engine.update_chain_id(chain_id))
     }
 
     pub fn get_chain_id(&self) -> Result<Option<U256>, StoreError> {
-        self.engine.lock().unwrap().get_chain_id()
+        self.engine.get_chain_id()
+    }
+
+    /// Stores the chain configuration along with its chain id
+    pub fn set_chain_config(&self, chain_config: &ChainConfig) -> Result<(), StoreError> {
+        self.write(|engine| {
+            engine.update_chain_id(chain_config.chain_id)?;
+            engine.update_chain_config(chain_config)
+        })
+    }
+
+    /// Obtain the current chain configuration, which must have been set beforehand
+    pub fn get_chain_config(&self) -> Result<ChainConfig, StoreError> {
+        self.engine
+            .get_chain_config()?
+            .ok_or_else(|| StoreError::Custom("Chain config not found".to_string()))
+    }
+
+    pub fn update_latest_block_number(&self, block_number: BlockNumber) -> Result<(), StoreError> {
+        self.write(|engine| engine.update_latest_block_number(block_number))
+    }
+
+    pub fn get_latest_block_number(&self) -> Result<Option<BlockNumber>, StoreError> {
+        self.engine.get_latest_block_number()
     }
+
+    /// Obtain the number of the latest canonical block whose body, receipts and transaction
+    /// index entries were pruned
+    pub fn get_pruned_block_number(&self) -> Result<Option<BlockNumber>, StoreError> {
+        self.engine.get_pruned_block_number()
+    }
+
+    /// Fails if the body, receipts and transaction index entries of the canonical block at the
+    /// given height were pruned, so that pruned data is not mistaken for missing data
+    pub fn check_block_not_pruned(&self, block_number: BlockNumber) -> Result<(), StoreError> {
+        // The genesis block is never pruned
+        if block_number > 0
+            && self
+                .get_pruned_block_number()?
+                .is_some_and(|pruned_block_number| block_number <= pruned_block_number)
+        {
+            return Err(StoreError::PrunedBlock(block_number));
+        }
+        Ok(())
+    }
+
+    pub fn update_finalized_block_number(
+        &self,
+        block_number: BlockNumber,
+    ) -> Result<(), StoreError> {
+        self.write(|engine| engine.update_finalized_block_number(block_number))
+    }
+
+    pub fn get_finalized_block_number(&self) -> Result<Option<BlockNumber>, StoreError> {
+        self.engine.get_finalized_block_number()
+    }
+
+    pub fn update_safe_block_number(&self, block_number: BlockNumber) -> Result<(), StoreError> {
+        self.write(|engine| engine.update_safe_block_number(block_number))
+    }
+
+    pub fn get_safe_block_number(&self) -> Result<Option<BlockNumber>, StoreError> {
+        self.engine.get_safe_block_number()
+    }
+
+    pub fn add_payload(&self, payload_id: u64, payload: BuiltPayload) -> Result<(), StoreError> {
+        self.write(|engine| engine.add_payload(payload_id, payload))
+    }
+
+    pub fn get_payload(&self, payload_id: u64) -> Result<Option<BuiltPayload>, StoreError> {
+        self.engine.get_payload(payload_id)
+    }
+}
+
+/// Adds the writes needed to store the block's header and body under its hash, along with its
+/// number, to the batch
+fn write_block(batch: &mut WriteBatch, block: Block) {
+    let number = block.header.number;
+    let hash = block.header.compute_block_hash();
+    batch.add_block_body(hash, block.body);
+    batch.add_block_header(hash, block.header);
+    batch.add_block_number(hash, number);
+}
+
+/// Adds the writes needed to make the block the canonical block at its height and to index its
+/// transactions to the batch
+fn write_canonical_block(
+    batch: &mut WriteBatch,
+    block_number: BlockNumber,
+    block_hash: BlockHash,
+    block_body: &BlockBody,
+) {
+    for (index, transaction) in block_body.transactions.iter().enumerate() {
+        batch.add_transaction_location(transaction.compute_hash(), block_number, index as Index);
+    }
+    batch.add_canonical_block_hash(block_number, block_hash);
+}
+
+/// Adds the writes needed to remove the canonical block at the given height (if any) from the
+/// canonical chain and to unindex its transactions to the batch
+/// The block itself is kept in the store
+fn remove_canonical_block(
+    engine: &dyn StoreEngine,
+    batch: &mut WriteBatch,
+    block_number: BlockNumber,
+) -> Result<(), StoreError> {
+    let Some(block_hash) = engine.get_canonical_block_hash(block_number)? else {
+        return Ok(());
+    };
+    if let Some(block_body) = engine.get_block_body_by_hash(block_hash)? {
+        for transaction in block_body.transactions {
+            batch.remove_transaction_location(transaction.compute_hash());
+        }
+    }
+    batch.remove_canonical_block_hash(block_number);
+    Ok(())
+}
+
+/// Adds the writes needed to remove the body, receipts and transaction index entries of the
+/// canonical block at the given height (if any) to the batch
+/// Its header is kept, so that the chain can still be traversed
+fn prune_canonical_block(
+    engine: &dyn StoreEngine,
+    batch: &mut WriteBatch,
+    block_number: BlockNumber,
+) -> Result<(), StoreError> {
+    let Some(block_hash) = engine.get_canonical_block_hash(block_number)? else {
+        return Ok(());
+    };
+    if let Some(block_body) = engine.get_block_body_by_hash(block_hash)? {
+        for transaction in block_body.transactions {
+            let transaction_hash = transaction.compute_hash();
+            // Only the entries pointing to this block are removed
+            if engine
+                .get_transaction_location(transaction_hash)?
+                .is_some_and(|(location_block_number, _)| location_block_number == block_number)
+            {
+                batch.remove_transaction_location(transaction_hash);
+            }
+        }
+    }
+    batch.remove_block_body(block_hash);
+    batch.remove_receipts(block_hash);
+    Ok(())
+}
+
+/// Adds the writes needed to store the receipts of a block's transactions to the batch
+fn write_receipts(batch: &mut WriteBatch, block_hash: BlockHash, receipts: Vec<Receipt>) {
+    for (index, receipt) in receipts.into_iter().enumerate() {
+        batch.add_receipt(block_hash, index as Index, receipt);
+    }
+}
+
+/// Adds the writes needed to apply the account updates to the current state and its state trie
+/// to the batch
+fn write_account_updates(
+    engine: &dyn StoreEngine,
+    batch: &mut WriteBatch,
+    account_updates: &[AccountUpdate],
+) -> Result<(), StoreError> {
+    for update in account_updates {
+        if update.removed {
+            batch.remove_account_info(update.address);
+            batch.remove_account_storage(update.address);
+        }
+        if let Some(info) = &update.info {
+            if let Some(code) = &update.code {
+                batch.add_account_code(info.code_hash, code.clone());
+            }
+            batch.add_account_info(update.address, info.clone());
+        }
+        for (storage_key, storage_value) in &update.added_storage {
+            // Zero-valued slots are not stored, same as in the storage trie, so that unwinding a
+            // block restores the storage exactly as it was
+            if storage_value.is_zero() {
+                batch.remove_storage_at(update.address, *storage_key);
+            } else {
+                batch.add_storage_at(update.address, *storage_key, *storage_value);
+            }
+        }
+    }
+    let (state_root, nodes) = update_tries(engine, account_updates)?;
+    batch.add_trie_nodes(nodes);
+    batch.update_state_root(state_root);
+    Ok(())
+}
+
+/// Adds the values that the accounts and storage slots modified by the account updates have
+/// before applying them to the batch, as the state history of the given block
+fn write_state_history(
+    engine: &dyn StoreEngine,
+    batch: &mut WriteBatch,
+    block_number: BlockNumber,
+    account_updates: &[AccountUpdate],
+) -> Result<(), StoreError> {
+    let mut account_infos = HashMap::new();
+    let mut storage_values: HashMap<(Address, H256), H256> = HashMap::new();
+    for update in account_updates {
+        if update.removed || update.info.is_some() {
+            if let Entry::Vacant(entry) = account_infos.entry(update.address) {
+                entry.insert(engine.get_account_info(update.address)?);
+            }
+        }
+        // The whole storage of a removed account is modified
+        let modified_storage = if update.removed {
+            engine.get_account_storage(update.address)?
+        } else {
+            HashMap::new()
+        };
+        for (storage_key, storage_value) in modified_storage {
+            storage_values.insert((update.address, storage_key), storage_value);
+        }
+        for storage_key in update.added_storage.keys() {
+            if let Entry::Vacant(entry) = storage_values.entry((update.address, *storage_key)) {
+                entry.insert(
+                    engine
+                        .get_storage_at(update.address, *storage_key)?
+                        .unwrap_or_default(),
+                );
+            }
+        }
+    }
+    for (address, account_info) in account_infos {
+        batch.add_account_info_history(block_number, address, account_info);
+    }
+    for ((address, storage_key), storage_value) in storage_values {
+        batch.add_storage_history(block_number, address, storage_key, storage_value);
+    }
+    Ok(())
+}
+
+/// Adds the writes needed to revert the state from the end of the latest block to the end of the
+/// given block, using the state history of the blocks in between, to the batch
+/// Storage slots that were unset back then are restored as zero and therefore removed
+fn write_state_unwind(
+    engine: &dyn StoreEngine,
+    batch: &mut WriteBatch,
+    block_number: BlockNumber,
+    latest_block_number: BlockNumber,
+) -> Result<(), StoreError> {
+    let mut account_infos = HashMap::new();
+    let mut storage_values: HashMap<Address, HashMap<H256, H256>> = HashMap::new();
+    // Blocks are visited from the latest one backwards, so that the values kept for each account
+    // and storage slot are the ones they had before the first block that modified them
+    for unwound_block_number in (block_number + 1..=latest_block_number).rev() {
+        account_infos.extend(engine.get_block_account_info_history(unwound_block_number)?);
+        for (address, storage_key, storage_value) in
+            engine.get_block_storage_history(unwound_block_number)?
+        {
+            storage_values
+                .entry(address)
+                .or_default()
+                .insert(storage_key, storage_value);
+        }
+        batch.remove_state_history(unwound_block_number);
+    }
+    let mut account_updates = Vec::new();
+    for (address, account_info) in account_infos {
+        let added_storage = storage_values.remove(&address).unwrap_or_default();
+        account_updates.push(match account_info {
+            Some(info) => AccountUpdate {
+                address,
+                removed: false,
+                info: Some(info),
+                code: None,
+                added_storage,
+            },
+            // The account didn't exist back then
+            None => AccountUpdate::removed(address),
+        });
+    }
+    for (address, added_storage) in storage_values {
+        account_updates.push(AccountUpdate {
+            address,
+            added_storage,
+            ..Default::default()
+        });
+    }
+    write_account_updates(engine, batch, &account_updates)?;
+    batch.update_latest_block_number(block_number);
+    Ok(())
+}
+
+/// Applies the account updates to the state trie and to the storage tries of the updated
+/// accounts, starting from the current state root
+/// Returns the resulting state root along with the trie nodes created in the process
+fn update_tries(
+    engine: &dyn StoreEngine,
+    account_updates: &[AccountUpdate],
+) -> Result<(H256, TrieNodes), StoreError> {
+    let state_root = engine.get_state_root()?.unwrap_or(EMPTY_TRIE_HASH);
+    let mut state_trie = Trie::open(engine, state_root);
+    let mut nodes = Vec::new();
+    for update in account_updates {
+        let hashed_address = keccak(update.address.as_bytes());
+        // A removed account starts over with an empty storage
+        let current_state = match state_trie.get(hashed_address.as_bytes())? {
+            Some(encoded) if !update.removed => {
+                Some(AccountState::decode(&encoded).map_err(|_| StoreError::DecodeError)?)
+            }
+            _ => None,
+        };
+        let mut account_state = match (&update.info, current_state) {
+            (Some(info), current_state) => AccountState {
+                nonce: info.nonce,
+                balance: info.balance,
+                storage_root: current_state
+                    .map(|state| state.storage_root)
+                    .unwrap_or(EMPTY_TRIE_HASH),
+                code_hash: info.code_hash,
+            },
+            (None, Some(current_state)) => current_state,
+            (None, None) => {
+                if update.removed {
+                    state_trie.remove(hashed_address.as_bytes())?;
+                }
+                continue;
+            }
+        };
+        if !update.added_storage.is_empty() {
+            let mut storage_trie = Trie::open(engine, account_state.storage_root);
+            for (storage_key, storage_value) in &update.added_storage {
+                let hashed_key = keccak(storage_key.as_bytes());
+                // Zero-valued slots are not part of the storage trie
+                if storage_value.is_zero() {
+                    storage_trie.remove(hashed_key.as_bytes())?;
+                } else {
+                    // Values are encoded as integers, without leading zeros
+                    let value = U256::from_big_endian(storage_value.as_bytes()).encode_to_vec();
+                    storage_trie.insert(hashed_key.as_bytes(), value)?;
+                }
+            }
+            let (storage_root, storage_nodes) = storage_trie.commit();
+            account_state.storage_root = storage_root;
+            nodes.extend(storage_nodes);
+        }
+        state_trie.insert(hashed_address.as_bytes(), account_state.encode_to_vec())?;
+    }
+    let (state_root, state_nodes) = state_trie.commit();
+    nodes.extend(state_nodes);
+    Ok((state_root, nodes))
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{env, fs, str::FromStr};
+    use std::{collections::BTreeMap, env, fs, str::FromStr, thread};
 
     use bytes::Bytes;
     use ethereum_rust_core::{
         rlp::decode::RLPDecode,
-        types::{self, Transaction, TxType},
+        types::{self, GenesisAccount, Transaction, TxType},
         Bloom,
     };
     use ethereum_types::{H256, U256};
@@ -338,6 +1094,26 @@ mod tests {
         remove_test_dbs("test.mdbx");
     }
 
+    #[cfg(feature = "libmdbx")]
+    #[test]
+    fn test_libmdbx_store_reopen() {
+        remove_test_dbs("reopen_test.mdbx");
+        let state_root = {
+            let mut store = Store::new("reopen_test.mdbx", EngineType::Libmdbx).unwrap();
+            store.add_initial_state(read_genesis_file()).unwrap();
+            store.get_state_root().unwrap()
+        };
+        // The chain stored before closing the database is still there after reopening it
+        let store = Store::new("reopen_test.mdbx", EngineType::Libmdbx).unwrap();
+        assert_eq!(store.get_latest_block_number().unwrap(), Some(0));
+        assert_eq!(store.get_state_root().unwrap(), state_root);
+        assert_eq!(
+            store.get_chain_config().unwrap(),
+            read_genesis_file().config
+        );
+        remove_test_dbs("reopen_test.mdbx");
+    }
+
     fn test_store_suite(store: Store) {
         test_store_account(store.clone());
         test_store_block(store.clone());
@@ -349,6 +1125,60 @@ mod tests {
         test_remove_account_storage(store.clone());
         test_increment_balance(store.clone());
         test_store_chain_data(store.clone());
+        test_overwrite_account_storage(store.clone());
+        test_apply_account_updates(store.clone());
+        test_state_history(store.clone());
+        test_incremental_state_root(store.clone());
+        test_account_proof(store.clone());
+        test_add_executed_block(store.clone());
+        test_reads_during_write(store.clone());
+        test_reorg(store.clone());
+        test_unwind_to(store.clone());
+        test_pruning(store.clone());
+        // Stores the genesis block, so it must run last
+        test_add_initial_state(store.clone());
+    }
+
+    fn read_genesis_file() -> Genesis {
+        let file = fs::File::open("../../test_data/genesis.json").unwrap();
+        serde_json::from_reader(file).unwrap()
+    }
+
+    fn test_add_initial_state(mut store: Store) {
+        let genesis = read_genesis_file();
+        let genesis_hash = genesis.get_block().header.compute_block_hash();
+        store.add_initial_state(genesis).unwrap();
+
+        assert_eq!(
+            store.get_chain_config().unwrap(),
+            read_genesis_file().config
+        );
+        assert_eq!(store.get_latest_block_number().unwrap(), Some(0));
+        assert_eq!(store.get_block_number(genesis_hash).unwrap(), Some(0));
+        // The state trie built from the alloc matches the genesis block's state root
+        assert_eq!(
+            store.get_state_root().unwrap(),
+            read_genesis_file().compute_state_root()
+        );
+
+        // Initializing again with the same genesis is allowed
+        store.add_initial_state(read_genesis_file()).unwrap();
+
+        // A different genesis block is rejected
+        let mut other_genesis = read_genesis_file();
+        other_genesis.timestamp += 1;
+        assert!(matches!(
+            store.add_initial_state(other_genesis),
+            Err(StoreError::GenesisMismatch(_, _))
+        ));
+
+        // The same genesis block with a different chain config is rejected
+        let mut other_genesis = read_genesis_file();
+        other_genesis.config.cancun_time = None;
+        assert!(matches!(
+            store.add_initial_state(other_genesis),
+            Err(StoreError::ChainConfigMismatch)
+        ));
     }
 
     fn test_store_account(store: Store) {
@@ -394,20 +1224,33 @@ mod tests {
 
     fn test_store_block(store: Store) {
         let (block_header, block_body) = create_block_for_testing();
+        let block_hash = block_header.compute_block_hash();
         let block_number = 6;
 
         store
-            .add_block_header(block_number, block_header.clone())
+            .add_block_header(block_hash, block_header.clone())
             .unwrap();
         store
-            .add_block_body(block_number, block_body.clone())
+            .add_block_body(block_hash, block_body.clone())
             .unwrap();
 
+        // The block can only be looked up by number once it is canonical
+        assert!(store.get_block_header(block_number).unwrap().is_none());
+        assert_eq!(
+            store.get_block_body_by_hash(block_hash).unwrap(),
+            Some(block_body.clone())
+        );
+        store.set_canonical_block(block_number, block_hash).unwrap();
+
         let stored_header = store.get_block_header(block_number).unwrap().unwrap();
         let stored_body = store.get_block_body(block_number).unwrap().unwrap();
 
         assert_eq!(stored_header, block_header);
         assert_eq!(stored_body, block_body);
+        assert_eq!(
+            store.get_canonical_block_hash(block_number).unwrap(),
+            Some(block_hash)
+        );
     }
 
     fn create_block_for_testing() -> (BlockHeader, BlockBody) {
@@ -498,11 +1341,20 @@ mod tests {
             bloom: Bloom::random(),
             logs: vec![],
         };
+        let (block_header, block_body) = create_block_for_testing();
+        let block_hash = block_header.compute_block_hash();
         let block_number = 6;
         let index = 4;
 
         store
-            .add_receipt(block_number, index, receipt.clone())
+            .add_block(Block {
+                header: block_header,
+                body: block_body,
+            })
+            .unwrap();
+        store.set_canonical_block(block_number, block_hash).unwrap();
+        store
+            .add_receipt(block_hash, index, receipt.clone())
             .unwrap();
 
         let stored_receipt = store.get_receipt(block_number, index).unwrap().unwrap();
@@ -608,5 +1460,589 @@ mod tests {
         let stored_chain_id = store.get_chain_id().unwrap().unwrap();
 
         assert_eq!(chain_id, stored_chain_id);
+
+        store.update_latest_block_number(12).unwrap();
+
+        store.update_safe_block_number(10).unwrap();
+        store.update_finalized_block_number(8).unwrap();
+
+        let stored_latest_block_number = store.get_latest_block_number().unwrap().unwrap();
+        let stored_safe_block_number = store.get_safe_block_number().unwrap().unwrap();
+        let stored_finalized_block_number = store.get_finalized_block_number().unwrap().unwrap();
+
+        assert_eq!(stored_latest_block_number, 12);
+        assert_eq!(stored_safe_block_number, 10);
+        assert_eq!(stored_finalized_block_number, 8);
+
+        let chain_config = ChainConfig {
+            chain_id: U256::from(47),
+            london_block: Some(0),
+            shanghai_time: Some(10),
+            cancun_time: Some(20),
+            terminal_total_difficulty: Some(U256::zero()),
+            ..Default::default()
+        };
+
+        store.set_chain_config(&chain_config).unwrap();
+
+        assert_eq!(store.get_chain_config().unwrap(), chain_config);
+        assert_eq!(store.get_chain_id().unwrap(), Some(U256::from(47)));
+    }
+
+    fn test_overwrite_account_storage(store: Store) {
+        let address = Address::random();
+        let storage_key = H256::random();
+        let storage_value_a = H256::random();
+        let storage_value_b = H256::random();
+
+        store
+            .add_storage_at(address, storage_key, storage_value_a)
+            .unwrap();
+        store
+            .add_storage_at(address, storage_key, storage_value_b)
+            .unwrap();
+
+        let stored_value = store.get_storage_at(address, storage_key).unwrap().unwrap();
+        let stored_storage = store.engine.get_account_storage(address).unwrap();
+
+        assert_eq!(stored_value, storage_value_b);
+        assert_eq!(stored_storage.len(), 1);
+        assert!(store
+            .get_storage_at(address, H256::random())
+            .unwrap()
+            .is_none());
+    }
+
+    fn test_apply_account_updates(store: Store) {
+        let address = Address::random();
+        let code = Bytes::from("kiwi");
+        let storage_key = H256::random();
+        let storage_value = H256::random();
+        let update = AccountUpdate {
+            address,
+            removed: false,
+            info: Some(new_account_info(code.clone(), 50.into(), 1)),
+            code: Some(code.clone()),
+            added_storage: HashMap::from([(storage_key, storage_value)]),
+        };
+
+        let expected_state_root = store.compute_state_root(&[update.clone()]).unwrap();
+        store.apply_account_updates(1, &[update]).unwrap();
+
+        assert_eq!(store.compute_state_root(&[]).unwrap(), expected_state_root);
+        assert_eq!(
+            store.get_code_by_account_address(address).unwrap().unwrap(),
+            code
+        );
+        assert_eq!(
+            store.get_storage_at(address, storage_key).unwrap().unwrap(),
+            storage_value
+        );
+
+        store
+            .apply_account_updates(2, &[AccountUpdate::removed(address)])
+            .unwrap();
+
+        assert!(store.get_account_info(address).unwrap().is_none());
+        assert!(store
+            .get_storage_at(address, storage_key)
+            .unwrap()
+            .is_none());
+    }
+
+    fn test_state_history(mut store: Store) {
+        store.set_state_history_window(2);
+        let address = Address::random();
+        let storage_key = H256::random();
+        let (info_a, storage_value_a) =
+            (new_account_info(Bytes::new(), 10.into(), 0), H256::random());
+        let (info_b, storage_value_b) =
+            (new_account_info(Bytes::new(), 20.into(), 1), H256::random());
+        // Block 1 creates the account, block 2 modifies it and block 3 removes it
+        let updates = [
+            AccountUpdate {
+                address,
+                info: Some(info_a.clone()),
+                added_storage: HashMap::from([(storage_key, storage_value_a)]),
+                ..Default::default()
+            },
+            AccountUpdate {
+                address,
+                info: Some(info_b.clone()),
+                added_storage: HashMap::from([(storage_key, storage_value_b)]),
+                ..Default::default()
+            },
+            AccountUpdate::removed(address),
+        ];
+        for (block_number, update) in (1..=3).zip(updates) {
+            store
+                .apply_account_updates(block_number, &[update])
+                .unwrap();
+            store.update_latest_block_number(block_number).unwrap();
+        }
+
+        assert_eq!(
+            store.get_account_info_at_block(1, address).unwrap(),
+            Some(info_a)
+        );
+        assert_eq!(
+            store.get_storage_at_block(1, address, storage_key).unwrap(),
+            Some(storage_value_a)
+        );
+        assert_eq!(
+            store.get_account_info_at_block(2, address).unwrap(),
+            Some(info_b.clone())
+        );
+        assert_eq!(
+            store.get_storage_at_block(2, address, storage_key).unwrap(),
+            Some(storage_value_b)
+        );
+        assert_eq!(store.get_account_info_at_block(3, address).unwrap(), None);
+        assert_eq!(
+            store.get_storage_at_block(3, address, storage_key).unwrap(),
+            None
+        );
+        assert!(matches!(
+            store.get_account_info_at_block(4, address),
+            Err(StoreError::StateNotAvailable(4))
+        ));
+
+        // Block 1 falls out of the window once block 4, which creates the account again, is applied
+        store
+            .apply_account_updates(
+                4,
+                &[AccountUpdate {
+                    address,
+                    info: Some(info_a.clone()),
+                    ..Default::default()
+                }],
+            )
+            .unwrap();
+        store.update_latest_block_number(4).unwrap();
+        assert!(matches!(
+            store.get_account_info_at_block(1, address),
+            Err(StoreError::StateNotAvailable(1))
+        ));
+        assert_eq!(
+            store.get_account_info_at_block(2, address).unwrap(),
+            Some(info_b.clone())
+        );
+        assert_eq!(store.get_account_info_at_block(3, address).unwrap(), None);
+
+        // The changes of the unwound blocks are forgotten, so that the ones of the blocks that
+        // replace them are found instead
+        store.unwind_to(2).unwrap();
+        let (info_c, storage_value_c) =
+            (new_account_info(Bytes::new(), 30.into(), 2), H256::random());
+        store
+            .apply_account_updates(
+                3,
+                &[AccountUpdate {
+                    address,
+                    info: Some(info_c.clone()),
+                    added_storage: HashMap::from([(storage_key, storage_value_c)]),
+                    ..Default::default()
+                }],
+            )
+            .unwrap();
+        store.apply_account_updates(4, &[]).unwrap();
+        store.update_latest_block_number(4).unwrap();
+        assert_eq!(
+            store.get_account_info_at_block(2, address).unwrap(),
+            Some(info_b)
+        );
+        assert_eq!(
+            store.get_account_info_at_block(3, address).unwrap(),
+            Some(info_c)
+        );
+        assert_eq!(
+            store.get_storage_at_block(3, address, storage_key).unwrap(),
+            Some(storage_value_c)
+        );
+    }
+
+    fn test_incremental_state_root(store: Store) {
+        let address = Address::random();
+        let info = new_account_info(Bytes::from("kiwi"), 10.into(), 1);
+        let (storage_key_a, storage_key_b) = (H256::random(), H256::random());
+        let storage_value_a = H256::random();
+        store
+            .apply_account_updates(
+                1,
+                &[AccountUpdate {
+                    address,
+                    info: Some(info.clone()),
+                    added_storage: HashMap::from([
+                        (storage_key_a, H256::random()),
+                        (storage_key_b, H256::random()),
+                    ]),
+                    ..Default::default()
+                }],
+            )
+            .unwrap();
+        // Modify one slot and clear the other one, without modifying the account info
+        store
+            .apply_account_updates(
+                2,
+                &[AccountUpdate {
+                    address,
+                    added_storage: HashMap::from([
+                        (storage_key_a, storage_value_a),
+                        (storage_key_b, H256::zero()),
+                    ]),
+                    ..Default::default()
+                }],
+            )
+            .unwrap();
+
+        // The updated trie matches the one built from scratch from the resulting state
+        let genesis = Genesis {
+            alloc: HashMap::from([(
+                address,
+                GenesisAccount {
+                    code: Bytes::from("kiwi"),
+                    storage: BTreeMap::from([(storage_key_a, storage_value_a)]),
+                    balance: info.balance,
+                    nonce: info.nonce,
+                },
+            )]),
+            ..read_genesis_file()
+        };
+        assert_eq!(
+            store.get_state_root().unwrap(),
+            genesis.compute_state_root()
+        );
+
+        store
+            .apply_account_updates(3, &[AccountUpdate::removed(address)])
+            .unwrap();
+        assert_eq!(store.get_state_root().unwrap(), EMPTY_TRIE_HASH);
+    }
+
+    fn test_account_proof(mut store: Store) {
+        let address = Address::random();
+        let storage_key = H256::random();
+        let storage_value = H256::from_low_u64_be(7);
+        let account = Account {
+            info: new_account_info(Bytes::from("kiwi"), 10.into(), 1),
+            code: Bytes::from("kiwi"),
+            storage: BTreeMap::from([(storage_key, storage_value)]),
+        };
+        // Other accounts make sure the proof goes through more than one node
+        for _ in 0..10 {
+            let other_account = Account {
+                info: new_account_info(Bytes::new(), 1.into(), 0),
+                code: Bytes::new(),
+                storage: BTreeMap::new(),
+            };
+            store.add_account(Address::random(), other_account).unwrap();
+        }
+        store.add_account(address, account.clone()).unwrap();
+        let state_root = store.get_state_root().unwrap();
+
+        let missing_key = H256::random();
+        let proof = store
+            .get_account_proof(state_root, address, &[storage_key, missing_key])
+            .unwrap();
+        assert_eq!(proof.balance, account.info.balance);
+        assert_eq!(proof.storage_proof[0].value, U256::from(7));
+        assert_eq!(proof.storage_proof[1].value, U256::zero());
+        proof.verify(state_root).unwrap();
+
+        // A proof for a missing account shows that it is empty
+        let proof = store
+            .get_account_proof(state_root, Address::random(), &[])
+            .unwrap();
+        assert_eq!(proof.storage_hash, EMPTY_TRIE_HASH);
+        proof.verify(state_root).unwrap();
+
+        // Proofs don't verify against a different state root
+        assert!(proof.verify(H256::random()).is_err());
+    }
+
+    fn test_add_executed_block(store: Store) {
+        let (mut block_header, block_body) = create_block_for_testing();
+        block_header.number = 20;
+        let block = Block {
+            header: block_header.clone(),
+            body: block_body,
+        };
+        let receipt = Receipt {
+            tx_type: TxType::EIP1559,
+            succeeded: true,
+            cumulative_gas_used: 21000,
+            bloom: Bloom::zero(),
+            logs: vec![],
+        };
+        let address = Address::random();
+        let update = AccountUpdate {
+            address,
+            info: Some(new_account_info(Bytes::new(), 10.into(), 1)),
+            ..Default::default()
+        };
+        let expected_state_root = store.compute_state_root(&[update.clone()]).unwrap();
+
+        store
+            .add_executed_block(block, vec![receipt.clone()], &[update.clone()])
+            .unwrap();
+
+        // The block, its receipts, its state and the latest block number are all stored
+        let block_hash = block_header.compute_block_hash();
+        assert_eq!(
+            store.get_block_header_by_hash(block_hash).unwrap(),
+            Some(block_header)
+        );
+        assert_eq!(store.get_receipt(20, 0).unwrap(), Some(receipt));
+        assert_eq!(store.get_latest_block_number().unwrap(), Some(20));
+        assert_eq!(store.get_account_info(address).unwrap(), update.info);
+        assert_eq!(store.get_state_root().unwrap(), expected_state_root);
+        // The state before the block is still available
+        assert_eq!(store.get_account_info_at_block(19, address).unwrap(), None);
+    }
+
+    fn test_reads_during_write(store: Store) {
+        // Reads don't wait for writes in progress, so this would never finish if they did
+        let _writer = store.writer.lock().unwrap();
+        let other_store = store.clone();
+        let handle = thread::spawn(move || other_store.get_latest_block_number());
+        assert!(handle.join().unwrap().is_ok());
+    }
+
+    fn test_reorg(store: Store) {
+        let (base_header, block_body) = create_block_for_testing();
+        let new_block = |parent: &BlockHeader, timestamp: u64, body: BlockBody| Block {
+            header: BlockHeader {
+                parent_hash: parent.compute_block_hash(),
+                number: parent.number + 1,
+                timestamp,
+                ..base_header.clone()
+            },
+            body,
+        };
+        let block_30 = Block {
+            header: BlockHeader {
+                number: 30,
+                ..base_header.clone()
+            },
+            body: BlockBody::empty(),
+        };
+        store
+            .add_executed_block(block_30.clone(), vec![], &[])
+            .unwrap();
+        let state_root = store.get_state_root().unwrap();
+
+        // Block 31 creates an account and includes the transactions
+        let block_31 = new_block(&block_30.header, 1, block_body.clone());
+        let address = Address::random();
+        let update = AccountUpdate {
+            address,
+            info: Some(new_account_info(Bytes::new(), 10.into(), 1)),
+            added_storage: HashMap::from([(H256::random(), H256::random())]),
+            ..Default::default()
+        };
+        store
+            .add_executed_block(block_31.clone(), vec![], &[update])
+            .unwrap();
+        let transaction_hash = block_body.transactions[0].compute_hash();
+        assert_eq!(
+            store.get_transaction_location(transaction_hash).unwrap(),
+            Some((31, 0))
+        );
+
+        // A side chain forks off block 30 and includes the transactions one block later
+        let side_block_31 = new_block(&block_30.header, 2, BlockBody::empty());
+        let side_block_32 = new_block(&side_block_31.header, 3, block_body);
+        let side_head = side_block_32.header.compute_block_hash();
+        store.add_block(side_block_31.clone()).unwrap();
+        store.add_block(side_block_32.clone()).unwrap();
+        // Side blocks are stored but not canonical
+        assert_eq!(
+            store.get_block_header(31).unwrap(),
+            Some(block_31.header.clone())
+        );
+        assert!(store.get_block_header(32).unwrap().is_none());
+
+        assert_eq!(store.reorg_to(side_head).unwrap(), 30);
+
+        assert_eq!(
+            store.get_block_header(31).unwrap(),
+            Some(side_block_31.header)
+        );
+        assert_eq!(
+            store.get_block_header(32).unwrap(),
+            Some(side_block_32.header)
+        );
+        assert_eq!(store.get_canonical_block_hash(32).unwrap(), Some(side_head));
+        // The replaced block is still stored
+        assert_eq!(
+            store
+                .get_block_header_by_hash(block_31.header.compute_block_hash())
+                .unwrap(),
+            Some(block_31.header)
+        );
+        assert_eq!(
+            store.get_transaction_location(transaction_hash).unwrap(),
+            Some((32, 0))
+        );
+        // The state is back at the end of the common ancestor
+        assert_eq!(store.get_latest_block_number().unwrap(), Some(30));
+        assert_eq!(store.get_state_root().unwrap(), state_root);
+        assert!(store.get_account_info(address).unwrap().is_none());
+
+        assert!(matches!(
+            store.reorg_to(H256::random()),
+            Err(StoreError::MissingBlock(_))
+        ));
+    }
+
+    fn test_unwind_to(store: Store) {
+        let (address_a, address_b) = (Address::random(), Address::random());
+        let (storage_key_a, storage_key_b, storage_key_c) =
+            (H256::random(), H256::random(), H256::random());
+        let info = new_account_info(Bytes::new(), 10.into(), 1);
+        let storage = HashMap::from([
+            (storage_key_a, H256::random()),
+            (storage_key_b, H256::random()),
+        ]);
+        // Block 41 creates an account with some storage
+        let updates = [
+            vec![AccountUpdate {
+                address: address_a,
+                info: Some(info.clone()),
+                added_storage: storage.clone(),
+                ..Default::default()
+            }],
+            // Block 42 modifies the account, updates a slot, clears another one, sets a new one
+            // and creates another account
+            vec![
+                AccountUpdate {
+                    address: address_a,
+                    info: Some(new_account_info(Bytes::new(), 20.into(), 2)),
+                    added_storage: HashMap::from([
+                        (storage_key_a, H256::random()),
+                        (storage_key_b, H256::zero()),
+                        (storage_key_c, H256::random()),
+                    ]),
+                    ..Default::default()
+                },
+                AccountUpdate {
+                    address: address_b,
+                    info: Some(new_account_info(Bytes::new(), 5.into(), 0)),
+                    ..Default::default()
+                },
+            ],
+            // Block 43 destroys the account and creates it again with a different storage
+            vec![AccountUpdate {
+                info: Some(new_account_info(Bytes::new(), 1.into(), 0)),
+                added_storage: HashMap::from([(H256::random(), H256::random())]),
+                ..AccountUpdate::removed(address_a)
+            }],
+        ];
+        let mut state_root = None;
+        for (block_number, updates) in (41..=43).zip(updates) {
+            store.apply_account_updates(block_number, &updates).unwrap();
+            store.update_latest_block_number(block_number).unwrap();
+            state_root.get_or_insert(store.get_state_root().unwrap());
+        }
+        // Cleared slots are not stored
+        assert!(store
+            .get_storage_at(address_a, storage_key_b)
+            .unwrap()
+            .is_none());
+
+        store.unwind_to(41).unwrap();
+
+        assert_eq!(store.get_latest_block_number().unwrap(), Some(41));
+        assert_eq!(store.get_account_info(address_a).unwrap(), Some(info));
+        assert_eq!(
+            store.engine.get_account_storage(address_a).unwrap(),
+            storage
+        );
+        assert!(store.get_account_info(address_b).unwrap().is_none());
+        assert_eq!(Some(store.get_state_root().unwrap()), state_root);
+        // The change sets of the unwound blocks are gone
+        assert!(store
+            .engine
+            .get_block_account_info_history(42)
+            .unwrap()
+            .is_empty());
+
+        assert!(matches!(
+            store.unwind_to(42),
+            Err(StoreError::StateNotAvailable(42))
+        ));
+    }
+
+    fn test_pruning(mut store: Store) {
+        store.set_pruning_mode(PruningMode::Distance(2));
+        let (base_header, block_body) = create_block_for_testing();
+        let receipt = Receipt {
+            tx_type: TxType::EIP2930,
+            succeeded: true,
+            cumulative_gas_used: 1747,
+            bloom: Bloom::random(),
+            logs: vec![],
+        };
+        let mut block_hashes = HashMap::new();
+        for block_number in 50..=54 {
+            // Only the first block includes transactions
+            let body = if block_number == 50 {
+                block_body.clone()
+            } else {
+                BlockBody::empty()
+            };
+            let block = Block {
+                header: BlockHeader {
+                    number: block_number,
+                    ..base_header.clone()
+                },
+                body,
+            };
+            block_hashes.insert(block_number, block.header.compute_block_hash());
+            store
+                .add_executed_block(block, vec![receipt.clone()], &[])
+                .unwrap();
+        }
+        let transaction_hash = block_body.transactions[0].compute_hash();
+
+        // Blocks more than 2 blocks before the latest one are pruned, except for their headers
+        assert_eq!(store.get_pruned_block_number().unwrap(), Some(51));
+        assert!(store.get_block_body(50).unwrap().is_none());
+        assert!(store.get_receipt(50, 0).unwrap().is_none());
+        assert!(store
+            .get_transaction_location(transaction_hash)
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            store.get_canonical_block_hash(50).unwrap(),
+            Some(block_hashes[&50])
+        );
+        assert!(store.get_block_header(50).unwrap().is_some());
+        assert!(matches!(
+            store.check_block_not_pruned(50),
+            Err(StoreError::PrunedBlock(50))
+        ));
+        // Recent blocks are kept
+        for block_number in 52..=54 {
+            assert!(store.check_block_not_pruned(block_number).is_ok());
+            assert!(store.get_block_body(block_number).unwrap().is_some());
+            assert_eq!(
+                store.get_receipt(block_number, 0).unwrap(),
+                Some(receipt.clone())
+            );
+        }
+
+        // Nothing is pruned in archive mode
+        store.set_pruning_mode(PruningMode::Archive);
+        let block = Block {
+            header: BlockHeader {
+                number: 55,
+                ..base_header
+            },
+            body: BlockBody::empty(),
+        };
+        store.add_executed_block(block, vec![receipt], &[]).unwrap();
+        assert_eq!(store.get_pruned_block_number().unwrap(), Some(51));
+        assert!(store.get_block_body(52).unwrap().is_some());
     }
 }
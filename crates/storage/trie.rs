@@ -0,0 +1,637 @@
+//! Merkle Patricia Trie whose nodes are persisted in the store under the hash of their encoding
+//! Opening a trie from its root hash only loads the nodes along the paths that are accessed, so
+//! updating it and computing its new root takes time proportional to the number of changes
+
+use std::borrow::Cow;
+
+use ethereum_rust_core::rlp::{
+    constants::RLP_NULL,
+    decode::{decode_rlp_item, get_item_with_prefix},
+    encode::RLPEncode,
+    error::RLPDecodeError,
+    structs::Encoder,
+};
+use ethereum_types::H256;
+use sha3::{Digest, Keccak256};
+
+use crate::{engines::api::StoreEngine, error::StoreError};
+
+/// Root hash of an empty trie, that is, the keccak hash of the rlp encoding of an empty string
+pub const EMPTY_TRIE_HASH: H256 = H256([
+    0x56, 0xe8, 0x1f, 0x17, 0x1b, 0xcc, 0x55, 0xa6, 0xff, 0x83, 0x45, 0xe6, 0x92, 0xc0, 0xf8, 0x6e,
+    0x5b, 0x48, 0xe0, 0x1b, 0x99, 0x6c, 0xad, 0xc0, 0x01, 0x62, 0x2f, 0xb5, 0xe3, 0x63, 0xb4, 0x21,
+]);
+
+/// Encoded trie nodes keyed by their hash
+pub type TrieNodes = Vec<(H256, Vec<u8>)>;
+
+/// Trie node, paths are expressed in nibbles and are relative to the node's position in the trie
+#[derive(Debug, Clone)]
+enum Node {
+    Leaf {
+        path: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Extension {
+        path: Vec<u8>,
+        child: NodeRef,
+    },
+    Branch {
+        children: Box<[NodeRef; 16]>,
+        value: Option<Vec<u8>>,
+    },
+}
+
+/// Reference from a node (or the trie itself) to a child node
+#[derive(Debug, Clone, Default)]
+enum NodeRef {
+    #[default]
+    Empty,
+    /// The node is stored under its hash and has not been loaded
+    Hash(H256),
+    /// The node is held in memory, either because it was modified or because it is small enough
+    /// to be embedded in its parent
+    Node(Box<Node>),
+}
+
+impl From<Node> for NodeRef {
+    fn from(node: Node) -> Self {
+        NodeRef::Node(Box::new(node))
+    }
+}
+
+impl Node {
+    /// Prepends the given path to the node, adding an extension in front of it if it is a branch
+    fn with_prefix(prefix: &[u8], node: Node) -> Node {
+        if prefix.is_empty() {
+            return node;
+        }
+        match node {
+            Node::Leaf { path, value } => Node::Leaf {
+                path: [prefix, &path].concat(),
+                value,
+            },
+            Node::Extension { path, child } => Node::Extension {
+                path: [prefix, &path].concat(),
+                child,
+            },
+            branch => Node::Extension {
+                path: prefix.to_vec(),
+                child: branch.into(),
+            },
+        }
+    }
+}
+
+/// A Merkle Patricia Trie backed by the nodes stored in a [`StoreEngine`]
+/// Modifications are kept in memory until the trie is committed
+pub struct Trie<'a> {
+    engine: &'a dyn StoreEngine,
+    root: NodeRef,
+}
+
+impl<'a> Trie<'a> {
+    /// Opens the trie with the given root hash, whose nodes must be stored in the engine
+    pub fn open(engine: &'a dyn StoreEngine, root_hash: H256) -> Self {
+        let root = if root_hash == EMPTY_TRIE_HASH {
+            NodeRef::Empty
+        } else {
+            NodeRef::Hash(root_hash)
+        };
+        Self { engine, root }
+    }
+
+    /// Obtain the value stored under the given key
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, StoreError> {
+        self.get_at(&self.root, &to_nibbles(key))
+    }
+
+    /// Stores a value under the given key, replacing the previous one
+    pub fn insert(&mut self, key: &[u8], value: Vec<u8>) -> Result<(), StoreError> {
+        let root = std::mem::take(&mut self.root);
+        self.root = self.insert_at(root, &to_nibbles(key), value)?.into();
+        Ok(())
+    }
+
+    /// Removes the value stored under the given key, if any
+    pub fn remove(&mut self, key: &[u8]) -> Result<(), StoreError> {
+        let root = std::mem::take(&mut self.root);
+        self.root = self.remove_at(root, &to_nibbles(key))?;
+        Ok(())
+    }
+
+    /// Obtain the encoded nodes along the path to the given key, starting from the root, which
+    /// prove the value stored under the key or its absence
+    /// Only stored nodes are included, so the trie must not have uncommitted modifications
+    pub fn get_proof(&self, key: &[u8]) -> Result<Vec<Vec<u8>>, StoreError> {
+        let mut proof = Vec::new();
+        self.get_proof_at(&self.root, &to_nibbles(key), &mut proof)?;
+        Ok(proof)
+    }
+
+    /// Computes the root hash of the trie
+    /// Returns it along with the encoded nodes created since the trie was opened, which must be
+    /// stored in order to open the trie again from its root hash
+    pub fn commit(self) -> (H256, TrieNodes) {
+        let mut nodes = Vec::new();
+        let root_hash = match self.root {
+            NodeRef::Empty => EMPTY_TRIE_HASH,
+            NodeRef::Hash(hash) => hash,
+            // The root node is always stored by its hash, even if it is small enough to be embedded
+            NodeRef::Node(node) => {
+                let encoded = encode_node(&node, &mut nodes);
+                let hash = keccak(&encoded);
+                nodes.push((hash, encoded));
+                hash
+            }
+        };
+        (root_hash, nodes)
+    }
+
+    fn get_at(&self, node_ref: &NodeRef, path: &[u8]) -> Result<Option<Vec<u8>>, StoreError> {
+        let Some(node) = self.resolve(node_ref)? else {
+            return Ok(None);
+        };
+        match node.as_ref() {
+            Node::Leaf {
+                path: leaf_path,
+                value,
+            } => Ok((leaf_path == path).then(|| value.clone())),
+            Node::Extension {
+                path: extension_path,
+                child,
+            } => match path.strip_prefix(extension_path.as_slice()) {
+                Some(rest) => self.get_at(child, rest),
+                None => Ok(None),
+            },
+            Node::Branch { children, value } => match path.split_first() {
+                Some((nibble, rest)) => self.get_at(&children[*nibble as usize], rest),
+                None => Ok(value.clone()),
+            },
+        }
+    }
+
+    fn get_proof_at(
+        &self,
+        node_ref: &NodeRef,
+        path: &[u8],
+        proof: &mut Vec<Vec<u8>>,
+    ) -> Result<(), StoreError> {
+        let node = match node_ref {
+            NodeRef::Empty => return Ok(()),
+            NodeRef::Hash(hash) => {
+                let encoded = self.load_encoded_node(*hash)?;
+                let node = decode_node(&encoded).map_err(|_| StoreError::DecodeError)?;
+                proof.push(encoded);
+                Cow::Owned(node)
+            }
+            // Embedded nodes are already part of their parent's encoding
+            NodeRef::Node(node) => Cow::Borrowed(node.as_ref()),
+        };
+        match node.as_ref() {
+            Node::Leaf { .. } => Ok(()),
+            Node::Extension {
+                path: extension_path,
+                child,
+            } => match path.strip_prefix(extension_path.as_slice()) {
+                Some(rest) => self.get_proof_at(child, rest, proof),
+                None => Ok(()),
+            },
+            Node::Branch { children, .. } => match path.split_first() {
+                Some((nibble, rest)) => self.get_proof_at(&children[*nibble as usize], rest, proof),
+                None => Ok(()),
+            },
+        }
+    }
+
+    fn insert_at(
+        &self,
+        node_ref: NodeRef,
+        path: &[u8],
+        value: Vec<u8>,
+    ) -> Result<Node, StoreError> {
+        let Some(node) = self.resolve_owned(node_ref)? else {
+            return Ok(Node::Leaf {
+                path: path.to_vec(),
+                value,
+            });
+        };
+        match node {
+            Node::Leaf {
+                path: leaf_path,
+                value: leaf_value,
+            } => {
+                if leaf_path == path {
+                    return Ok(Node::Leaf {
+                        path: leaf_path,
+                        value,
+                    });
+                }
+                // Both values are moved into a branch placed where their paths diverge
+                let common = common_prefix_len(&leaf_path, path);
+                let branch = self.insert_at(empty_branch(), &leaf_path[common..], leaf_value)?;
+                let branch = self.insert_at(branch.into(), &path[common..], value)?;
+                Ok(Node::with_prefix(&path[..common], branch))
+            }
+            Node::Extension {
+                path: extension_path,
+                child,
+            } => {
+                let common = common_prefix_len(&extension_path, path);
+                if common == extension_path.len() {
+                    let child = self.insert_at(child, &path[common..], value)?;
+                    return Ok(Node::Extension {
+                        path: extension_path,
+                        child: child.into(),
+                    });
+                }
+                // The extension is split by a branch where the paths diverge
+                let remaining_path = &extension_path[common + 1..];
+                let mut children: [NodeRef; 16] = Default::default();
+                children[extension_path[common] as usize] = if remaining_path.is_empty() {
+                    child
+                } else {
+                    Node::Extension {
+                        path: remaining_path.to_vec(),
+                        child,
+                    }
+                    .into()
+                };
+                let branch = Node::Branch {
+                    children: Box::new(children),
+                    value: None,
+                };
+                let branch = self.insert_at(branch.into(), &path[common..], value)?;
+                Ok(Node::with_prefix(&path[..common], branch))
+            }
+            Node::Branch {
+                mut children,
+                value: branch_value,
+            } => match path.split_first() {
+                Some((nibble, rest)) => {
+                    let child = std::mem::take(&mut children[*nibble as usize]);
+                    children[*nibble as usize] = self.insert_at(child, rest, value)?.into();
+                    Ok(Node::Branch {
+                        children,
+                        value: branch_value,
+                    })
+                }
+                None => Ok(Node::Branch {
+                    children,
+                    value: Some(value),
+                }),
+            },
+        }
+    }
+
+    fn remove_at(&self, node_ref: NodeRef, path: &[u8]) -> Result<NodeRef, StoreError> {
+        let Some(node) = self.resolve_owned(node_ref)? else {
+            return Ok(NodeRef::Empty);
+        };
+        match node {
+            Node::Leaf {
+                path: leaf_path, ..
+            } if leaf_path == path => Ok(NodeRef::Empty),
+            Node::Extension {
+                path: extension_path,
+                child,
+            } if path.starts_with(&extension_path) => {
+                let child = self.remove_at(child, &path[extension_path.len()..])?;
+                // The child may have been collapsed into a leaf or extension, which absorb the path
+                Ok(match self.resolve_owned(child)? {
+                    Some(child) => Node::with_prefix(&extension_path, child).into(),
+                    None => NodeRef::Empty,
+                })
+            }
+            Node::Branch {
+                mut children,
+                mut value,
+            } => {
+                match path.split_first() {
+                    Some((nibble, rest)) => {
+                        let child = std::mem::take(&mut children[*nibble as usize]);
+                        children[*nibble as usize] = self.remove_at(child, rest)?;
+                    }
+                    None => value = None,
+                }
+                self.collapse_branch(children, value)
+            }
+            node => Ok(node.into()),
+        }
+    }
+
+    /// Replaces a branch that is left with a single child and no value, or with no children, by
+    /// the equivalent leaf or extension
+    fn collapse_branch(
+        &self,
+        mut children: Box<[NodeRef; 16]>,
+        value: Option<Vec<u8>>,
+    ) -> Result<NodeRef, StoreError> {
+        let used_children: Vec<usize> = children
+            .iter()
+            .enumerate()
+            .filter(|(_, child)| !matches!(child, NodeRef::Empty))
+            .map(|(index, _)| index)
+            .collect();
+        match (used_children.as_slice(), value) {
+            ([], None) => Ok(NodeRef::Empty),
+            ([], Some(value)) => Ok(Node::Leaf {
+                path: vec![],
+                value,
+            }
+            .into()),
+            ([index], None) => {
+                let child = std::mem::take(&mut children[*index]);
+                Ok(match self.resolve_owned(child)? {
+                    Some(child) => Node::with_prefix(&[*index as u8], child).into(),
+                    None => NodeRef::Empty,
+                })
+            }
+            (_, value) => Ok(Node::Branch { children, value }.into()),
+        }
+    }
+
+    /// Obtain the node referenced, loading it from the store if needed
+    fn resolve<'b>(&self, node_ref: &'b NodeRef) -> Result<Option<Cow<'b, Node>>, StoreError> {
+        match node_ref {
+            NodeRef::Empty => Ok(None),
+            NodeRef::Hash(hash) => Ok(Some(Cow::Owned(self.load_node(*hash)?))),
+            NodeRef::Node(node) => Ok(Some(Cow::Borrowed(node))),
+        }
+    }
+
+    fn resolve_owned(&self, node_ref: NodeRef) -> Result<Option<Node>, StoreError> {
+        match node_ref {
+            NodeRef::Empty => Ok(None),
+            NodeRef::Hash(hash) => Ok(Some(self.load_node(hash)?)),
+            NodeRef::Node(node) => Ok(Some(*node)),
+        }
+    }
+
+    fn load_node(&self, hash: H256) -> Result<Node, StoreError> {
+        decode_node(&self.load_encoded_node(hash)?).map_err(|_| StoreError::DecodeError)
+    }
+
+    fn load_encoded_node(&self, hash: H256) -> Result<Vec<u8>, StoreError> {
+        self.engine
+            .get_trie_node(hash)?
+            .ok_or(StoreError::MissingTrieNode(hash))
+    }
+}
+
+fn empty_branch() -> NodeRef {
+    Node::Branch {
+        children: Default::default(),
+        value: None,
+    }
+    .into()
+}
+
+/// Encodes the node, adding the encoding of each of its descendants that is not embedded in its
+/// parent to the given nodes
+fn encode_node(node: &Node, nodes: &mut TrieNodes) -> Vec<u8> {
+    let mut buf = Vec::new();
+    match node {
+        Node::Leaf { path, value } => Encoder::new(&mut buf)
+            .encode_raw(&encode_path(path, true).as_slice().encode_to_vec())
+            .encode_raw(&value.as_slice().encode_to_vec())
+            .finish(),
+        Node::Extension { path, child } => Encoder::new(&mut buf)
+            .encode_raw(&encode_path(path, false).as_slice().encode_to_vec())
+            .encode_raw(&encode_child(child, nodes))
+            .finish(),
+        Node::Branch { children, value } => {
+            let mut encoder = Encoder::new(&mut buf);
+            for child in children.iter() {
+                encoder = encoder.encode_raw(&encode_child(child, nodes));
+            }
+            let value = value.as_deref().unwrap_or_default();
+            encoder.encode_raw(&value.encode_to_vec()).finish()
+        }
+    }
+    buf
+}
+
+/// Encodes the reference to a child node, which is either the node's hash or the node itself if
+/// its encoding is shorter than a hash
+fn encode_child(child: &NodeRef, nodes: &mut TrieNodes) -> Vec<u8> {
+    match child {
+        NodeRef::Empty => vec![RLP_NULL],
+        NodeRef::Hash(hash) => hash.encode_to_vec(),
+        NodeRef::Node(node) => {
+            let encoded = encode_node(node, nodes);
+            if encoded.len() < 32 {
+                return encoded;
+            }
+            let hash = keccak(&encoded);
+            nodes.push((hash, encoded));
+            hash.encode_to_vec()
+        }
+    }
+}
+
+fn decode_node(encoded: &[u8]) -> Result<Node, RLPDecodeError> {
+    let (true, mut payload, _) = decode_rlp_item(encoded)? else {
+        return Err(RLPDecodeError::UnexpectedString);
+    };
+    let mut items = Vec::new();
+    while !payload.is_empty() {
+        let (item, rest) = get_item_with_prefix(payload)?;
+        items.push(item);
+        payload = rest;
+    }
+    match items.as_slice() {
+        [path, second] => {
+            let (path, is_leaf) = decode_path(decode_bytes(path)?)?;
+            Ok(if is_leaf {
+                Node::Leaf {
+                    path,
+                    value: decode_bytes(second)?.to_vec(),
+                }
+            } else {
+                Node::Extension {
+                    path,
+                    child: decode_child(second)?,
+                }
+            })
+        }
+        [encoded_children @ .., value] if encoded_children.len() == 16 => {
+            let mut children: [NodeRef; 16] = Default::default();
+            for (child, encoded_child) in children.iter_mut().zip(encoded_children) {
+                *child = decode_child(encoded_child)?;
+            }
+            let value = decode_bytes(value)?;
+            Ok(Node::Branch {
+                children: Box::new(children),
+                value: (!value.is_empty()).then(|| value.to_vec()),
+            })
+        }
+        _ => Err(RLPDecodeError::MalformedData),
+    }
+}
+
+fn decode_child(encoded: &[u8]) -> Result<NodeRef, RLPDecodeError> {
+    match decode_rlp_item(encoded)? {
+        // Embedded node
+        (true, _, _) => Ok(decode_node(encoded)?.into()),
+        (false, [], _) => Ok(NodeRef::Empty),
+        (false, hash, _) if hash.len() == 32 => Ok(NodeRef::Hash(H256::from_slice(hash))),
+        _ => Err(RLPDecodeError::MalformedData),
+    }
+}
+
+fn decode_bytes(encoded: &[u8]) -> Result<&[u8], RLPDecodeError> {
+    match decode_rlp_item(encoded)? {
+        (false, payload, _) => Ok(payload),
+        (true, _, _) => Err(RLPDecodeError::UnexpectedList),
+    }
+}
+
+/// Encodes a path using hex-prefix encoding, whose first nibble flags whether the node is a
+/// leaf and whether the path has an odd length
+fn encode_path(path: &[u8], is_leaf: bool) -> Vec<u8> {
+    let flag = 2 * is_leaf as u8 + (path.len() % 2) as u8;
+    let mut encoded = Vec::with_capacity(path.len() / 2 + 1);
+    let even_path = if path.len() % 2 == 1 {
+        encoded.push(flag << 4 | path[0]);
+        &path[1..]
+    } else {
+        encoded.push(flag << 4);
+        path
+    };
+    encoded.extend(even_path.chunks(2).map(|pair| pair[0] << 4 | pair[1]));
+    encoded
+}
+
+/// Decodes a hex-prefix encoded path, returning it along with whether it belongs to a leaf
+fn decode_path(encoded: &[u8]) -> Result<(Vec<u8>, bool), RLPDecodeError> {
+    let (first, rest) = encoded.split_first().ok_or(RLPDecodeError::InvalidLength)?;
+    let flag = first >> 4;
+    if flag > 3 {
+        return Err(RLPDecodeError::MalformedData);
+    }
+    let mut path = Vec::with_capacity(rest.len() * 2 + 1);
+    if flag & 1 == 1 {
+        path.push(first & 0x0f);
+    }
+    path.extend(to_nibbles(rest));
+    Ok((path, flag & 2 == 2))
+}
+
+fn to_nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0x0f])
+        .collect()
+}
+
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+pub fn keccak(data: &[u8]) -> H256 {
+    H256(Keccak256::digest(data).into())
+}
+
+#[cfg(all(test, feature = "in_memory"))]
+mod tests {
+    use std::{collections::BTreeMap, str::FromStr};
+
+    use ethereum_rust_core::types::compute_storage_root;
+    use ethereum_types::U256;
+
+    use super::*;
+    use crate::engines::in_memory::Store;
+
+    fn root_of(engine: &dyn StoreEngine, entries: &[(&str, &str)]) -> H256 {
+        let mut trie = Trie::open(engine, EMPTY_TRIE_HASH);
+        for (key, value) in entries {
+            trie.insert(key.as_bytes(), value.as_bytes().to_vec())
+                .unwrap();
+        }
+        trie.commit().0
+    }
+
+    #[test]
+    fn empty_trie_root() {
+        let engine = Store::new().unwrap();
+        assert_eq!(root_of(&engine, &[]), keccak(&[RLP_NULL]));
+    }
+
+    #[test]
+    fn known_roots() {
+        let engine = Store::new().unwrap();
+        let puppy = [
+            ("do", "verb"),
+            ("horse", "stallion"),
+            ("doge", "coin"),
+            ("dog", "puppy"),
+        ];
+        assert_eq!(
+            root_of(&engine, &puppy),
+            H256::from_str("0x5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84")
+                .unwrap()
+        );
+        let dogs = [
+            ("doe", "reindeer"),
+            ("dog", "puppy"),
+            ("dogglesworth", "cat"),
+        ];
+        assert_eq!(
+            root_of(&engine, &dogs),
+            H256::from_str("0x8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn matches_storage_root() {
+        let engine = Store::new().unwrap();
+        let storage: BTreeMap<H256, H256> = (1..100)
+            .map(|i| (H256::random(), H256::from_low_u64_be(i)))
+            .collect();
+        let mut trie = Trie::open(&engine, EMPTY_TRIE_HASH);
+        for (key, value) in &storage {
+            let value = U256::from_big_endian(value.as_bytes()).encode_to_vec();
+            trie.insert(keccak(key.as_bytes()).as_bytes(), value)
+                .unwrap();
+        }
+        assert_eq!(trie.commit().0, compute_storage_root(&storage));
+    }
+
+    #[test]
+    fn update_committed_trie() {
+        let engine = Store::new().unwrap();
+        let keys: Vec<H256> = (0..50).map(|_| H256::random()).collect();
+        let mut trie = Trie::open(&engine, EMPTY_TRIE_HASH);
+        for key in &keys[..40] {
+            trie.insert(key.as_bytes(), key.as_bytes().to_vec())
+                .unwrap();
+        }
+        let (root, nodes) = trie.commit();
+        engine.add_trie_nodes(nodes).unwrap();
+
+        // Adding and then removing values from the stored trie yields the same root
+        let mut trie = Trie::open(&engine, root);
+        for key in &keys[40..] {
+            trie.insert(key.as_bytes(), vec![1]).unwrap();
+        }
+        assert_eq!(trie.get(keys[45].as_bytes()).unwrap(), Some(vec![1]));
+        for key in &keys[40..] {
+            trie.remove(key.as_bytes()).unwrap();
+        }
+        assert_eq!(trie.get(keys[45].as_bytes()).unwrap(), None);
+        assert_eq!(trie.commit().0, root);
+
+        // Removing every value yields an empty trie
+        let mut trie = Trie::open(&engine, root);
+        assert_eq!(
+            trie.get(keys[0].as_bytes()).unwrap(),
+            Some(keys[0].as_bytes().to_vec())
+        );
+        for key in &keys[..40] {
+            trie.remove(key.as_bytes()).unwrap();
+        }
+        assert_eq!(trie.commit().0, EMPTY_TRIE_HASH);
+    }
+}